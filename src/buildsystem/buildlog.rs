@@ -0,0 +1,170 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{buildsystem::BuildGraph, error::ApplicationError};
+
+/// Where the build log lives, relative to the config file's directory
+pub const BUILD_LOG: &str = ".gftools-builder.buildlog";
+
+/// What each target was last successfully built from
+///
+/// We record a hash of the operations which produced each target and of
+/// every source file it read, so that we can later tell which targets a
+/// change to the sources or the config would affect.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildLog {
+    pub targets: BTreeMap<String, TargetRecord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetRecord {
+    /// A hash of the identifiers of the operations which build the target
+    pub recipe: String,
+    /// A hash of each source file (or source directory) the target reads
+    pub inputs: BTreeMap<String, String>,
+}
+
+/// Why a target would be rebuilt
+#[derive(Debug, Clone, PartialEq)]
+pub enum DirtyReason {
+    NeverBuilt,
+    OutputMissing,
+    RecipeChanged,
+    InputChanged(String),
+    InputAdded(String),
+    InputRemoved(String),
+}
+
+impl Display for DirtyReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DirtyReason::NeverBuilt => write!(f, "it has not been built"),
+            DirtyReason::OutputMissing => write!(f, "it is missing"),
+            DirtyReason::RecipeChanged => write!(f, "the steps which build it have changed"),
+            DirtyReason::InputChanged(path) => write!(f, "{path} has changed"),
+            DirtyReason::InputAdded(path) => write!(f, "it now reads {path}"),
+            DirtyReason::InputRemoved(path) => write!(f, "it no longer reads {path}"),
+        }
+    }
+}
+
+impl BuildLog {
+    /// Read the build log in the current directory, or start an empty one
+    pub fn load() -> Result<Self, ApplicationError> {
+        match std::fs::read_to_string(BUILD_LOG) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| {
+                ApplicationError::Other(format!("Could not parse build log {BUILD_LOG}: {e}"))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), ApplicationError> {
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|e| ApplicationError::Other(format!("Could not serialize build log: {e}")))?;
+        std::fs::write(BUILD_LOG, text)?;
+        Ok(())
+    }
+
+    /// Record what the given targets were built from
+    pub fn record(&mut self, records: BTreeMap<String, TargetRecord>) {
+        self.targets.extend(records);
+    }
+
+    /// Work out what each target in the graph would be built from now
+    pub fn fingerprint(graph: &BuildGraph) -> BTreeMap<String, TargetRecord> {
+        let mut hashes: HashMap<String, String> = HashMap::new();
+        graph
+            .target_dependencies()
+            .into_iter()
+            .map(|(target, (operations, sources))| {
+                let recipe = format!("{:x}", Sha256::digest(operations.join("\n")));
+                let inputs = sources
+                    .into_iter()
+                    .map(|source| {
+                        let hash = hashes
+                            .entry(source.clone())
+                            .or_insert_with(|| hash_path(Path::new(&source)))
+                            .clone();
+                        (source, hash)
+                    })
+                    .collect();
+                (target, TargetRecord { recipe, inputs })
+            })
+            .collect()
+    }
+
+    /// Every target in the graph which would be rebuilt, with the reasons why
+    pub fn dirty_targets(&self, graph: &BuildGraph) -> BTreeMap<String, Vec<DirtyReason>> {
+        let mut dirty = BTreeMap::new();
+        for (target, now) in Self::fingerprint(graph) {
+            let reasons = match self.targets.get(&target) {
+                None => vec![DirtyReason::NeverBuilt],
+                Some(then) => {
+                    let mut reasons = vec![];
+                    if !Path::new(&target).exists() {
+                        reasons.push(DirtyReason::OutputMissing);
+                    }
+                    if then.recipe != now.recipe {
+                        reasons.push(DirtyReason::RecipeChanged);
+                    }
+                    for (input, hash) in &now.inputs {
+                        match then.inputs.get(input) {
+                            None => reasons.push(DirtyReason::InputAdded(input.clone())),
+                            Some(old) if old != hash => {
+                                reasons.push(DirtyReason::InputChanged(input.clone()))
+                            }
+                            _ => {}
+                        }
+                    }
+                    for input in then.inputs.keys() {
+                        if !now.inputs.contains_key(input) {
+                            reasons.push(DirtyReason::InputRemoved(input.clone()));
+                        }
+                    }
+                    reasons
+                }
+            };
+            if !reasons.is_empty() {
+                dirty.insert(target, reasons);
+            }
+        }
+        dirty
+    }
+}
+
+/// Hash a file, or everything inside a directory (such as a UFO)
+///
+/// A missing or unreadable path gets an empty hash, so it counts as changed.
+fn hash_path(path: &Path) -> String {
+    let mut hasher = Sha256::new();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        if path.is_dir() {
+            let Ok(entries) = std::fs::read_dir(&path) else {
+                return String::new();
+            };
+            let mut children: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+            // Pop in name order, so the hash doesn't depend on directory order
+            children.sort_by(|a, b| b.cmp(a));
+            pending.extend(children);
+        } else {
+            let Ok(contents) = std::fs::read(&path) else {
+                return String::new();
+            };
+            hasher.update(path.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            hasher.update(&contents);
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
@@ -0,0 +1,186 @@
+//! The environment the external tools a step runs see
+//!
+//! A recipe step can give `env:` to set variables for the shell commands it
+//! runs, for example to quieten Python warnings or to pin a timestamp, and
+//! `cwd:` to run them somewhere other than the config file's directory.
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Output,
+    sync::RwLock,
+};
+
+use serde_json::Value;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// Variables set for every step's shell commands, such as `SOURCE_DATE_EPOCH`
+/// in a reproducible build
+static BUILD_ENV: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+
+thread_local! {
+    /// The environment of the step executing on this thread
+    static STEP_ENV: RefCell<BTreeMap<String, String>> = const { RefCell::new(BTreeMap::new()) };
+    /// The working directory of the step executing on this thread
+    static STEP_CWD: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Set the variables every step's shell commands get
+pub(crate) fn set_build_env(env: BTreeMap<String, String>) {
+    if let Ok(mut build_env) = BUILD_ENV.write() {
+        *build_env = env;
+    }
+}
+
+/// The variables to set for shell commands run on this thread; a step's own
+/// `env:` wins over the build's
+pub(crate) fn step_env() -> BTreeMap<String, String> {
+    let mut env = BUILD_ENV.read().map(|env| env.clone()).unwrap_or_default();
+    STEP_ENV.with(|step| env.extend(step.borrow().clone()));
+    env
+}
+
+/// Where shell commands run on this thread should run, if not in the
+/// builder's own working directory
+pub(crate) fn step_cwd() -> Option<PathBuf> {
+    STEP_CWD.with(|cwd| cwd.borrow().clone())
+}
+
+/// Read a step's `env:` mapping; numbers and booleans are taken as strings
+pub(crate) fn parse_env(value: Value) -> Result<BTreeMap<String, String>, ApplicationError> {
+    let Value::Object(map) = value else {
+        return Err(ApplicationError::InvalidRecipe(
+            "`env` must be a mapping of variable names to values".to_string(),
+        ));
+    };
+    map.into_iter()
+        .map(|(name, value)| match value {
+            Value::String(value) => Ok((name, value)),
+            Value::Number(_) | Value::Bool(_) => Ok((name, value.to_string())),
+            _ => Err(ApplicationError::InvalidRecipe(format!(
+                "the value of `{name}` in `env` must be a string"
+            ))),
+        })
+        .collect()
+}
+
+/// Read a step's `cwd:`, which is relative to the config file's directory
+pub(crate) fn parse_cwd(value: Value) -> Result<PathBuf, ApplicationError> {
+    match value {
+        Value::String(cwd) if !cwd.is_empty() => Ok(PathBuf::from(cwd)),
+        _ => Err(ApplicationError::InvalidRecipe(
+            "`cwd` must be the path of a directory".to_string(),
+        )),
+    }
+}
+
+/// An operation whose shell commands run with extra environment variables,
+/// or in a different working directory
+pub(crate) struct WithEnvironment {
+    inner: Box<dyn Operation>,
+    env: BTreeMap<String, String>,
+    cwd: Option<PathBuf>,
+}
+
+impl WithEnvironment {
+    pub fn new(
+        inner: Box<dyn Operation>,
+        env: BTreeMap<String, String>,
+        cwd: Option<PathBuf>,
+    ) -> Self {
+        WithEnvironment { inner, env, cwd }
+    }
+}
+
+impl Operation for WithEnvironment {
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        if let Some(cwd) = &self.cwd
+            && !cwd.is_dir()
+        {
+            return Err(ApplicationError::Other(format!(
+                "Working directory {} does not exist",
+                cwd.display()
+            )));
+        }
+        let previous_env = STEP_ENV.with(|env| env.replace(self.env.clone()));
+        let previous_cwd = STEP_CWD.with(|cwd| cwd.replace(self.cwd.clone()));
+        let result = self.inner.execute(inputs, outputs);
+        STEP_ENV.with(|env| *env.borrow_mut() = previous_env);
+        STEP_CWD.with(|cwd| *cwd.borrow_mut() = previous_cwd);
+        result
+    }
+
+    fn description(&self) -> String {
+        self.inner.description()
+    }
+
+    fn shortname(&self) -> &str {
+        self.inner.shortname()
+    }
+
+    fn identifier(&self) -> String {
+        let mut identifier = self.inner.identifier();
+        if !self.env.is_empty() {
+            let env: Vec<String> = self
+                .env
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            identifier.push_str(&format!("-env:{}", env.join(",")));
+        }
+        if let Some(cwd) = &self.cwd {
+            identifier.push_str(&format!("-cwd:{}", cwd.display()));
+        }
+        identifier
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.inner.set_args(args);
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        self.inner.set_extra(extra);
+    }
+
+    fn hidden(&self) -> bool {
+        self.inner.hidden()
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        self.inner.input_kinds()
+    }
+
+    fn variadic_inputs(&self) -> bool {
+        self.inner.variadic_inputs()
+    }
+
+    fn reads_finished_targets(&self) -> bool {
+        self.inner.reads_finished_targets()
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        self.inner.output_kinds()
+    }
+}
+
+/// A file name to hand to a shell command
+///
+/// Paths in the recipe are relative to the config file's directory, so when
+/// the command runs somewhere else they're made absolute.
+pub(crate) fn command_path(path: &str) -> String {
+    if step_cwd().is_none() {
+        return path.to_string();
+    }
+    std::path::absolute(Path::new(path))
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string())
+}
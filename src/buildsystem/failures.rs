@@ -0,0 +1,88 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::error::ApplicationError;
+
+/// How many lines of a failed tool's output we keep for the report
+const OUTPUT_LINES: usize = 20;
+
+/// An operation which failed, and what we know about why
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Failure {
+    /// The targets which can't be built because of this
+    pub targets: Vec<String>,
+    pub operation: String,
+    pub description: String,
+    /// The command lines the operation ran, if it shelled out
+    pub commands: Vec<String>,
+    pub error: String,
+    /// The end of what the tool printed to stderr (or stdout, if it printed
+    /// nothing to stderr)
+    pub output: Vec<String>,
+    /// The error the operation returned
+    #[serde(skip)]
+    pub cause: Box<ApplicationError>,
+    /// Where the operation's temporary files were kept, with `--keep-temps`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scratch_dir: Option<String>,
+}
+
+impl Failure {
+    /// Keep the last few lines of what a tool printed
+    pub fn trim_output(stdout: &[u8], stderr: &[u8]) -> Vec<String> {
+        let text = if stderr.iter().any(|byte| !byte.is_ascii_whitespace()) {
+            stderr
+        } else {
+            stdout
+        };
+        let text = String::from_utf8_lossy(text);
+        let lines: Vec<&str> = text.trim_end().lines().collect();
+        lines[lines.len().saturating_sub(OUTPUT_LINES)..]
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    }
+}
+
+/// Every failure in a build, reported together at the end
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailureReport {
+    pub failures: Vec<Failure>,
+}
+
+impl FailureReport {
+    pub fn to_json(&self) -> Result<String, ApplicationError> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| ApplicationError::Other(format!("Could not serialize failures: {e}")))
+    }
+}
+
+impl Display for FailureReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let count = self.failures.len();
+        writeln!(
+            f,
+            "{count} operation{} failed:",
+            if count == 1 { "" } else { "s" }
+        )?;
+        for failure in &self.failures {
+            writeln!(f)?;
+            writeln!(f, "{}", failure.targets.join(", "))?;
+            writeln!(f, "  operation: {}", failure.description)?;
+            writeln!(f, "  error: {}", failure.error)?;
+            for command in &failure.commands {
+                writeln!(f, "  command: {command}")?;
+            }
+            for line in &failure.output {
+                writeln!(f, "  | {line}")?;
+            }
+            if let Some(dir) = &failure.scratch_dir {
+                writeln!(f, "  temporary files kept in: {dir}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,24 @@
-use std::{fmt::Display, path::Path, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use petgraph::{Graph, graph::NodeIndex, visit::EdgeRef};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::{
-    buildsystem::{Operation, OperationOutput, output::RawOperationOutput, sourcesink::SourceSink},
+    buildsystem::{
+        DataKind, Operation, OperationOutput, output::RawOperationOutput, sourcesink::SourceSink,
+    },
     error::ApplicationError,
-    operations::convert::{FileToBytes, PathToSourceFont},
+    operations::convert::{
+        BinaryFontToBytes, BytesToBinaryFont, BytesToSourceFont, FileToBytes, PathToSourceFont,
+        SourceFontToBytes, converter_named,
+    },
 };
 
 pub type BuildStep = Arc<Box<dyn Operation>>;
@@ -38,6 +51,58 @@ pub struct BuildGraph {
     pub sinks: Vec<NodeIndex>,
     /// Maps target names to their final operation node (before the sink)
     pub(crate) target_nodes: std::collections::HashMap<String, NodeIndex>,
+    /// The recipe step each operation node was made from, for serialization
+    steps: HashMap<NodeIndex, Value>,
+}
+
+/// A build graph in a form other tools can read; see [BuildGraph::to_json]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphJson {
+    pub nodes: Vec<NodeJson>,
+    /// The data passed along edges; edges which share an output refer to the same entry
+    pub outputs: Vec<OutputJson>,
+    pub edges: Vec<EdgeJson>,
+    /// The node which reads the recipe's source files
+    pub source: usize,
+    pub sinks: Vec<usize>,
+    /// Each target's final operation node
+    pub targets: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeJson {
+    pub id: usize,
+    pub shortname: String,
+    pub identifier: String,
+    pub description: String,
+    /// The recipe step this operation was made from; converters, sources
+    /// and sinks don't have one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<Value>,
+    pub input_kinds: Vec<String>,
+    pub output_kinds: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum OutputJson {
+    NamedFile { path: String },
+    TemporaryFile,
+    InMemoryBytes,
+    SourceFont,
+    BinaryFont,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EdgeJson {
+    pub from: usize,
+    pub to: usize,
+    pub slot: usize,
+    /// Index into the graph's outputs
+    pub output: usize,
 }
 
 impl BuildGraph {
@@ -52,7 +117,197 @@ impl BuildGraph {
             source,
             sinks,
             target_nodes: std::collections::HashMap::new(),
+            steps: HashMap::new(),
+        }
+    }
+
+    /// Record the recipe step an operation node was made from
+    pub(crate) fn set_step(&mut self, node: NodeIndex, step: Value) {
+        self.steps.insert(node, step);
+    }
+
+    /// Rebuild a graph from the JSON written by [BuildGraph::to_json]
+    ///
+    /// Operations are made afresh from the recipe steps they came from, so the
+    /// graph has to have been written by the same version of the builder.
+    pub fn from_json(text: &str) -> Result<Self, ApplicationError> {
+        let json: GraphJson = serde_json::from_str(text)
+            .map_err(|e| ApplicationError::InvalidRecipe(format!("Could not parse graph: {e}")))?;
+        let mut graph: Graph<BuildStep, BuildEdge> = Graph::new();
+        let mut steps = HashMap::new();
+        for (index, node) in json.nodes.iter().enumerate() {
+            if node.id != index {
+                return Err(ApplicationError::InvalidRecipe(format!(
+                    "Graph nodes are out of order at node {}",
+                    node.id
+                )));
+            }
+            let op: BuildStep = match (node.shortname.as_str(), &node.step) {
+                ("Source", None) => Arc::new(Box::new(SourceSink::Source)),
+                ("Sink", None) => Arc::new(Box::new(SourceSink::Sink)),
+                (_, Some(step)) => {
+                    let step: crate::recipe::Step =
+                        serde_json::from_value(step.clone()).map_err(|e| {
+                            ApplicationError::InvalidRecipe(format!(
+                                "Could not read the step of node {}: {e}",
+                                node.id
+                            ))
+                        })?;
+                    step.to_operation()?.1
+                }
+                (shortname, None) => {
+                    Arc::new(converter_named(&node.identifier).ok_or_else(|| {
+                        ApplicationError::InvalidRecipe(format!(
+                            "Unknown operation {shortname} in graph"
+                        ))
+                    })?)
+                }
+            };
+            if op.identifier() != node.identifier {
+                return Err(ApplicationError::InvalidRecipe(format!(
+                    "Node {} should be {} but was rebuilt as {}; was the graph written by another version?",
+                    node.id,
+                    node.identifier,
+                    op.identifier()
+                )));
+            }
+            let added = graph.add_node(op);
+            if let Some(step) = &node.step {
+                steps.insert(added, step.clone());
+            }
+        }
+
+        let outputs: Vec<OperationOutput> = json
+            .outputs
+            .iter()
+            .map(|output| {
+                match output {
+                    OutputJson::NamedFile { path } => RawOperationOutput::from(path.as_str()),
+                    OutputJson::TemporaryFile => RawOperationOutput::TemporaryFile(None),
+                    OutputJson::InMemoryBytes | OutputJson::SourceFont => {
+                        RawOperationOutput::InMemoryBytes(Vec::new().into())
+                    }
+                    OutputJson::BinaryFont => RawOperationOutput::BinaryFont(Vec::new().into()),
+                }
+                .into()
+            })
+            .collect();
+        let node = |id: usize| {
+            (id < graph.node_count())
+                .then(|| NodeIndex::new(id))
+                .ok_or_else(|| ApplicationError::InvalidRecipe(format!("No node {id} in graph")))
+        };
+        let mut edges = vec![];
+        for edge in &json.edges {
+            let output = outputs.get(edge.output).ok_or_else(|| {
+                ApplicationError::InvalidRecipe(format!("No output {} in graph", edge.output))
+            })?;
+            edges.push((
+                node(edge.from)?,
+                node(edge.to)?,
+                BuildEdge {
+                    output: output.clone(),
+                    output_slot: edge.slot,
+                },
+            ));
+        }
+        let source = node(json.source)?;
+        let sinks = json
+            .sinks
+            .iter()
+            .map(|&id| node(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let target_nodes = json
+            .targets
+            .iter()
+            .map(|(target, &id)| Ok((target.clone(), node(id)?)))
+            .collect::<Result<HashMap<_, _>, ApplicationError>>()?;
+        for (from, to, weight) in edges {
+            graph.add_edge(from, to, weight);
+        }
+
+        Ok(Self {
+            graph,
+            debug_intermediates: false,
+            source,
+            sinks,
+            target_nodes,
+            steps,
+        })
+    }
+
+    /// Serialize the graph as JSON: every node with its identifier and the
+    /// recipe step it came from, and every edge with its slot and output
+    pub fn to_json(&self) -> Result<String, ApplicationError> {
+        let nodes = self
+            .graph
+            .node_indices()
+            .map(|node| {
+                let op = &self.graph[node];
+                NodeJson {
+                    id: node.index(),
+                    shortname: op.shortname().to_string(),
+                    identifier: op.identifier(),
+                    description: op.description(),
+                    step: self.steps.get(&node).cloned(),
+                    input_kinds: op.input_kinds().iter().map(|k| format!("{k:?}")).collect(),
+                    output_kinds: op.output_kinds().iter().map(|k| format!("{k:?}")).collect(),
+                }
+            })
+            .collect();
+
+        let mut outputs: Vec<OperationOutput> = vec![];
+        let mut edges = vec![];
+        for edge in self.graph.edge_references() {
+            let output = match outputs
+                .iter()
+                .position(|seen| seen.ptr_eq(&edge.weight().output))
+            {
+                Some(index) => index,
+                None => {
+                    outputs.push(edge.weight().output.clone());
+                    outputs.len() - 1
+                }
+            };
+            edges.push(EdgeJson {
+                from: edge.source().index(),
+                to: edge.target().index(),
+                slot: edge.weight().output_slot,
+                output,
+            });
         }
+        let outputs = outputs
+            .iter()
+            .map(|output| {
+                let raw = output.lock()?;
+                Ok(match &*raw {
+                    RawOperationOutput::NamedFile(path) => {
+                        OutputJson::NamedFile { path: path.clone() }
+                    }
+                    RawOperationOutput::TemporaryFile(_) => OutputJson::TemporaryFile,
+                    RawOperationOutput::SourceFont(_) => OutputJson::SourceFont,
+                    RawOperationOutput::BinaryFont(_) => OutputJson::BinaryFont,
+                    RawOperationOutput::InMemoryBytes(_) | RawOperationOutput::Released => {
+                        OutputJson::InMemoryBytes
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, ApplicationError>>()?;
+
+        let graph = GraphJson {
+            nodes,
+            outputs,
+            edges,
+            source: self.source.index(),
+            sinks: self.sinks.iter().map(|sink| sink.index()).collect(),
+            targets: self
+                .target_nodes
+                .iter()
+                .map(|(target, node)| (target.clone(), node.index()))
+                .collect(),
+        };
+        serde_json::to_string_pretty(&graph)
+            .map_err(|e| ApplicationError::Other(format!("Could not serialize graph: {e}")))
     }
 
     fn sanitize_debug_component(component: &str) -> String {
@@ -126,11 +381,13 @@ impl BuildGraph {
 
         match kind {
             crate::buildsystem::DataKind::Path => RawOperationOutput::TemporaryFile(None).into(),
+            crate::buildsystem::DataKind::BinaryFont => {
+                RawOperationOutput::BinaryFont(Vec::new().into()).into()
+            }
             crate::buildsystem::DataKind::Bytes
-            | crate::buildsystem::DataKind::BinaryFont
             | crate::buildsystem::DataKind::Any
             | crate::buildsystem::DataKind::SourceFont => {
-                RawOperationOutput::InMemoryBytes(Vec::new()).into()
+                RawOperationOutput::InMemoryBytes(Vec::new().into()).into()
             }
         }
     }
@@ -138,6 +395,9 @@ impl BuildGraph {
     pub fn externals(&self, direction: petgraph::Direction) -> impl Iterator<Item = NodeIndex> {
         self.graph.externals(direction)
     }
+    pub fn node_indices(&self) -> impl Iterator<Item = NodeIndex> {
+        self.graph.node_indices()
+    }
     pub fn node_weight(&self, index: NodeIndex) -> Option<&BuildStep> {
         self.graph.node_weight(index)
     }
@@ -149,15 +409,56 @@ impl BuildGraph {
         self.graph.edges_directed(index, direction)
     }
 
+    /// The next conversion to insert on the way from one data kind to another
+    ///
+    /// Returns the converter and the kind it produces, which may not yet be the
+    /// kind we want. Pairs without a converter are left for the operation to
+    /// handle itself, usually by asking its input for a filename; there is no
+    /// way to make a binary font out of a source without compiling it.
+    /// `source_suffix` is the extension of the format any source bytes are in.
+    fn converter(
+        from: DataKind,
+        to: DataKind,
+        source_suffix: &str,
+    ) -> Option<(Box<dyn Operation>, DataKind)> {
+        match (from, to) {
+            (DataKind::Path, DataKind::Bytes) | (DataKind::Path, DataKind::BinaryFont) => {
+                Some((Box::new(FileToBytes), DataKind::Bytes))
+            }
+            (DataKind::Path, DataKind::SourceFont) => {
+                Some((Box::new(PathToSourceFont), DataKind::SourceFont))
+            }
+            (DataKind::Bytes, DataKind::SourceFont) => Some((
+                Box::new(BytesToSourceFont {
+                    suffix: source_suffix.to_string(),
+                }),
+                DataKind::SourceFont,
+            )),
+            (DataKind::SourceFont, DataKind::Bytes) => {
+                Some((Box::new(SourceFontToBytes), DataKind::Bytes))
+            }
+            (DataKind::Bytes, DataKind::BinaryFont) => {
+                Some((Box::new(BytesToBinaryFont), DataKind::BinaryFont))
+            }
+            (DataKind::BinaryFont, DataKind::Bytes) => {
+                Some((Box::new(BinaryFontToBytes), DataKind::Bytes))
+            }
+            _ => None,
+        }
+    }
+
     pub fn add_path<S: AsRef<str>>(
         &mut self,
         source_filename: &str,
         operations: Vec<(Option<S>, BuildStep)>,
         sink_filename: &str,
     ) -> AddedPath {
-        use crate::buildsystem::operation::DataKind;
         let mut current_node = self.source;
         let mut current_kind: DataKind = DataKind::Path;
+        let mut source_suffix = Path::new(source_filename)
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_else(|| ".glyphs".to_string());
         let mut op_nodes: Vec<NodeIndex> = Vec::new();
         let mut entry_node: Option<NodeIndex> = None;
         let mut debug_chain: Vec<String> = Vec::new();
@@ -189,69 +490,69 @@ impl BuildGraph {
             };
 
             let want_kind = op.input_kinds().first().cloned().unwrap_or(DataKind::Any);
-            let need_conversion = !(want_kind == DataKind::Any || want_kind == current_kind);
-            if need_conversion {
-                let conv: Option<(Box<dyn Operation>, DataKind)> = match (current_kind, want_kind) {
-                    (DataKind::Path, DataKind::Bytes) => {
-                        Some((Box::new(FileToBytes), DataKind::Bytes))
-                    }
-                    (DataKind::Path, DataKind::SourceFont) => {
-                        Some((Box::new(PathToSourceFont), DataKind::SourceFont))
-                    }
-                    _ => None,
+            // Some conversions take more than one hop (e.g. Path to BinaryFont
+            // goes through Bytes), so keep converting until we get there or
+            // run out of converters
+            while !(want_kind == DataKind::Any || want_kind == current_kind) {
+                if current_kind == DataKind::SourceFont {
+                    // Source fonts serialize to Glyphs files
+                    source_suffix = ".glyphs".to_string();
+                }
+                let Some((conv_op, new_kind)) =
+                    Self::converter(current_kind, want_kind, &source_suffix)
+                else {
+                    break;
                 };
-                if let Some((conv_op, new_kind)) = conv {
-                    let conv_shortname = conv_op.identifier();
-                    let existing_conv = self
-                        .graph
-                        .edges_directed(current_node, petgraph::Direction::Outgoing)
-                        .find(|edge| {
-                            if let Some(node_op) = self.graph.node_weight(edge.target()) {
-                                if node_op.shortname() == conv_op.shortname() {
-                                    return !started_at_source
-                                        || edge.weight().output.value_eq(&computed_output);
-                                }
+                let conv_shortname = conv_op.identifier();
+                let existing_conv = self
+                    .graph
+                    .edges_directed(current_node, petgraph::Direction::Outgoing)
+                    .find(|edge| {
+                        if let Some(node_op) = self.graph.node_weight(edge.target()) {
+                            if node_op.identifier() == conv_op.identifier() {
+                                return current_node != self.source
+                                    || edge.weight().output.value_eq(&computed_output);
                             }
-                            false
-                        })
-                        .map(|edge| edge.target());
+                        }
+                        false
+                    })
+                    .map(|edge| edge.target());
 
-                    let conv_node = if let Some(existing) = existing_conv {
-                        existing
-                    } else {
-                        let new_conv_node = self.graph.add_node(Arc::new(conv_op));
-                        self.graph.update_edge(
-                            current_node,
-                            new_conv_node,
-                            BuildEdge {
-                                output: broadcast_output.clone(),
-                                output_slot: 0,
-                            },
-                        );
-                        new_conv_node
-                    };
-
-                    if entry_node.is_none() && current_node == self.source {
-                        entry_node = Some(conv_node);
-                    }
+                let conv_node = if let Some(existing) = existing_conv {
+                    existing
+                } else {
+                    let new_conv_node = self.graph.add_node(Arc::new(conv_op));
+                    self.graph.update_edge(
+                        current_node,
+                        new_conv_node,
+                        BuildEdge {
+                            output: broadcast_output.clone(),
+                            output_slot: 0,
+                        },
+                    );
+                    new_conv_node
+                };
 
-                    current_node = conv_node;
-                    current_kind = new_kind;
-                    debug_chain.push(conv_shortname);
-                    broadcast_output = self
-                        .graph
-                        .edges_directed(current_node, petgraph::Direction::Outgoing)
-                        .next()
-                        .map(|edge| edge.weight().output.clone())
-                        .unwrap_or_else(|| {
-                            self.default_output_for_kind(
-                                source_filename,
-                                sink_filename,
-                                &debug_chain,
-                                current_kind,
-                            )
-                        });
+                if entry_node.is_none() && current_node == self.source {
+                    entry_node = Some(conv_node);
                 }
+
+                current_node = conv_node;
+                current_kind = new_kind;
+                debug_chain.push(conv_shortname);
+                broadcast_output = self
+                    .graph
+                    .edges_directed(current_node, petgraph::Direction::Outgoing)
+                    .next()
+                    .map(|edge| edge.weight().output.clone())
+                    .unwrap_or_else(|| {
+                        self.default_output_for_kind(
+                            source_filename,
+                            sink_filename,
+                            &debug_chain,
+                            current_kind,
+                        )
+                    });
             }
 
             let op_shortname = op.identifier();
@@ -511,6 +812,216 @@ impl BuildGraph {
         Ok(())
     }
 
+    /// The target a node's work ends up in, for error messages
+    fn target_of(&self, node: NodeIndex) -> String {
+        let mut dfs = petgraph::visit::Dfs::new(&self.graph, node);
+        while let Some(next) = dfs.next(&self.graph) {
+            // Targets routed through this node by `needs:` leave on later slots
+            if let Some(edge) = self
+                .graph
+                .edges_directed(next, petgraph::Direction::Outgoing)
+                .filter(|edge| self.graph[edge.target()].shortname() == "Sink")
+                .min_by_key(|edge| edge.weight().output_slot)
+            {
+                return edge.weight().output.to_string();
+            }
+        }
+        "(unknown)".to_string()
+    }
+
+    /// Where a node's inputs come from: the producing node and slot of each
+    /// incoming edge, plus the file name for inputs read straight from a source
+    fn provenance(&self, node: NodeIndex) -> Vec<(NodeIndex, usize, Option<String>)> {
+        let mut provenance = self
+            .graph
+            .edges_directed(node, petgraph::Direction::Incoming)
+            .map(|edge| {
+                let from_source = self.graph[edge.source()].shortname() == "Source";
+                (
+                    edge.source(),
+                    edge.weight().output_slot,
+                    from_source.then(|| {
+                        // "./Foo.glyphs" and "Foo.glyphs" are the same file
+                        Path::new(&edge.weight().output.to_string())
+                            .components()
+                            .filter(|part| *part != std::path::Component::CurDir)
+                            .collect::<std::path::PathBuf>()
+                            .to_string_lossy()
+                            .to_string()
+                    }),
+                )
+            })
+            .collect::<Vec<_>>();
+        provenance.sort();
+        provenance
+    }
+
+    /// The named files each output slot of a node is written to
+    fn named_outputs(&self, node: NodeIndex) -> std::collections::HashMap<usize, String> {
+        self.graph
+            .edges_directed(node, petgraph::Direction::Outgoing)
+            .filter(|edge| edge.weight().output.is_named_file())
+            .map(|edge| (edge.weight().output_slot, edge.weight().output.to_string()))
+            .collect()
+    }
+
+    /// Merge operations which would do exactly the same work for different targets
+    ///
+    /// `add_path` only shares steps along a common prefix from the same source.
+    /// Here we look across the whole graph for nodes with the same identifier
+    /// fed by the same inputs, and fold each such pair into one node. Nodes are
+    /// visited in dependency order, so by the time we reach a node its inputs
+    /// have already been merged, and whole duplicated sub-pipelines collapse in
+    /// one pass. Nodes writing different named files from the same slot are
+    /// left alone.
+    pub fn merge_duplicates(&mut self) {
+        let Ok(order) = petgraph::algo::toposort(&self.graph, None) else {
+            return;
+        };
+        let mut seen: std::collections::HashMap<_, NodeIndex> = std::collections::HashMap::new();
+        let mut duplicates = vec![];
+        for node in order {
+            let op = &self.graph[node];
+            if matches!(op.shortname(), "Source" | "Sink") {
+                continue;
+            }
+            let provenance = self.provenance(node);
+            if provenance.is_empty() {
+                continue;
+            }
+            let key = (op.identifier(), provenance);
+            if let Some(&keep) = seen.get(&key) {
+                let ours = self.named_outputs(node);
+                let theirs = self.named_outputs(keep);
+                let clash = ours
+                    .iter()
+                    .any(|(slot, name)| theirs.get(slot).is_some_and(|other| other != name));
+                if !clash {
+                    self.merge_node(keep, node);
+                    duplicates.push(node);
+                }
+            } else {
+                seen.insert(key, node);
+            }
+        }
+        // Removing a node renumbers the last one, so go from the top down
+        duplicates.sort_unstable_by(|a, b| b.cmp(a));
+        for duplicate in duplicates {
+            self.remove_merged_node(duplicate);
+        }
+    }
+
+    /// Hand everything `duplicate` feeds over to `keep`
+    fn merge_node(&mut self, keep: NodeIndex, duplicate: NodeIndex) {
+        let moved = self
+            .graph
+            .edges_directed(duplicate, petgraph::Direction::Outgoing)
+            .map(|edge| (edge.id(), edge.target(), edge.weight().clone()))
+            .collect::<Vec<_>>();
+        for (_, target, weight) in &moved {
+            let already_fed = self
+                .graph
+                .edges_directed(keep, petgraph::Direction::Outgoing)
+                .any(|edge| {
+                    edge.target() == *target && edge.weight().output_slot == weight.output_slot
+                });
+            if !already_fed {
+                self.graph.add_edge(keep, *target, weight.clone());
+            }
+        }
+        // Removing an edge renumbers the last one, so go from the top down
+        let mut moved_ids = moved.into_iter().map(|(id, _, _)| id).collect::<Vec<_>>();
+        moved_ids.sort_unstable_by(|a, b| b.cmp(a));
+        for id in moved_ids {
+            self.graph.remove_edge(id);
+        }
+
+        // Everything leaving one slot must share one output, so that consumers
+        // see what the operation writes; a named file wins over a temporary one.
+        let edges = self
+            .graph
+            .edges_directed(keep, petgraph::Direction::Outgoing)
+            .map(|edge| {
+                (
+                    edge.id(),
+                    edge.weight().output_slot,
+                    edge.weight().output.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut canonical: std::collections::HashMap<usize, OperationOutput> =
+            std::collections::HashMap::new();
+        for (_, slot, output) in &edges {
+            let entry = canonical.entry(*slot).or_insert_with(|| output.clone());
+            if output.is_named_file() && !entry.is_named_file() {
+                *entry = output.clone();
+            }
+        }
+        for (id, slot, _) in edges {
+            self.graph[id].output = canonical[&slot].clone();
+        }
+
+        for node in self.target_nodes.values_mut() {
+            if *node == duplicate {
+                *node = keep;
+            }
+        }
+    }
+
+    /// Remove a node whose work has been handed to another by `merge_node`
+    fn remove_merged_node(&mut self, duplicate: NodeIndex) {
+        // Removing a node moves the last node into its index, so fix up
+        // anything which refers to the last node by index
+        let last = NodeIndex::new(self.graph.node_count() - 1);
+        self.graph.remove_node(duplicate);
+        self.steps.remove(&duplicate);
+        if last != duplicate {
+            if let Some(step) = self.steps.remove(&last) {
+                self.steps.insert(duplicate, step);
+            }
+            let renumber = |node: &mut NodeIndex| {
+                if *node == last {
+                    *node = duplicate;
+                }
+            };
+            renumber(&mut self.source);
+            self.sinks.iter_mut().for_each(renumber);
+            self.target_nodes.values_mut().for_each(renumber);
+        }
+    }
+
+    /// Check that every operation will be given as many inputs as it expects
+    ///
+    /// Inputs are counted by slot, as the orchestrator will gather them.
+    pub fn validate(&self) -> Result<(), ApplicationError> {
+        for node in self.graph.node_indices() {
+            let op = &self.graph[node];
+            if matches!(op.shortname(), "Source" | "Sink") {
+                continue;
+            }
+            let mut slots = self
+                .graph
+                .edges_directed(node, petgraph::Direction::Incoming)
+                .map(|edge| edge.weight().output_slot)
+                .collect::<Vec<_>>();
+            slots.sort();
+            slots.dedup();
+            let expected = op.input_kinds().len();
+            let actual = slots.len();
+            if actual < expected || (actual > expected && !op.variadic_inputs()) {
+                return Err(ApplicationError::InvalidRecipe(format!(
+                    "operation {} for target {} expects {} input{} but has {}",
+                    op.shortname(),
+                    self.target_of(node),
+                    expected,
+                    if expected == 1 { "" } else { "s" },
+                    actual
+                )));
+            }
+        }
+        Ok(())
+    }
+
     pub fn ensure_directories(&self) -> Result<(), ApplicationError> {
         for edge in self.graph.raw_edges() {
             if edge.weight.output.is_named_file()
@@ -529,6 +1040,62 @@ impl BuildGraph {
         Ok(())
     }
 
+    /// Check that we can write to every output directory, and warn if there
+    /// may not be room for what we're about to build
+    ///
+    /// We expect each target to take as much space as it did last time, or as
+    /// much as its sources if it hasn't been built before. That's a rough
+    /// guess (a source is often far bigger than the fonts built from it), so
+    /// running short is only a warning.
+    pub fn preflight(&self) -> Result<(), ApplicationError> {
+        let mut dirs = BTreeSet::new();
+        for edge in self.graph.raw_edges() {
+            if edge.weight.output.is_named_file()
+                && self.graph[edge.source()].shortname() != "Source"
+            {
+                let filename = edge.weight.output.to_filename(None)?;
+                dirs.insert(directory_of(Path::new(&filename)));
+            }
+        }
+        for dir in &dirs {
+            tempfile::tempfile_in(dir).map_err(|e| {
+                ApplicationError::Preflight(format!("cannot write to {}: {e}", dir.display()))
+            })?;
+        }
+
+        // Add up what we need on each filesystem
+        let mut needed: HashMap<u64, (PathBuf, u64)> = HashMap::new();
+        for (target, (_, sources)) in self.target_dependencies() {
+            let path = Path::new(&target);
+            let estimate = match std::fs::metadata(path) {
+                Ok(metadata) => metadata.len(),
+                Err(_) => sources
+                    .iter()
+                    .map(|source| disk_usage(Path::new(source)))
+                    .sum(),
+            };
+            let dir = directory_of(path);
+            let Ok(metadata) = std::fs::metadata(&dir) else {
+                continue;
+            };
+            needed.entry(metadata.dev()).or_insert((dir, 0)).1 += estimate;
+        }
+        for (dir, bytes) in needed.into_values() {
+            let Ok(available) = fs4::available_space(&dir) else {
+                continue;
+            };
+            if available < bytes {
+                log::warn!(
+                    "The build may need up to {} MB in {}, but only {} MB is free",
+                    bytes.div_ceil(1 << 20),
+                    dir.display(),
+                    available >> 20
+                );
+            }
+        }
+        Ok(())
+    }
+
     #[cfg(feature = "graphviz")]
     pub fn draw(&self) -> Result<String, ApplicationError> {
         let contents = format!("{}", petgraph::dot::Dot::new(&self.graph));
@@ -584,12 +1151,184 @@ impl BuildGraph {
     }
 }
 
+impl BuildGraph {
+    /// The target written by a sink node, if any
+    fn sink_target(&self, sink: NodeIndex) -> Option<String> {
+        self.graph
+            .edges_directed(sink, petgraph::Direction::Incoming)
+            .find_map(|edge| match &*edge.weight().output.lock().ok()? {
+                RawOperationOutput::NamedFile(name) => Some(name.clone()),
+                _ => None,
+            })
+    }
+
+    /// What each target is built from: the operations it depends on and the
+    /// source files they read, both sorted
+    ///
+    /// Operations are described by the recipe steps they were made from, as
+    /// JSON, since not every operation folds all of its configuration into
+    /// its identifier; converters, which have no step, by their identifiers.
+    pub(crate) fn target_dependencies(&self) -> BTreeMap<String, (Vec<String>, Vec<String>)> {
+        let reversed = petgraph::visit::Reversed(&self.graph);
+        let is_source =
+            |node: NodeIndex| node == self.source || self.graph[node].shortname() == "Source";
+        let mut dependencies = BTreeMap::new();
+        for &sink in &self.sinks {
+            let Some(target) = self.sink_target(sink) else {
+                continue;
+            };
+            let mut operations = vec![];
+            let mut sources = vec![];
+            let mut dfs = petgraph::visit::Dfs::new(reversed, sink);
+            while let Some(node) = dfs.next(reversed) {
+                if node == sink || is_source(node) {
+                    continue;
+                }
+                operations.push(match self.steps.get(&node) {
+                    Some(step) => step.to_string(),
+                    None => self.graph[node].identifier(),
+                });
+                for edge in self
+                    .graph
+                    .edges_directed(node, petgraph::Direction::Incoming)
+                    .filter(|edge| is_source(edge.source()))
+                {
+                    sources.push(edge.weight().output.to_string());
+                }
+            }
+            operations.sort();
+            sources.sort();
+            sources.dedup();
+            dependencies.insert(target, (operations, sources));
+        }
+        dependencies
+    }
+
+    /// All targets which are built (directly or indirectly) from a node
+    fn downstream_targets(&self, node: NodeIndex) -> Vec<String> {
+        let mut targets = vec![];
+        let mut dfs = petgraph::visit::Dfs::new(&self.graph, node);
+        while let Some(index) = dfs.next(&self.graph) {
+            if self.sinks.contains(&index)
+                && let Some(target) = self.sink_target(index)
+            {
+                targets.push(target);
+            }
+        }
+        targets.sort();
+        targets.dedup();
+        targets
+    }
+
+    /// Describe how a target will be built
+    ///
+    /// This walks back from the target's sink along each operation's primary
+    /// input, and lists the operations in build order together with their
+    /// parameters, the kinds of data they consume and produce, where their
+    /// output goes, any additional inputs, and which other targets share them.
+    pub fn explain(&self, target: &str) -> Result<String, ApplicationError> {
+        let sink = self
+            .sinks
+            .iter()
+            .copied()
+            .find(|sink| self.sink_target(*sink).as_deref() == Some(target))
+            .ok_or_else(|| {
+                ApplicationError::InvalidRecipe(format!("Target '{target}' is not in the recipe"))
+            })?;
+
+        // Walk backwards from the sink, following the lowest input slot
+        let mut chain = vec![];
+        let mut node = sink;
+        let mut source_file = None;
+        while let Some(edge) = self
+            .graph
+            .edges_directed(node, petgraph::Direction::Incoming)
+            .min_by_key(|edge| edge.weight().output_slot)
+        {
+            node = edge.source();
+            if self.graph[node].shortname() == "Source" {
+                source_file = Some(edge.weight().output.to_string());
+                break;
+            }
+            chain.push(node);
+        }
+        chain.reverse();
+
+        let mut explanation = format!(
+            "{target} is built from {} by:\n",
+            source_file.unwrap_or_else(|| "<unknown source>".to_string())
+        );
+        for (step, &node) in chain.iter().enumerate() {
+            let op = &self.graph[node];
+            explanation.push_str(&format!("{:>3}. {}\n", step + 1, op.description()));
+            if op.identifier() != op.shortname() {
+                explanation.push_str(&format!("     parameters: {}\n", op.identifier()));
+            }
+            explanation.push_str(&format!(
+                "     consumes: {:?}, produces: {:?}\n",
+                op.input_kinds(),
+                op.output_kinds()
+            ));
+            let mut inputs: Vec<_> = self
+                .graph
+                .edges_directed(node, petgraph::Direction::Incoming)
+                .collect();
+            inputs.sort_by_key(|edge| edge.weight().output_slot);
+            for edge in inputs.iter().skip(1) {
+                explanation.push_str(&format!(
+                    "     also reads: {} (slot {})\n",
+                    edge.weight().output,
+                    edge.weight().output_slot
+                ));
+            }
+            if let Some(edge) = self
+                .graph
+                .edges_directed(node, petgraph::Direction::Outgoing)
+                .next()
+            {
+                explanation.push_str(&format!("     writes: {}\n", edge.weight().output));
+            }
+            let shared: Vec<String> = self
+                .downstream_targets(node)
+                .into_iter()
+                .filter(|other| other != target)
+                .collect();
+            if !shared.is_empty() {
+                explanation.push_str(&format!("     shared with: {}\n", shared.join(", ")));
+            }
+        }
+        Ok(explanation)
+    }
+}
+
 impl Default for BuildGraph {
     fn default() -> Self {
         Self::new(false)
     }
 }
 
+/// The directory a file is in, as something we can stat
+fn directory_of(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// How many bytes a file, or everything in a directory, takes up
+fn disk_usage(path: &Path) -> u64 {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| disk_usage(&entry.path()))
+            .sum(),
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    }
+}
+
 impl OperationOutput {
     fn value_eq(&self, other: &Self) -> bool {
         if let (Ok(a), Ok(b)) = (self.lock(), other.lock()) {
@@ -1,12 +1,27 @@
+mod buildlog;
+mod environment;
+mod failures;
 mod graph;
 mod operation;
 mod orchestrator;
 mod output;
+mod reporter;
+mod reproducible;
+mod scratch;
+mod sourcekind;
 mod sourcesink;
 
-pub use graph::{BuildGraph, BuildStep};
+pub use buildlog::{BUILD_LOG, BuildLog, DirtyReason, TargetRecord};
+pub(crate) use environment::{WithEnvironment, command_path, parse_cwd, parse_env};
+pub use failures::{Failure, FailureReport};
+pub use graph::{BuildGraph, BuildStep, EdgeJson, GraphJson, NodeJson, OutputJson};
+pub(crate) use operation::report_warning;
 pub use operation::{DataKind, Operation};
 pub use output::OperationOutput;
+pub use reporter::{Reporter, ReporterKind};
+pub use reproducible::source_date_epoch;
+pub use scratch::scratch_file;
+pub use sourcekind::SourceKind;
 
 // This is the main entry point to the build process
-pub use orchestrator::run;
+pub use orchestrator::{RunOptions, run, run_targets};
@@ -1,7 +1,39 @@
-use crate::{buildsystem::OperationOutput, error::ApplicationError};
+use crate::{
+    buildsystem::{
+        OperationOutput,
+        environment::{step_cwd, step_env},
+    },
+    error::ApplicationError,
+};
 use async_trait::async_trait;
 use serde_json::Value;
-use std::{collections::HashMap, process::Output};
+use std::{cell::RefCell, collections::HashMap, process::Output};
+
+thread_local! {
+    /// Shell commands run by the operation executing on this thread, for the audit log
+    static SHELL_COMMANDS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    /// Problems found by the operation executing on this thread, for the reporter
+    static WARNINGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Take the shell commands run on this thread since the last call
+pub(crate) fn take_shell_commands() -> Vec<String> {
+    SHELL_COMMANDS.with(|commands| commands.take())
+}
+
+/// Warn about a problem an operation found in the font, such as a QA
+/// check failing, which shouldn't stop the build
+///
+/// As well as being logged, these are passed to the build's reporter.
+pub(crate) fn report_warning(message: String) {
+    log::warn!("{message}");
+    WARNINGS.with(|warnings| warnings.borrow_mut().push(message));
+}
+
+/// Take the warnings reported on this thread since the last call
+pub(crate) fn take_warnings() -> Vec<String> {
+    WARNINGS.with(|warnings| warnings.take())
+}
 
 /// Logical data kind that operations consume/produce
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -60,19 +92,53 @@ pub trait Operation: Send + Sync {
         _outputs: &[OperationOutput],
     ) -> Result<Output, ApplicationError> {
         log::debug!("Running shell command: {}", cmd);
-        let process_output = std::process::Command::new("sh")
-            .arg("-c")
-            .arg(cmd)
+        SHELL_COMMANDS.with(|commands| commands.borrow_mut().push(cmd.to_string()));
+        let mut command = std::process::Command::new("sh");
+        command.arg("-c").arg(cmd).envs(step_env());
+        if let Some(cwd) = step_cwd() {
+            command.current_dir(cwd);
+        }
+        let process_output = command
             .output()
             .map_err(|e| ApplicationError::Other(e.to_string()))?;
         Ok(process_output)
     }
 
+    /// Hand the inputs after the first, which came from `needs:`, on to
+    /// their outputs unchanged
+    fn pass_through_needs(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<(), ApplicationError> {
+        for (input, output) in inputs.iter().zip(outputs.iter()).skip(1) {
+            if input != output {
+                output.set_contents(input.to_bytes()?)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Declare the input kinds for this operation (one per input slot).
     /// Defaults to a single `Any` input, meaning no constraints.
     fn input_kinds(&self) -> Vec<DataKind> {
         vec![DataKind::Any]
     }
+    /// Whether inputs beyond those in `input_kinds` are allowed.
+    ///
+    /// Operations which work over a whole family take any number of
+    /// sibling fonts through `needs:` and should return true.
+    fn variadic_inputs(&self) -> bool {
+        false
+    }
+    /// Whether this operation only reads the targets it `needs:`, and so
+    /// must see them after every other operation has finished with them.
+    ///
+    /// Those dependencies are wired last, so that (for example) a checksum
+    /// manifest hashes fonts after a sibling's BuildStat has written STAT.
+    fn reads_finished_targets(&self) -> bool {
+        false
+    }
     /// Declare the output kinds for this operation (one per output slot).
     /// Defaults to a single `Any` output, meaning unspecified.
     fn output_kinds(&self) -> Vec<DataKind> {
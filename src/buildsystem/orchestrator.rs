@@ -4,23 +4,38 @@
 //! Many thanks to Yota Toyama for making this code available under the MIT/Apache licenses.
 //! A parallel build system in just under 200 lines of Rust is astonishing.
 use crate::{
-    buildsystem::{BuildGraph, BuildStep, OperationOutput, graph::BuildEdge},
+    buildsystem::{
+        BuildGraph, BuildLog, BuildStep, Failure, FailureReport, OperationOutput, TargetRecord,
+        environment::set_build_env,
+        graph::BuildEdge,
+        operation::{take_shell_commands, take_warnings},
+        output::RawOperationOutput,
+        reporter::{Reporter, ReporterKind},
+        reproducible::{normalize_outputs, source_date_epoch},
+        scratch::with_scratch_dir,
+    },
     error::ApplicationError,
 };
 use async_recursion::async_recursion;
 use dashmap::DashMap;
-use futures::future::{FutureExt, Shared, try_join_all};
+use futures::future::{FutureExt, Shared, join_all, try_join_all};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use petgraph::{
     Direction,
     graph::{EdgeIndex, EdgeReference, NodeIndex},
     visit::EdgeRef,
 };
+use sha2::{Digest, Sha256};
 use std::{
-    collections::HashSet, error::Error, future::Future, pin::Pin, process::Output, sync::Arc,
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    future::Future,
+    io::Write,
+    pin::Pin,
+    process::Output,
+    sync::Arc,
 };
 use tokio::{
-    io::{AsyncWriteExt, stderr, stdout},
     spawn,
     sync::{Mutex, Semaphore},
     time::Instant,
@@ -46,6 +61,33 @@ impl Configuration {
 type RawBuildFuture = Pin<Box<dyn Future<Output = Result<(), ApplicationError>> + Send>>;
 pub(crate) type BuildFuture = Shared<RawBuildFuture>;
 
+/// The external tools whose `--version` goes in the audit log. Anything else a
+/// step runs, such as a script of the user's own, isn't run a second time.
+const VERSIONED_TOOLS: &[&str] = &[
+    "diffenator2",
+    "fontmake",
+    "fonttools",
+    "fontspector",
+    "gftools",
+    "gftools-fix-font",
+    "hb-view",
+    "paintcompiler",
+    "ttfautohint",
+    "ttfautohint-vf",
+];
+
+/// Where an output lives on disk, if it does; otherwise how it's held
+fn resolved_path(output: &OperationOutput) -> String {
+    let path = match output.lock().as_deref() {
+        Ok(RawOperationOutput::NamedFile(name)) => Some(name.clone()),
+        Ok(RawOperationOutput::TemporaryFile(Some(file))) => {
+            Some(file.path().to_string_lossy().to_string())
+        }
+        _ => None,
+    };
+    path.unwrap_or_else(|| output.to_string())
+}
+
 /// Helper function to get final sink target filenames for a given node index.
 /// This traces through outgoing edges until it reaches Sink nodes and returns the named
 /// files written there, ignoring intermediate debug artifacts.
@@ -68,7 +110,8 @@ fn get_target_files(context: &Context, index: NodeIndex) -> Vec<String> {
             if let Some(node_weight) = context.configuration.graph().node_weight(edge.target())
                 && node_weight.shortname() == "Sink"
                 && let Ok(output_lock) = edge.weight().output.lock()
-                && let crate::buildsystem::output::RawOperationOutput::NamedFile(name) = &*output_lock
+                && let crate::buildsystem::output::RawOperationOutput::NamedFile(name) =
+                    &*output_lock
             {
                 targets.push(name.clone());
                 continue;
@@ -82,30 +125,122 @@ fn get_target_files(context: &Context, index: NodeIndex) -> Vec<String> {
     targets
 }
 
-pub async fn run(
+/// How to run a build
+#[derive(Clone, Debug)]
+pub struct RunOptions {
+    /// Maximum number of operations to run at once
+    pub job_limit: usize,
+    /// Whether to show progress bars
+    pub progress: bool,
+    /// How to report what the build is doing
+    pub reporter: ReporterKind,
+    /// Carry on building the other targets when one fails
+    pub keep_going: bool,
+    /// Keep the scratch directories of operations which fail
+    pub keep_temps: bool,
+    /// Make the same sources build to byte-identical fonts
+    pub reproducible: bool,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            job_limit: num_cpus::get(),
+            progress: true,
+            reporter: ReporterKind::default(),
+            keep_going: false,
+            keep_temps: false,
+            reproducible: false,
+        }
+    }
+}
+
+pub async fn run(graph: BuildGraph, options: RunOptions) -> Result<(), ApplicationError> {
+    let targets: Vec<String> = graph.target_nodes.keys().cloned().collect();
+    run_targets(graph, &targets, options).await
+}
+
+/// Build only the named targets
+///
+/// Only the operations the targets depend on are scheduled. Operations
+/// shared with other targets still write those targets' files as they go.
+///
+/// If anything fails, the error is a [FailureReport] of every operation
+/// which failed; with `keep_going`, that's everything which could fail
+/// without stopping the targets which don't depend on it.
+pub async fn run_targets(
     graph: BuildGraph,
-    job_limit: usize,
-    progress: bool,
+    targets: &[String],
+    options: RunOptions,
 ) -> Result<(), ApplicationError> {
+    let mut target_nodes = vec![];
+    for target in targets {
+        let node = graph.target_nodes.get(target).ok_or_else(|| {
+            ApplicationError::InvalidRecipe(format!("Target '{target}' is not in the recipe"))
+        })?;
+        target_nodes.push((target.clone(), *node));
+    }
+
+    // Hash the sources before anything has a chance to touch them
+    let mut fingerprints = BuildLog::fingerprint(&graph);
+    fingerprints.retain(|target, _| targets.contains(target));
     let configuration = Configuration::new(graph);
-    let context = Arc::new(Context::new(job_limit, Arc::new(configuration), progress));
-    for (name, target_node) in &context.configuration.graph().target_nodes {
+    let context = Arc::new(Context::new(Arc::new(configuration), &options)?);
+    // Tools which honour SOURCE_DATE_EPOCH date what they make as of it too
+    let mut build_env = BTreeMap::new();
+    if let Some(epoch) = context.source_date_epoch {
+        build_env.insert("SOURCE_DATE_EPOCH".to_string(), epoch.to_string());
+    }
+    set_build_env(build_env);
+    for (name, target_node) in &target_nodes {
         trigger_build(context.clone(), *target_node).await?;
-        if progress {
+        if context.progress {
             context.add_progressbar(*target_node, name);
         }
     }
 
     // Do not inline this to avoid borrowing a lock of builds.
-    let futures = context
-        .build_futures
+    let futures = target_nodes
         .iter()
+        .filter_map(|(_, node)| context.build_futures.get(node))
         .map(|r#ref| r#ref.value().clone())
         .collect::<Vec<_>>();
 
-    let result = try_join_all(futures).await;
+    let result = if options.keep_going {
+        let results = join_all(futures).await;
+        for ((target, _), result) in target_nodes.iter().zip(&results) {
+            if result.is_err() {
+                fingerprints.remove(target);
+            }
+        }
+        results.into_iter().collect::<Result<Vec<_>, _>>()
+    } else {
+        try_join_all(futures).await
+    };
+    let failures = std::mem::take(&mut *context.failures.lock()?);
+    context.finish_scratch(&failures)?;
+    if !failures.is_empty() {
+        // Record the targets which did get built
+        if options.keep_going {
+            save_build_log(fingerprints);
+        }
+        return Err(ApplicationError::BuildFailed(FailureReport { failures }));
+    }
+    result?;
+    save_build_log(fingerprints);
+    Ok(())
+}
 
-    result.map(|_| ())
+/// Record what targets were built from in the build log
+fn save_build_log(fingerprints: BTreeMap<String, TargetRecord>) {
+    let mut build_log = BuildLog::load().unwrap_or_else(|e| {
+        log::warn!("{e}; starting a new build log");
+        BuildLog::default()
+    });
+    build_log.record(fingerprints);
+    if let Err(e) = build_log.save() {
+        log::warn!("Could not write build log: {e}");
+    }
 }
 
 #[async_recursion]
@@ -125,6 +260,14 @@ async fn trigger_build(context: Arc<Context>, build: NodeIndex) -> Result<(), Ap
 }
 
 async fn spawn_build(context: Arc<Context>, index: NodeIndex) -> Result<(), ApplicationError> {
+    // In case the operation panics
+    let operation = context
+        .configuration
+        .graph()
+        .node_weight(index)
+        .map(|op| op.shortname().to_string())
+        .unwrap_or_default();
+    let final_targets = get_target_files(&context, index);
     spawn(async move {
         let targets = get_target_files(&context, index);
         let targets_str = targets.join(", ");
@@ -186,6 +329,7 @@ async fn spawn_build(context: Arc<Context>, index: NodeIndex) -> Result<(), Appl
 
             // OK, we are ready.
             run_op(&context, build, &input_files, &output_files, &targets).await?;
+            context.consumed(&in_edges)?;
 
             // Advance progress bars for all targets reachable from this build step.
             if context.progress {
@@ -204,7 +348,13 @@ async fn spawn_build(context: Arc<Context>, index: NodeIndex) -> Result<(), Appl
         .instrument(span)
         .await
     })
-    .await?
+    .await
+    .map_err(|e| ApplicationError::OperationFailed {
+        operation,
+        targets: final_targets,
+        inputs: vec![],
+        source: Box::new(e.into()),
+    })?
 }
 
 async fn build_input(
@@ -215,9 +365,16 @@ async fn build_input(
     context
         .build_futures
         .get(&input)
-        .ok_or(ApplicationError::Build)
         .map(|f| f.clone())
-        .map_err(|_| ApplicationError::Build)
+        .ok_or_else(|| ApplicationError::Build {
+            operation: context
+                .configuration
+                .graph()
+                .node_weight(input)
+                .map(|op| op.shortname().to_string())
+                .unwrap_or_default(),
+            targets: get_target_files(&context, input),
+        })
 }
 
 async fn run_op(
@@ -238,12 +395,7 @@ async fn run_op(
         targets = %outputs_str
     );
 
-    let description = format!(
-        "{}: {} -> {}",
-        op.description(),
-        inputs_str,
-        outputs_str,
-    );
+    let description = format!("{}: {} -> {}", op.description(), inputs_str, outputs_str);
 
     let target_summary = if final_targets.is_empty() {
         outputs_str.clone()
@@ -251,25 +403,98 @@ async fn run_op(
         final_targets.join(", ")
     };
 
-    let failure_context = format!(
-        "operation '{}' while building [{}] from [{}]",
-        op.shortname(), target_summary, inputs_str
-    );
+    let target_list: Vec<String> = if final_targets.is_empty() {
+        output_strs.clone()
+    } else {
+        final_targets.to_vec()
+    };
+    let failed = |source: ApplicationError| ApplicationError::OperationFailed {
+        operation: op.shortname().to_string(),
+        targets: target_list.clone(),
+        inputs: input_strs.clone(),
+        source: Box::new(source),
+    };
 
     let inner = async {
-        let ((output, duration), _console) = try_join!(
+        let ((output, duration, commands, scratch_dir), _console) = try_join!(
             async {
                 let start_time = Instant::now();
                 if !inputs.is_empty() && !outputs.is_empty() && !op.hidden() {
-                    context.print_description(&description).await;
+                    context.reporter.started(&description);
+                }
+                let commands = std::sync::Mutex::new(vec![]);
+                let warnings = std::sync::Mutex::new(vec![]);
+                let scratch = context.scratch_dir(op, &outputs_str)?;
+                let result = context
+                    .run_with_semaphore(|| {
+                        let stable_names = context.source_date_epoch.is_some();
+                        let result =
+                            with_scratch_dir(&scratch, context.keep_temps, stable_names, || {
+                                let was_named: Vec<bool> =
+                                    outputs.iter().map(OperationOutput::is_named_file).collect();
+                                let output = op.execute(inputs, outputs)?;
+                                if let Some(epoch) = context.source_date_epoch
+                                    && output.status.success()
+                                {
+                                    normalize_outputs(outputs, &was_named, epoch)?;
+                                }
+                                Ok(output)
+                            });
+                        if let Ok(mut commands) = commands.lock() {
+                            *commands = take_shell_commands();
+                        }
+                        if let Ok(mut warnings) = warnings.lock() {
+                            *warnings = take_warnings();
+                        }
+                        result
+                    })
+                    .await;
+                for warning in warnings.into_inner().unwrap_or_default() {
+                    context
+                        .reporter
+                        .warned(&target_summary, &description, &warning);
                 }
-                let output = context
-                    .run_with_semaphore(|| op.execute(inputs, outputs))
-                    .await
-                    .map_err(|e| ApplicationError::Other(format!("{}: {}", failure_context, e)))?;
 
                 let elapsed = Instant::now() - start_time;
-                Ok::<_, ApplicationError>((output, elapsed))
+                let commands = commands.into_inner().unwrap_or_default();
+                // Keep everything a failed operation was working with if asked to
+                let keep_scratch =
+                    context.keep_temps && !matches!(&result, Ok(output) if output.status.success());
+                if !keep_scratch {
+                    context.tidy_scratch(&scratch, inputs, outputs);
+                }
+                let scratch_dir = keep_scratch.then(|| scratch.to_string_lossy().to_string());
+                context
+                    .audit(
+                        op,
+                        inputs,
+                        outputs,
+                        result.as_ref().map_err(|e| e.to_string()),
+                        elapsed,
+                        commands.clone(),
+                    )
+                    .await;
+                let output = result.map_err(|e| {
+                    let cause = e
+                        .downcast_ref::<ApplicationError>()
+                        .cloned()
+                        .unwrap_or_else(|| ApplicationError::Other(e.to_string()));
+                    context
+                        .reporter
+                        .failed(&target_summary, &description, &e.to_string(), None);
+                    context.failed(Failure {
+                        targets: final_targets.to_vec(),
+                        operation: op.shortname().to_string(),
+                        description: description.clone(),
+                        commands: commands.clone(),
+                        error: e.to_string(),
+                        output: vec![],
+                        cause: Box::new(cause.clone()),
+                        scratch_dir: scratch_dir.clone(),
+                    });
+                    failed(cause)
+                })?;
+                Ok::<_, ApplicationError>((output, elapsed, commands, scratch_dir))
             },
             async {
                 let console = context.console().lock().await;
@@ -291,12 +516,39 @@ async fn run_op(
         );
 
         if !output.status.success() {
-            stdout().write_all(&output.stdout).await?;
-            stderr().write_all(&output.stderr).await?;
-            return Err(ApplicationError::Other(format!(
-                "{}: process exited with status {}",
-                failure_context, output.status
-            )));
+            // The shell says 127 when it can't find the command
+            let cause = match (output.status.code(), commands.first()) {
+                (Some(127), Some(command)) => ApplicationError::ToolNotFound(
+                    command
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or(command)
+                        .to_string(),
+                ),
+                _ => {
+                    ApplicationError::Other(format!("process exited with status {}", output.status))
+                }
+            };
+            let error = cause.to_string();
+            context
+                .reporter
+                .failed(&target_summary, &description, &error, Some(&output));
+            context.failed(Failure {
+                targets: final_targets.to_vec(),
+                operation: op.shortname().to_string(),
+                description: description.clone(),
+                commands,
+                error: error.clone(),
+                output: Failure::trim_output(&output.stdout, &output.stderr),
+                cause: Box::new(cause.clone()),
+                scratch_dir,
+            });
+            return Err(failed(cause));
+        }
+        if !inputs.is_empty() && !outputs.is_empty() && !op.hidden() {
+            context
+                .reporter
+                .finished(&target_summary, &description, &output, duration);
         }
 
         Ok::<(), ApplicationError>(())
@@ -313,22 +565,289 @@ pub struct Context {
     pub build_futures: DashMap<NodeIndex, BuildFuture>,
     pub progress: bool,
     pub progressbars: MultiProgress,
+    /// What we tell the user about each operation
+    pub reporter: Box<dyn Reporter>,
+    /// The operations which have failed so far
+    pub failures: std::sync::Mutex<Vec<Failure>>,
+    /// Holds a scratch directory for each operation; see [Context::scratch_dir]
+    scratch_root: std::sync::Mutex<Option<tempfile::TempDir>>,
+    scratch_path: std::path::PathBuf,
+    scratch_count: std::sync::atomic::AtomicUsize,
+    keep_temps: bool,
+    /// What to date fonts as of, in a reproducible build
+    source_date_epoch: Option<i64>,
     pub progress_bar_for_target: DashMap<NodeIndex, indicatif::ProgressBar>,
     pub edges_to_final_target_nodes: DashMap<EdgeIndex, Vec<NodeIndex>>,
+    /// How many operations still have to read each node's outputs
+    pub pending_consumers: DashMap<NodeIndex, usize>,
+    /// Where we record every operation we run; opened on first use
+    audit_log: std::sync::Mutex<Option<std::fs::File>>,
+    /// The `--version` of each external tool we've run
+    tool_versions: DashMap<String, Option<String>>,
 }
 
 impl Context {
-    pub fn new(job_limit: usize, configuration: Arc<Configuration>, progress: bool) -> Self {
-        Self {
-            command_semaphore: Semaphore::new(job_limit),
+    pub fn new(
+        configuration: Arc<Configuration>,
+        options: &RunOptions,
+    ) -> Result<Self, ApplicationError> {
+        let graph = configuration.graph();
+        let pending_consumers = graph
+            .node_indices()
+            .map(|node| {
+                let mut consumers = graph
+                    .edges_directed(node, Direction::Outgoing)
+                    .map(|edge| edge.target())
+                    .filter(|target| {
+                        graph
+                            .node_weight(*target)
+                            .is_some_and(|op| op.shortname() != "Sink")
+                    })
+                    .collect::<Vec<_>>();
+                consumers.sort();
+                consumers.dedup();
+                (node, consumers.len())
+            })
+            .collect();
+        let progress = options.progress && options.reporter.allows_progress();
+        let progressbars = MultiProgress::new();
+        let reporter = options
+            .reporter
+            .reporter(progress.then(|| progressbars.clone()));
+        let scratch_root = tempfile::Builder::new()
+            .prefix("gftools-builder-")
+            .tempdir()?;
+        Ok(Self {
+            command_semaphore: Semaphore::new(options.job_limit),
             console: Mutex::new(()),
             configuration,
             build_futures: DashMap::new(),
             progress,
-            progressbars: MultiProgress::new(),
+            progressbars,
+            reporter,
+            failures: std::sync::Mutex::new(vec![]),
+            scratch_path: scratch_root.path().to_path_buf(),
+            scratch_root: std::sync::Mutex::new(Some(scratch_root)),
+            scratch_count: std::sync::atomic::AtomicUsize::new(0),
+            keep_temps: options.keep_temps,
+            source_date_epoch: options.reproducible.then(source_date_epoch),
             progress_bar_for_target: DashMap::new(),
             edges_to_final_target_nodes: DashMap::new(),
+            pending_consumers,
+            audit_log: std::sync::Mutex::new(None),
+            tool_versions: DashMap::new(),
+        })
+    }
+
+    /// Make a fresh scratch directory for an operation to run in
+    ///
+    /// In a reproducible build, the directory is named after the operation
+    /// and its outputs rather than the order operations happened to start in.
+    fn scratch_dir(
+        &self,
+        op: &BuildStep,
+        outputs: &str,
+    ) -> Result<std::path::PathBuf, ApplicationError> {
+        let count = self
+            .scratch_count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if self.source_date_epoch.is_some() {
+            let hash = format!(
+                "{:x}",
+                Sha256::digest(format!("{}\n{outputs}", op.identifier()))
+            );
+            let dir = self
+                .scratch_path
+                .join(format!("{}-{}", &hash[..16], op.shortname()));
+            if std::fs::create_dir(&dir).is_ok() {
+                return Ok(dir);
+            }
         }
+        let dir = self
+            .scratch_path
+            .join(format!("{count:04}-{}", op.shortname()));
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// Remove what an operation left in its scratch directory, apart from
+    /// the files its inputs and outputs are still held in
+    fn tidy_scratch(
+        &self,
+        dir: &std::path::Path,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) {
+        let in_use: HashSet<String> = inputs.iter().chain(outputs).map(resolved_path).collect();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if in_use.contains(&*path.to_string_lossy()) {
+                continue;
+            }
+            let removed = if path.is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else {
+                std::fs::remove_file(&path)
+            };
+            if let Err(e) = removed {
+                log::debug!("Could not remove {}: {e}", path.display());
+            }
+        }
+    }
+
+    /// Clean up the scratch directories at the end of the build, keeping
+    /// those of failed operations if `--keep-temps` was given
+    fn finish_scratch(&self, failures: &[Failure]) -> Result<(), ApplicationError> {
+        let Some(root) = self.scratch_root.lock()?.take() else {
+            return Ok(());
+        };
+        let kept: HashSet<&str> = failures
+            .iter()
+            .filter_map(|failure| failure.scratch_dir.as_deref())
+            .collect();
+        if kept.is_empty() {
+            return Ok(());
+        }
+        for entry in std::fs::read_dir(root.path())?.flatten() {
+            if !kept.contains(&*entry.path().to_string_lossy()) {
+                let _ = std::fs::remove_dir_all(entry.path());
+            }
+        }
+        root.keep();
+        Ok(())
+    }
+
+    /// Note an operation's failure for the report at the end of the build
+    fn failed(&self, failure: Failure) {
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.push(failure);
+        }
+    }
+
+    /// The version an external tool reports, asking it only once per build
+    async fn tool_version(&self, tool: &str) -> Option<String> {
+        if let Some(version) = self.tool_versions.get(tool) {
+            return version.clone();
+        }
+        let version = tokio::process::Command::new(tool)
+            .arg("--version")
+            .output()
+            .await
+            .ok()
+            .and_then(|output| {
+                [output.stdout, output.stderr].iter().find_map(|text| {
+                    String::from_utf8_lossy(text)
+                        .lines()
+                        .map(str::trim)
+                        .find(|line| !line.is_empty())
+                        .map(String::from)
+                })
+            });
+        self.tool_versions.insert(tool.to_string(), version.clone());
+        version
+    }
+
+    /// Append a record of an operation we ran to the audit log
+    ///
+    /// The log is `.gftools-builder.log` in the config file's directory, one
+    /// JSON object per line. Failing to write it doesn't fail the build.
+    /// Versions are only asked of the tools in [VERSIONED_TOOLS].
+    async fn audit(
+        &self,
+        op: &BuildStep,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+        result: Result<&Output, String>,
+        duration: std::time::Duration,
+        commands: Vec<String>,
+    ) {
+        if matches!(op.shortname(), "Source" | "Sink") {
+            return;
+        }
+        let mut tools = std::collections::BTreeMap::new();
+        for tool in commands
+            .iter()
+            .filter_map(|command| command.split_whitespace().next())
+            .filter(|tool| VERSIONED_TOOLS.contains(tool))
+        {
+            if !tools.contains_key(tool) {
+                tools.insert(tool.to_string(), self.tool_version(tool).await);
+            }
+        }
+        let time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or_default();
+        let (status, error) = match result {
+            Ok(output) => (output.status.code(), None),
+            Err(error) => (None, Some(error)),
+        };
+        let entry = serde_json::json!({
+            "time": time,
+            "builderVersion": env!("CARGO_PKG_VERSION"),
+            "operation": op.identifier(),
+            "inputs": inputs.iter().map(resolved_path).collect::<Vec<_>>(),
+            "outputs": outputs.iter().map(resolved_path).collect::<Vec<_>>(),
+            "status": status,
+            "error": error,
+            "durationMs": duration.as_millis() as u64,
+            "commands": commands,
+            "tools": tools,
+        });
+
+        let Ok(mut log) = self.audit_log.lock() else {
+            return;
+        };
+        if log.is_none() {
+            match std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(".gftools-builder.log")
+            {
+                Ok(file) => *log = Some(file),
+                Err(e) => {
+                    log::warn!("Could not open audit log: {e}");
+                    return;
+                }
+            }
+        }
+        if let Some(file) = log.as_mut()
+            && let Err(e) = writeln!(file, "{entry}")
+        {
+            log::warn!("Could not write to audit log: {e}");
+        }
+    }
+
+    /// Note that an operation has read its inputs, and release any in-memory
+    /// outputs which nothing else is waiting for.
+    fn consumed(&self, in_edges: &[EdgeReference<BuildEdge>]) -> Result<(), ApplicationError> {
+        let mut producers = in_edges
+            .iter()
+            .map(|edge| edge.source())
+            .collect::<Vec<_>>();
+        producers.sort();
+        producers.dedup();
+        for producer in producers {
+            let done = match self.pending_consumers.get_mut(&producer) {
+                Some(mut pending) => {
+                    *pending = pending.saturating_sub(1);
+                    *pending == 0
+                }
+                None => false,
+            };
+            if done {
+                for edge in self
+                    .configuration
+                    .graph()
+                    .edges_directed(producer, Direction::Outgoing)
+                {
+                    edge.weight().output.release()?;
+                }
+            }
+        }
+        Ok(())
     }
 
     pub fn console(&self) -> &Mutex<()> {
@@ -396,13 +915,4 @@ impl Context {
 
         Ok(output)
     }
-
-    pub async fn print_description(&self, description: &str) {
-        if self.progress {
-            let _ = self.progressbars.println(description);
-        } else {
-            let _console_lock = self.console.lock().await;
-            println!("{description}");
-        }
-    }
 }
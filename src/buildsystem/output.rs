@@ -1,9 +1,19 @@
 use babelfont::Font;
 // use fontations::read::FontRef;
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
 use tempfile::NamedTempFile;
 
-use crate::error::ApplicationError;
+use crate::{
+    buildsystem::{
+        SourceKind,
+        environment::command_path,
+        scratch::{scratch_file, scratch_file_for},
+    },
+    error::ApplicationError,
+};
 
 /// An output from an operation
 ///
@@ -32,14 +42,25 @@ use crate::error::ApplicationError;
 ///    of the build process.
 /// 2. Temporary files: these are files which have a name on disk, but we don't care what the
 ///    name is; usually intermediate files in the build process passed to external processes.
-/// 3. In-memory bytes: these are just `Vec<u8>`s stored in memory; usually intermediate files
-///    in the build process passed to internal Rust functions.
+/// 3. In-memory bytes: these are just bytes stored in memory; usually intermediate files
+///    in the build process passed to internal Rust functions. They're held as `Arc<[u8]>`s, so
+///    an output which feeds several operations is shared between them rather than copied.
+///
+/// On top of these, consecutive operations which work on font objects can hand them over
+/// directly: source fonts as babelfont `Font`s, and binary fonts as bytes which we know to
+/// be a font.
 #[derive(Debug)]
 pub enum RawOperationOutput {
     NamedFile(String),
     TemporaryFile(Option<NamedTempFile>),
-    InMemoryBytes(Vec<u8>),
+    InMemoryBytes(Arc<[u8]>),
+    /// A font source object, passed between operations without serializing it
     SourceFont(Box<babelfont::Font>),
+    /// The bytes of a binary font; unlike plain bytes, we know it's a font
+    /// and so what to call it when it has to hit the disk
+    BinaryFont(Arc<[u8]>),
+    /// In-memory contents which were dropped once every consumer had read them
+    Released,
 }
 
 impl PartialEq for RawOperationOutput {
@@ -50,6 +71,7 @@ impl PartialEq for RawOperationOutput {
                 a.as_ref().map(|f| f.path()) == b.as_ref().map(|f| f.path())
             }
             (RawOperationOutput::InMemoryBytes(a), RawOperationOutput::InMemoryBytes(b)) => a == b,
+            (RawOperationOutput::BinaryFont(a), RawOperationOutput::BinaryFont(b)) => a == b,
             (RawOperationOutput::SourceFont(a), RawOperationOutput::SourceFont(b)) => {
                 std::ptr::eq(a, b) // This is probably wrong and definitely evil.
             }
@@ -76,6 +98,29 @@ impl From<Font> for RawOperationOutput {
     }
 }
 
+/// Read-only access to the contents of an output; see [OperationOutput::to_mapped_bytes]
+pub enum MappedBytes {
+    Mapped(memmap2::Mmap),
+    Shared(Arc<[u8]>),
+}
+
+impl std::ops::Deref for MappedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            MappedBytes::Mapped(mmap) => mmap,
+            MappedBytes::Shared(bytes) => bytes,
+        }
+    }
+}
+
+impl AsRef<[u8]> for MappedBytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
 /// A thread-safe, reference-counted wrapper around [RawOperationOutput]
 #[derive(Clone)]
 pub struct OperationOutput(Arc<Mutex<RawOperationOutput>>);
@@ -107,6 +152,8 @@ impl std::fmt::Display for OperationOutput {
             RawOperationOutput::NamedFile(name) => write!(f, "{name}"),
             RawOperationOutput::TemporaryFile(_) => write!(f, "<temporary file>"),
             RawOperationOutput::InMemoryBytes(_) => write!(f, "<in-memory bytes>"),
+            RawOperationOutput::BinaryFont(_) => write!(f, "<in-memory font>"),
+            RawOperationOutput::Released => write!(f, "<released>"),
             RawOperationOutput::SourceFont(font) => write!(
                 f,
                 "<{} source>",
@@ -129,6 +176,8 @@ impl std::fmt::Debug for OperationOutput {
                 write!(f, "NamedTemporaryFile({})", x.path().to_string_lossy())
             }
             RawOperationOutput::InMemoryBytes(_) => write!(f, "InMemoryBytes"),
+            RawOperationOutput::BinaryFont(_) => write!(f, "BinaryFont"),
+            RawOperationOutput::Released => write!(f, "Released"),
             RawOperationOutput::SourceFont(font) => {
                 write!(
                     f,
@@ -153,31 +202,31 @@ impl OperationOutput {
     /// If the output is in-memory bytes, writes the bytes to a temporary file and returns the temp file name.
     pub fn to_filename(&self, suffix: Option<&str>) -> Result<String, ApplicationError> {
         let mut f = self.lock().map_err(|_| ApplicationError::MutexPoisoned)?;
+        let is_font = matches!(&*f, RawOperationOutput::BinaryFont(_));
         match &mut *f {
-            RawOperationOutput::NamedFile(name) => Ok(name.to_string()),
+            RawOperationOutput::NamedFile(name) => Ok(command_path(name)),
+            RawOperationOutput::Released => Err(ApplicationError::Other(
+                "Output was released after its last consumer".to_string(),
+            )),
             RawOperationOutput::TemporaryFile(x) => {
                 // if it's none, make one and set it to some
                 if let Some(temp_file) = x {
                     Ok(temp_file.path().to_string_lossy().to_string())
                 } else {
-                    let temp_file = if let Some(suffix) = suffix {
-                        NamedTempFile::with_suffix(suffix)
-                    } else {
-                        NamedTempFile::new()
-                    }
-                    .map_err(|e| ApplicationError::Other(e.to_string()))?;
+                    let temp_file = scratch_file(suffix)?;
                     *x = Some(temp_file);
                     Ok(x.as_ref().unwrap().path().to_string_lossy().to_string())
                 }
             }
-            RawOperationOutput::InMemoryBytes(bytes) => {
-                // Convert in-memory bytes to a temp file by writing it
-                let temp_file = if let Some(suffix) = suffix {
-                    NamedTempFile::with_suffix(suffix)
-                } else {
-                    NamedTempFile::new()
-                }
-                .map_err(|e| ApplicationError::Other(e.to_string()))?;
+            RawOperationOutput::InMemoryBytes(bytes) | RawOperationOutput::BinaryFont(bytes) => {
+                // Convert in-memory bytes to a temp file by writing it; a binary
+                // font is a TrueType font unless we're told otherwise
+                let suffix = match (suffix, is_font) {
+                    (Some(suffix), _) => Some(suffix),
+                    (None, true) => Some(".ttf"),
+                    (None, false) => None,
+                };
+                let temp_file = scratch_file_for(bytes, suffix)?;
                 // write
                 let temp_path = temp_file.path();
                 let temp_path_string = temp_path.to_string_lossy().to_string();
@@ -188,8 +237,7 @@ impl OperationOutput {
             }
             RawOperationOutput::SourceFont(font) => {
                 // Convert in-memory bytes to a temp file by writing it in Glyphs format
-                let temp_file = NamedTempFile::with_suffix(".glyphs")
-                    .map_err(|e| ApplicationError::Other(e.to_string()))?;
+                let temp_file = scratch_file(Some(".glyphs"))?;
                 // write
                 let temp_path = temp_file.path();
                 let temp_path_string = temp_path.to_string_lossy().to_string();
@@ -207,7 +255,8 @@ impl OperationOutput {
     /// Use this when you have completed an operation and want to store the output bytes.
     /// This differs from `set_contents` in that it always sets the output to in-memory bytes,
     /// whereas `set_contents` will write to a named file if the output is a named file.
-    pub fn set_bytes(&self, bytes: Vec<u8>) -> Result<(), ApplicationError> {
+    pub fn set_bytes(&self, bytes: impl Into<Arc<[u8]>>) -> Result<(), ApplicationError> {
+        let bytes: Arc<[u8]> = bytes.into();
         let mut f = self.lock().map_err(|_| ApplicationError::MutexPoisoned)?;
         match &mut *f {
             RawOperationOutput::NamedFile(name) => {
@@ -224,52 +273,137 @@ impl OperationOutput {
         Ok(())
     }
 
+    /// Set the contents of the OperationOutput to the bytes of a binary font.
+    ///
+    /// Like `set_bytes`, but an in-memory result is kept as a binary font.
+    pub fn set_binary_font(&self, bytes: impl Into<Arc<[u8]>>) -> Result<(), ApplicationError> {
+        let bytes: Arc<[u8]> = bytes.into();
+        let mut f = self.lock().map_err(|_| ApplicationError::MutexPoisoned)?;
+        match &mut *f {
+            RawOperationOutput::NamedFile(name) => {
+                std::fs::write(&*name, bytes).map_err(|e| ApplicationError::Other(e.to_string()))?;
+            }
+            RawOperationOutput::TemporaryFile(Some(temp_file)) => {
+                std::fs::write(temp_file.path(), bytes)
+                    .map_err(|e| ApplicationError::Other(e.to_string()))?;
+            }
+            _ => {
+                *f = RawOperationOutput::BinaryFont(bytes);
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the contents of an in-memory output.
+    ///
+    /// The orchestrator calls this once every operation consuming the output has
+    /// finished, so that long builds don't keep every intermediate font in memory.
+    /// Outputs on disk are left alone.
+    pub fn release(&self) -> Result<(), ApplicationError> {
+        let mut f = self.lock().map_err(|_| ApplicationError::MutexPoisoned)?;
+        if matches!(
+            &*f,
+            RawOperationOutput::InMemoryBytes(_)
+                | RawOperationOutput::BinaryFont(_)
+                | RawOperationOutput::SourceFont(_)
+        ) {
+            *f = RawOperationOutput::Released;
+        }
+        Ok(())
+    }
+
+    /// Whether two outputs are the same shared output, rather than equal ones
+    pub(crate) fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
     /// Returns true if the OperationOutput is a named file.
     pub fn is_named_file(&self) -> bool {
         let f = self.lock().unwrap();
         matches!(&*f, RawOperationOutput::NamedFile(_))
     }
 
+    /// Returns true if the OperationOutput is a babelfont Font object.
+    pub fn is_source_font(&self) -> bool {
+        let f = self.lock().unwrap();
+        matches!(&*f, RawOperationOutput::SourceFont(_))
+    }
+
     /// Gets the contents of the OperationOutput as bytes.
     ///
     /// Use this when you need to read the output of an operation as bytes.
     /// If the output is a named file or temporary file, reads the file contents.
-    /// If the output is in-memory bytes, returns the bytes directly.
-    pub fn to_bytes(&self) -> Result<Vec<u8>, ApplicationError> {
+    /// If the output is in-memory bytes, returns the bytes directly; this is a cheap
+    /// clone of a shared buffer, not a copy.
+    pub fn to_bytes(&self) -> Result<Arc<[u8]>, ApplicationError> {
         let f = self.lock().map_err(|_| ApplicationError::MutexPoisoned)?;
         match &*f {
             RawOperationOutput::NamedFile(name) => {
                 // Read the file contents
                 let bytes =
                     std::fs::read(name).map_err(|e| ApplicationError::Other(e.to_string()))?;
-                Ok(bytes)
+                Ok(bytes.into())
             }
             RawOperationOutput::TemporaryFile(Some(temp_file)) => {
                 // Read the temp file contents
                 let bytes = std::fs::read(temp_file.path())
                     .map_err(|e| ApplicationError::Other(e.to_string()))?;
-                Ok(bytes)
+                Ok(bytes.into())
             }
             RawOperationOutput::TemporaryFile(None) => Err(ApplicationError::Other(
                 "Temporary file is not set".to_string(),
             )),
-            RawOperationOutput::InMemoryBytes(bytes) => Ok(bytes.clone()),
-            RawOperationOutput::SourceFont(_font) => {
+            RawOperationOutput::Released => Err(ApplicationError::Other(
+                "Output was released after its last consumer".to_string(),
+            )),
+            RawOperationOutput::InMemoryBytes(bytes) | RawOperationOutput::BinaryFont(bytes) => {
+                Ok(bytes.clone())
+            }
+            RawOperationOutput::SourceFont(_) => {
                 // Convert in-memory bytes to a temp file by writing it in Glyphs format
                 // Unfortunately this currently requires a temp file on disk
+                drop(f); // Release the lock before calling to_filename which needs it
                 let temp_file = self.to_filename(Some(".glyphs"))?;
                 // Noe read the bytes back
                 let buffer =
                     std::fs::read(temp_file).map_err(|e| ApplicationError::Other(e.to_string()))?;
-                Ok(buffer)
+                Ok(buffer.into())
             }
         }
     }
 
+    /// Gets read-only access to the contents of the OperationOutput.
+    ///
+    /// Use this instead of `to_bytes` when an operation only reads its input.
+    /// Files on disk are memory-mapped rather than read in, so that parallel
+    /// operations looking at large fonts don't each hold a copy of them.
+    /// Anything else falls back to `to_bytes`.
+    pub fn to_mapped_bytes(&self) -> Result<MappedBytes, ApplicationError> {
+        let path = {
+            let f = self.lock().map_err(|_| ApplicationError::MutexPoisoned)?;
+            match &*f {
+                RawOperationOutput::NamedFile(name) => Some(std::path::PathBuf::from(name)),
+                RawOperationOutput::TemporaryFile(Some(temp_file)) => {
+                    Some(temp_file.path().to_path_buf())
+                }
+                _ => None,
+            }
+        };
+        let Some(path) = path else {
+            return Ok(MappedBytes::Shared(self.to_bytes()?));
+        };
+        let file = std::fs::File::open(&path)?;
+        // SAFETY: the mapping is only read, and an output's file isn't
+        // written to while the operations consuming it are running.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(MappedBytes::Mapped(mmap))
+    }
+
     /// Set the contents of the OperationOutput to the given bytes.
     ///
     /// If the output is a named file, writes the bytes to the file.
-    pub fn set_contents(&self, bytes: Vec<u8>) -> Result<(), ApplicationError> {
+    pub fn set_contents(&self, bytes: impl Into<Arc<[u8]>>) -> Result<(), ApplicationError> {
+        let bytes: Arc<[u8]> = bytes.into();
         if self.is_named_file() {
             // OK, we write it
             let output_path = self.to_filename(None)?;
@@ -279,6 +413,54 @@ impl OperationOutput {
         }
     }
 
+    /// Point the OperationOutput at an existing file on disk.
+    ///
+    /// Use this when an operation passes its input through unchanged. If the output
+    /// is a named file, the file is copied there; otherwise the output simply refers
+    /// to the existing file, so no copy is made. A designspace names its UFOs relative
+    /// to itself, so a copied designspace has them resolved against the directory of
+    /// the original.
+    pub fn set_path(&self, path: &str) -> Result<(), ApplicationError> {
+        if self.is_named_file() {
+            let output_path = self.to_filename(None)?;
+            if SourceKind::from_path(path) == SourceKind::Designspace {
+                let from_dir = Path::new(path)
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."));
+                let text = std::fs::read_to_string(path)?;
+                std::fs::write(output_path, rebase_designspace(&text, from_dir)?)?;
+            } else {
+                std::fs::copy(path, output_path)?;
+            }
+        } else {
+            let mut f = self.lock()?;
+            *f = RawOperationOutput::NamedFile(path.to_string());
+        }
+        Ok(())
+    }
+
+    /// Convert the OperationOutput to the filename of a font source which a tool can read.
+    ///
+    /// `reads` says which kinds of source the tool understands. A source of any other
+    /// kind is loaded with babelfont and written out as a Glyphs file, which is returned
+    /// along with the filename so that it lives as long as the caller needs it.
+    pub fn to_source_filename(
+        &self,
+        reads: impl Fn(SourceKind) -> bool,
+    ) -> Result<(String, Option<NamedTempFile>), ApplicationError> {
+        let filename = self.to_filename(Some(".glyphs"))?;
+        if reads(SourceKind::from_path(&filename)) {
+            return Ok((filename, None));
+        }
+        let font = self.to_font_source()?;
+        let temp_file = scratch_file(Some(".glyphs"))?;
+        font.save(temp_file.path())
+            .map_err(|e| ApplicationError::Other(format!("Could not convert {filename}: {e}")))?;
+        let path = temp_file.path().to_string_lossy().to_string();
+        Ok((path, Some(temp_file)))
+    }
+
     /// Convert the OperationOutput to a babelfont Font object.
     ///
     /// Use this when you need to work with the font source data directly.
@@ -305,7 +487,10 @@ impl OperationOutput {
             RawOperationOutput::TemporaryFile(None) => Err(ApplicationError::Other(
                 "Temporary file is not set".to_string(),
             )),
-            RawOperationOutput::InMemoryBytes(_) => {
+            RawOperationOutput::Released => Err(ApplicationError::Other(
+                "Output was released after its last consumer".to_string(),
+            )),
+            RawOperationOutput::InMemoryBytes(_) | RawOperationOutput::BinaryFont(_) => {
                 // Need to write to temp file first, then load
                 drop(f); // Release the lock before calling to_filename which needs it
                 let temp_filename = self.to_filename(None)?;
@@ -336,3 +521,124 @@ impl OperationOutput {
         Ok(())
     }
 }
+
+/// Make the relative `filename` of each `<source>` in a designspace absolute,
+/// resolving it against `from_dir`, the directory the designspace lives in
+///
+/// Other elements' filenames, such as an `<instance>`'s or a `<variable-font>`'s,
+/// name outputs rather than the UFOs we read, so they're left alone.
+fn rebase_designspace(text: &str, from_dir: &Path) -> Result<String, ApplicationError> {
+    const ELEMENT: &str = "<source";
+    let base = std::path::absolute(from_dir)?
+        .to_string_lossy()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;");
+    let mut rebased = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(ELEMENT) {
+        let (before, from_tag) = rest.split_at(start);
+        rebased.push_str(before);
+        let tag_len = from_tag.find('>').map_or(from_tag.len(), |end| end + 1);
+        let (tag, after) = from_tag.split_at(tag_len);
+        // `<sources>` starts the same way
+        if tag[ELEMENT.len()..].starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>') {
+            rebased.push_str(&rebase_filename(tag, &base));
+        } else {
+            rebased.push_str(tag);
+        }
+        rest = after;
+    }
+    rebased.push_str(rest);
+    Ok(rebased)
+}
+
+/// Put `base` in front of the `filename` attribute of a tag, if it's relative
+fn rebase_filename(tag: &str, base: &str) -> String {
+    const ATTRIBUTE: &str = "filename=";
+    let Some(start) = tag
+        .match_indices(ATTRIBUTE)
+        .map(|(index, _)| index)
+        .find(|&index| tag[..index].ends_with(char::is_whitespace))
+    else {
+        return tag.to_string();
+    };
+    let value_start = start + ATTRIBUTE.len();
+    let Some(quote) = tag[value_start..]
+        .chars()
+        .next()
+        .filter(|c| *c == '"' || *c == '\'')
+    else {
+        return tag.to_string();
+    };
+    let value = &tag[value_start + 1..];
+    let Some(end) = value.find(quote) else {
+        return tag.to_string();
+    };
+    let filename = &value[..end];
+    if Path::new(filename).is_absolute() {
+        return tag.to_string();
+    }
+    format!(
+        "{}{quote}{base}/{filename}{}",
+        &tag[..value_start],
+        &value[end..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{buildsystem::Operation, operations::convert::SourceFontToBytes};
+
+    #[test]
+    fn test_source_font_to_bytes() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/resources/radio-canada/RadioCanadaDisplay.glyphs"
+        );
+        let font = babelfont::load(path).unwrap();
+        let input = OperationOutput::from(RawOperationOutput::from(font));
+        let output = OperationOutput::from(RawOperationOutput::TemporaryFile(None));
+        SourceFontToBytes
+            .execute(std::slice::from_ref(&input), std::slice::from_ref(&output))
+            .unwrap();
+        let glyphs = String::from_utf8(output.to_bytes().unwrap().to_vec()).unwrap();
+        assert!(glyphs.contains("Radio Canada Display"));
+    }
+    #[test]
+    fn test_rebase_designspace_moves_only_sources() {
+        let designspace = r#"<?xml version="1.0" encoding="UTF-8"?>
+<designspace format="5.0">
+  <sources>
+    <source filename="masters/Foo-Light.ufo" name="Light"/>
+    <source name="Bold" filename='/fonts/Foo-Bold.ufo'>
+      <location/>
+    </source>
+  </sources>
+  <variable-fonts>
+    <variable-font name="Foo" filename="Foo-VF.ttf"/>
+  </variable-fonts>
+  <instances>
+    <instance filename="instances/Foo-Regular.ufo" stylename="Regular"/>
+  </instances>
+</designspace>
+"#;
+        let dir = tempfile::TempDir::new().unwrap();
+        let base = std::path::absolute(dir.path()).unwrap();
+        let rebased = rebase_designspace(designspace, dir.path()).unwrap();
+        assert!(rebased.contains(&format!(
+            r#"<source filename="{}/masters/Foo-Light.ufo" name="Light"/>"#,
+            base.display()
+        )));
+        assert!(rebased.contains(r#"<source name="Bold" filename='/fonts/Foo-Bold.ufo'>"#));
+        assert!(rebased.contains(r#"<variable-font name="Foo" filename="Foo-VF.ttf"/>"#));
+        assert!(
+            rebased.contains(
+                r#"<instance filename="instances/Foo-Regular.ufo" stylename="Regular"/>"#
+            )
+        );
+        assert!(rebased.contains("<sources>"));
+    }
+}
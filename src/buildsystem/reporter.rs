@@ -0,0 +1,270 @@
+//! How the build tells the user what it's doing
+//!
+//! The orchestrator reports each operation as it starts, finishes or fails,
+//! and a [Reporter] decides what (if anything) to print. Each report is
+//! written with a single locked write, so reports from operations running in
+//! parallel don't interleave.
+use std::{
+    io::Write,
+    process::Output,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use indicatif::MultiProgress;
+use serde_json::json;
+
+pub trait Reporter: Send + Sync {
+    /// An operation is about to run
+    fn started(&self, description: &str);
+
+    /// An operation ran successfully, printing `output` as it went
+    fn finished(&self, targets: &str, description: &str, output: &Output, duration: Duration);
+
+    /// An operation failed; we have its output if it got as far as running a tool
+    fn failed(&self, targets: &str, description: &str, error: &str, output: Option<&Output>);
+
+    /// An operation found a problem which doesn't stop the build, such as a
+    /// QA check failing. It has already been logged.
+    fn warned(&self, _targets: &str, _description: &str, _warning: &str) {}
+}
+
+/// The reporters which can be chosen on the command line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReporterKind {
+    /// Describe each operation, with progress bars if they're enabled
+    #[default]
+    Console,
+    /// Only print the output of operations which fail
+    Quiet,
+    /// Print one JSON object per line for each operation started, finished or failed
+    Json,
+    /// Plain, timestamped lines with no progress bars, for CI logs
+    Ci,
+    /// Like `ci`, plus workflow commands which make GitHub Actions annotate
+    /// the run with failures and QA warnings
+    Github,
+}
+
+impl ReporterKind {
+    /// Whether progress bars make sense alongside this reporter
+    pub fn allows_progress(self) -> bool {
+        self == ReporterKind::Console
+    }
+
+    pub fn reporter(self, progress: Option<MultiProgress>) -> Box<dyn Reporter> {
+        match self {
+            ReporterKind::Console => Box::new(ConsoleReporter { progress }),
+            ReporterKind::Quiet => Box::new(QuietReporter),
+            ReporterKind::Json => Box::new(JsonReporter),
+            ReporterKind::Ci => Box::new(CiReporter),
+            ReporterKind::Github => Box::new(GithubReporter),
+        }
+    }
+}
+
+/// Prefix each line of what a tool printed with the targets it was building
+fn prefixed(targets: &str, text: &[u8]) -> String {
+    String::from_utf8_lossy(text)
+        .lines()
+        .map(|line| format!("[{targets}] {line}\n"))
+        .collect()
+}
+
+/// Write a tool's output in one go, each line prefixed with its targets
+fn write_output(targets: &str, output: &Output) {
+    let (out, err) = (
+        prefixed(targets, &output.stdout),
+        prefixed(targets, &output.stderr),
+    );
+    if !out.is_empty() {
+        let _ = std::io::stdout().lock().write_all(out.as_bytes());
+    }
+    if !err.is_empty() {
+        let _ = std::io::stderr().lock().write_all(err.as_bytes());
+    }
+}
+
+fn write_line(line: &str) {
+    let _ = writeln!(std::io::stdout().lock(), "{line}");
+}
+
+pub struct ConsoleReporter {
+    progress: Option<MultiProgress>,
+}
+
+impl ConsoleReporter {
+    fn print(&self, write: impl FnOnce()) {
+        match &self.progress {
+            Some(progress) => progress.suspend(write),
+            None => write(),
+        }
+    }
+}
+
+impl Reporter for ConsoleReporter {
+    fn started(&self, description: &str) {
+        match &self.progress {
+            Some(progress) => {
+                let _ = progress.println(description);
+            }
+            None => write_line(description),
+        }
+    }
+
+    fn finished(&self, targets: &str, _description: &str, output: &Output, _duration: Duration) {
+        // Tools are chatty, so we only pass on what they said when asked to
+        if log::log_enabled!(log::Level::Info) {
+            self.print(|| write_output(targets, output));
+        }
+    }
+
+    fn failed(&self, targets: &str, _description: &str, _error: &str, output: Option<&Output>) {
+        // The error itself is what the build returns, so it gets printed then
+        if let Some(output) = output {
+            self.print(|| write_output(targets, output));
+        }
+    }
+}
+
+pub struct QuietReporter;
+
+impl Reporter for QuietReporter {
+    fn started(&self, _description: &str) {}
+
+    fn finished(&self, _targets: &str, _description: &str, _output: &Output, _duration: Duration) {}
+
+    fn failed(&self, targets: &str, _description: &str, _error: &str, output: Option<&Output>) {
+        if let Some(output) = output {
+            write_output(targets, output);
+        }
+    }
+}
+
+pub struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn started(&self, description: &str) {
+        write_line(&json!({"event": "started", "description": description}).to_string());
+    }
+
+    fn finished(&self, targets: &str, description: &str, output: &Output, duration: Duration) {
+        write_line(
+            &json!({
+                "event": "finished",
+                "targets": targets,
+                "description": description,
+                "durationMs": duration.as_millis() as u64,
+                "stdout": String::from_utf8_lossy(&output.stdout),
+                "stderr": String::from_utf8_lossy(&output.stderr),
+            })
+            .to_string(),
+        );
+    }
+
+    fn failed(&self, targets: &str, description: &str, error: &str, output: Option<&Output>) {
+        write_line(
+            &json!({
+                "event": "failed",
+                "targets": targets,
+                "description": description,
+                "error": error,
+                "stdout": output.map(|output| String::from_utf8_lossy(&output.stdout)),
+                "stderr": output.map(|output| String::from_utf8_lossy(&output.stderr)),
+            })
+            .to_string(),
+        );
+    }
+}
+
+pub struct CiReporter;
+
+impl CiReporter {
+    fn timestamp() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or_default()
+    }
+}
+
+impl Reporter for CiReporter {
+    fn started(&self, description: &str) {
+        write_line(&format!("[{}] {description}", Self::timestamp()));
+    }
+
+    fn finished(&self, targets: &str, description: &str, output: &Output, duration: Duration) {
+        let mut report = format!(
+            "[{}] done in {:.1}s: {description}\n",
+            Self::timestamp(),
+            duration.as_secs_f64()
+        );
+        if log::log_enabled!(log::Level::Info) {
+            report.push_str(&prefixed(targets, &output.stdout));
+            report.push_str(&prefixed(targets, &output.stderr));
+        }
+        let _ = std::io::stdout().lock().write_all(report.as_bytes());
+    }
+
+    fn failed(&self, targets: &str, description: &str, error: &str, output: Option<&Output>) {
+        let mut report = String::new();
+        if let Some(output) = output {
+            report.push_str(&prefixed(targets, &output.stdout));
+            report.push_str(&prefixed(targets, &output.stderr));
+        }
+        report.push_str(&format!(
+            "[{}] error: {description}: {error}\n",
+            Self::timestamp()
+        ));
+        let _ = std::io::stderr().lock().write_all(report.as_bytes());
+    }
+}
+
+pub struct GithubReporter;
+
+impl GithubReporter {
+    /// Escape text for a workflow command's message
+    fn escape_data(text: &str) -> String {
+        text.replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    }
+
+    /// Escape text for a workflow command's properties
+    fn escape_property(text: &str) -> String {
+        Self::escape_data(text)
+            .replace(':', "%3A")
+            .replace(',', "%2C")
+    }
+
+    /// An `::error` or `::warning` command, attached to the first target
+    fn annotation(level: &str, targets: &str, description: &str, message: &str) -> String {
+        let file = targets.split(", ").next().unwrap_or(targets);
+        format!(
+            "::{level} file={},title={}::{}\n",
+            Self::escape_property(file),
+            Self::escape_property(description),
+            Self::escape_data(message)
+        )
+    }
+}
+
+impl Reporter for GithubReporter {
+    fn started(&self, description: &str) {
+        CiReporter.started(description);
+    }
+
+    fn finished(&self, targets: &str, description: &str, output: &Output, duration: Duration) {
+        CiReporter.finished(targets, description, output, duration);
+    }
+
+    fn failed(&self, targets: &str, description: &str, error: &str, output: Option<&Output>) {
+        CiReporter.failed(targets, description, error, output);
+        let annotation = Self::annotation("error", targets, description, error);
+        let _ = std::io::stdout().lock().write_all(annotation.as_bytes());
+    }
+
+    fn warned(&self, targets: &str, description: &str, warning: &str) {
+        let annotation = Self::annotation("warning", targets, description, warning);
+        let _ = std::io::stdout().lock().write_all(annotation.as_bytes());
+    }
+}
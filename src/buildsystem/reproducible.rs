@@ -0,0 +1,84 @@
+//! Reproducible builds
+//!
+//! With `--reproducible`, the tools we run are given `SOURCE_DATE_EPOCH`,
+//! each font an operation makes has its `head` timestamps set from it and
+//! any `DSIG` table dropped, and temporary files are named after what they
+//! hold rather than at random, so that building the same sources twice
+//! gives byte-identical fonts.
+use read_fonts::{FontRef, TableProvider, types::Tag};
+use write_fonts::{FontBuilder, from_obj::ToOwnedTable, tables::head::Head, types::LongDateTime};
+
+use crate::{buildsystem::OperationOutput, error::ApplicationError};
+
+/// Seconds from the font epoch (1904) to the Unix epoch (1970)
+const FONT_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// The time to build fonts as of: `SOURCE_DATE_EPOCH` if it's set, and
+/// otherwise the Unix epoch
+pub fn source_date_epoch() -> i64 {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Date a font as of `epoch` and drop its DSIG table
+///
+/// Returns `None` if the bytes aren't a font, or if there's nothing to change.
+pub(crate) fn normalize_font(
+    bytes: &[u8],
+    epoch: i64,
+) -> Result<Option<Vec<u8>>, ApplicationError> {
+    let Ok(font) = FontRef::new(bytes) else {
+        return Ok(None);
+    };
+    let Ok(head) = font.head() else {
+        return Ok(None);
+    };
+    let dsig = Tag::new(b"DSIG");
+    let date = LongDateTime::new(epoch + FONT_EPOCH_OFFSET);
+    let mut head: Head = head.to_owned_table();
+    if head.created == date && head.modified == date && font.table_data(dsig).is_none() {
+        return Ok(None);
+    }
+    head.created = date;
+    head.modified = date;
+
+    let mut builder = FontBuilder::new();
+    builder.add_table(&head)?;
+    for record in font.table_directory.table_records() {
+        let tag = record.tag();
+        if tag == dsig || tag == Tag::new(b"head") {
+            continue;
+        }
+        if let Some(data) = font.table_data(tag) {
+            builder.add_raw(tag, data);
+        }
+    }
+    Ok(Some(builder.build()))
+}
+
+/// Normalize the fonts an operation has just made
+///
+/// `was_named` says which outputs were files on disk before the operation
+/// ran. An output which has become a file since was passed through from an
+/// input, which could be one of the sources, so it's left alone.
+pub(crate) fn normalize_outputs(
+    outputs: &[OperationOutput],
+    was_named: &[bool],
+    epoch: i64,
+) -> Result<(), ApplicationError> {
+    for (output, was_named) in outputs.iter().zip(was_named) {
+        if output.is_source_font() || (output.is_named_file() && !was_named) {
+            continue;
+        }
+        // Outputs the operation didn't write have nothing to normalize
+        let Ok(bytes) = output.to_bytes() else {
+            continue;
+        };
+        if let Some(normalized) = normalize_font(&bytes, epoch)? {
+            output.set_binary_font(normalized)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! Per-operation scratch directories
+//!
+//! While an operation runs, the temporary files it makes (including those
+//! made when its inputs and outputs are turned into files) go in a directory
+//! of its own, so that after a failure we can see exactly what it was
+//! working with.
+//!
+//! In a reproducible build, the files are named after what they hold (or,
+//! for files a tool will write, in the order they're made) rather than at
+//! random, in case a tool records the names of the files it reads.
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+
+use crate::error::ApplicationError;
+
+/// The scratch directory of an operation
+struct Scratch {
+    dir: PathBuf,
+    /// Whether its files should outlive it
+    keep: bool,
+    /// Whether to name its files predictably
+    stable_names: bool,
+    /// How many files have been made in it
+    count: usize,
+}
+
+thread_local! {
+    /// The scratch directory of the operation running on this thread
+    static SCRATCH_DIR: RefCell<Option<Scratch>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with temporary files going to `dir`
+///
+/// If `keep` is set, the files aren't deleted when dropped, so that they're
+/// still there if the operation fails; the orchestrator tidies up instead.
+pub(crate) fn with_scratch_dir<T>(
+    dir: &Path,
+    keep: bool,
+    stable_names: bool,
+    f: impl FnOnce() -> T,
+) -> T {
+    SCRATCH_DIR.with(|scratch| {
+        *scratch.borrow_mut() = Some(Scratch {
+            dir: dir.to_path_buf(),
+            keep,
+            stable_names,
+            count: 0,
+        })
+    });
+    let result = f();
+    SCRATCH_DIR.with(|scratch| *scratch.borrow_mut() = None);
+    result
+}
+
+/// Make a temporary file in the running operation's scratch directory, or
+/// the system's temporary directory outside of an operation
+pub fn scratch_file(suffix: Option<&str>) -> Result<NamedTempFile, ApplicationError> {
+    make_scratch_file(None, suffix)
+}
+
+/// Make a temporary file to hold `contents`, which is named after them in a
+/// reproducible build
+pub(crate) fn scratch_file_for(
+    contents: &[u8],
+    suffix: Option<&str>,
+) -> Result<NamedTempFile, ApplicationError> {
+    make_scratch_file(Some(contents), suffix)
+}
+
+fn make_scratch_file(
+    contents: Option<&[u8]>,
+    suffix: Option<&str>,
+) -> Result<NamedTempFile, ApplicationError> {
+    let mut builder = tempfile::Builder::new();
+    if let Some(suffix) = suffix {
+        builder.suffix(suffix);
+    }
+    SCRATCH_DIR
+        .with(|scratch| match &mut *scratch.borrow_mut() {
+            Some(scratch) if scratch.stable_names => {
+                scratch.count += 1;
+                // The count keeps two files with the same contents apart
+                let name = match contents {
+                    Some(contents) => format!(
+                        "{}-{}",
+                        &format!("{:x}", Sha256::digest(contents))[..16],
+                        scratch.count
+                    ),
+                    None => format!("tmp-{}", scratch.count),
+                };
+                builder
+                    .prefix(&name)
+                    .rand_bytes(0)
+                    .disable_cleanup(scratch.keep)
+                    .tempfile_in(&scratch.dir)
+            }
+            Some(scratch) => builder
+                .disable_cleanup(scratch.keep)
+                .tempfile_in(&scratch.dir),
+            None => builder.tempfile(),
+        })
+        .map_err(|e| ApplicationError::Other(format!("Could not create temporary file: {e}")))
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+/// The kinds of font source file, as told by their extensions
+///
+/// Anything babelfont can load may be a source. The kinds differ in which
+/// external tools can read them; a source in a format a tool can't read is
+/// converted to a Glyphs file for it (see [crate::buildsystem::OperationOutput::to_source_filename]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Glyphs,
+    Designspace,
+    Ufo,
+    Fontra,
+    /// Any other format babelfont can load, such as FontLab's `.vfj`
+    Other,
+}
+
+impl SourceKind {
+    pub fn from_path(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("glyphs" | "glyphspackage") => SourceKind::Glyphs,
+            Some("designspace") => SourceKind::Designspace,
+            Some("ufo") => SourceKind::Ufo,
+            Some("fontra") => SourceKind::Fontra,
+            _ => SourceKind::Other,
+        }
+    }
+
+    /// Whether fontc can compile this kind of source itself
+    pub fn fontc_reads(self) -> bool {
+        self != SourceKind::Other
+    }
+
+    /// The fontmake flag which introduces this kind of source, if fontmake
+    /// can read it
+    pub fn fontmake_flag(self) -> Option<&'static str> {
+        match self {
+            SourceKind::Glyphs => Some("-g"),
+            SourceKind::Designspace => Some("-m"),
+            SourceKind::Ufo => Some("-u"),
+            SourceKind::Fontra | SourceKind::Other => None,
+        }
+    }
+
+    pub fn fontmake_reads(self) -> bool {
+        self.fontmake_flag().is_some()
+    }
+}
@@ -2,12 +2,17 @@ use std::{error::Error, sync::PoisonError};
 use thiserror::Error;
 use tokio::{io, task::JoinError};
 
+use crate::buildsystem::FailureReport;
+
 // We can't use thiserror `[from]` on these because they need to be Clone/Eq/PartialEq
 // to be used in the build system, so we have a bunch of sad manual impls below.
 #[derive(Clone, Debug, Eq, PartialEq, Error)]
 pub enum ApplicationError {
-    #[error("build failed")]
-    Build,
+    #[error("could not schedule {operation} while building [{}]", .targets.join(", "))]
+    Build {
+        operation: String,
+        targets: Vec<String>,
+    },
     #[error("default output not found")]
     DefaultOutputNotFound,
     #[error("Inputs provided to operation are not correct: {0}")]
@@ -26,6 +31,73 @@ pub enum ApplicationError {
     CompressionError(String),
     #[error("Error including a subset font: {0}")]
     IncludeSubsetsError(String),
+    #[error("Masters are not interpolation-compatible in {0}")]
+    IncompatibleMasters(String),
+    #[error("Font failed QA checks: {0}")]
+    ChecksFailed(String),
+    #[error("Font is not valid: {0}")]
+    InvalidFont(String),
+    #[error("{0} is not installed or not on the PATH")]
+    ToolNotFound(String),
+    #[error("Cannot start the build: {0}")]
+    Preflight(String),
+    #[error("{0}")]
+    BuildFailed(FailureReport),
+    #[error(
+        "Build is not reproducible; these targets differed (both builds are kept in {sandbox}): {}",
+        .targets.join(", ")
+    )]
+    NotReproducible {
+        targets: Vec<String>,
+        sandbox: String,
+    },
+    #[error(
+        "operation '{operation}' while building [{}] from [{}]: {source}",
+        .targets.join(", "),
+        .inputs.join(", ")
+    )]
+    OperationFailed {
+        operation: String,
+        targets: Vec<String>,
+        inputs: Vec<String>,
+        source: Box<ApplicationError>,
+    },
+}
+
+impl ApplicationError {
+    /// The process exit code for this error, so that scripts can tell what
+    /// kind of failure it was:
+    ///
+    /// - 1: an operation failed
+    /// - 2: the config file or recipe is not valid
+    /// - 3: an external tool is not installed
+    /// - 4: a font failed QA checks
+    /// - 5: two builds of the same sources weren't byte-identical
+    ///
+    /// A build with several failures only gets a specific code if they all
+    /// have the same one.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::InvalidRecipe(_) => 2,
+            Self::ToolNotFound(_) => 3,
+            Self::ChecksFailed(_) | Self::InvalidFont(_) => 4,
+            Self::NotReproducible { .. } => 5,
+            Self::OperationFailed { source, .. } => source.exit_code(),
+            Self::BuildFailed(report) => {
+                let mut codes = report
+                    .failures
+                    .iter()
+                    .map(|failure| failure.cause.exit_code());
+                let first = codes.next().unwrap_or(1);
+                if codes.all(|code| code == first) {
+                    first
+                } else {
+                    1
+                }
+            }
+            _ => 1,
+        }
+    }
 }
 
 impl From<Box<dyn Error>> for ApplicationError {
@@ -58,6 +130,12 @@ impl From<read_fonts::ReadError> for ApplicationError {
     }
 }
 
+impl From<write_fonts::BuilderError> for ApplicationError {
+    fn from(error: write_fonts::BuilderError) -> Self {
+        Self::Other(format!("Could not build font: {}", error))
+    }
+}
+
 impl From<ttf2woff2::Error> for ApplicationError {
     fn from(error: ttf2woff2::Error) -> Self {
         Self::CompressionError(error.to_string())
@@ -1,22 +1,30 @@
 // Public modules for library usage
 pub mod buildsystem;
 pub mod error;
+pub mod loader;
 pub mod operations;
 pub mod recipe;
 pub mod recipe_providers;
 
 use error::ApplicationError;
+use loader::ConfigFormat;
+use read_fonts::FontRef;
 use recipe::Config;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 use crate::recipe::Recipe;
 
 /// Configuration for building fonts
+#[derive(Clone)]
 pub struct BuildConfig {
     /// Path to the config file
     pub config_path: String,
+    /// Format of the config file (detected from the extension if not given)
+    pub config_format: Option<ConfigFormat>,
     /// Maximum number of parallel jobs
     pub job_limit: usize,
+    /// Only build these targets; if empty, build everything
+    pub targets: Vec<String>,
     /// Whether to only generate the recipe (don't build)
     pub generate_only: bool,
     /// Generate graphviz graph
@@ -24,47 +32,81 @@ pub struct BuildConfig {
     pub draw_graph: bool,
     /// Generate ASCII graph
     pub ascii_graph: bool,
+    /// Write the build graph as JSON to this file instead of building
+    pub emit_graph: Option<String>,
     /// Materialize intermediate outputs as named files for debugging
     pub debug_intermediates: bool,
     // Verbosity level for logging
     pub verbosity: log::Level,
     /// Whether to show progress bars
     pub progress: bool,
+    /// How to report what the build is doing
+    pub reporter: buildsystem::ReporterKind,
+    /// Carry on building other targets when one fails
+    pub keep_going: bool,
+    /// Write a JSON report of any failures to this file
+    pub failure_report: Option<String>,
+    /// Keep the temporary files of operations which fail
+    pub keep_temps: bool,
+    /// Make the same sources build to byte-identical fonts
+    pub reproducible: bool,
+    /// Shell out to `gftools fix-font` rather than using the built-in fixer
+    pub use_external_fix: bool,
 }
 
 impl Default for BuildConfig {
     fn default() -> Self {
         BuildConfig {
             config_path: String::new(),
+            config_format: None,
             job_limit: num_cpus::get(),
+            targets: vec![],
             generate_only: false,
             #[cfg(feature = "graphviz")]
             draw_graph: false,
             ascii_graph: false,
+            emit_graph: None,
             debug_intermediates: false,
             verbosity: log::Level::Info,
             progress: true,
+            reporter: buildsystem::ReporterKind::default(),
+            keep_going: false,
+            failure_report: None,
+            keep_temps: false,
+            reproducible: false,
+            use_external_fix: false,
         }
     }
 }
 
-/// Load and parse a config file
-pub fn load_config(config_path: &str) -> Result<Config, ApplicationError> {
-    let config_yaml = std::fs::read_to_string(config_path).map_err(|e| {
-        ApplicationError::InvalidRecipe(format!(
-            "Could not read config file {}: {}",
-            config_path, e
-        ))
-    })?;
+/// Load and parse a config file, resolving any `include:` directives
+pub fn load_config(
+    config_path: &str,
+    format: Option<ConfigFormat>,
+) -> Result<Config, ApplicationError> {
+    let config_value = loader::read_config_value(config_path, format)?;
 
-    serde_yaml_ng::from_str::<Config>(&config_yaml).map_err(|e| {
+    serde_path_to_error::deserialize::<_, Config>(config_value).map_err(|e| {
+        let location = match loader::find_key_line(config_path, format, e.path()) {
+            Some(line) => format!(" at `{}` (line {})", e.path(), line),
+            None if e.path().iter().next().is_some() => format!(" at `{}`", e.path()),
+            None => String::new(),
+        };
         ApplicationError::InvalidRecipe(format!(
-            "Could not parse config file {}: {}",
-            config_path, e
+            "Could not parse config file {}{}: {}",
+            config_path,
+            location,
+            e.inner()
         ))
     })
 }
 
+/// Generate the JSON schema describing config files
+pub fn generate_schema() -> Result<String, ApplicationError> {
+    serde_json::to_string_pretty(&schemars::schema_for!(Config))
+        .map_err(|e| ApplicationError::Other(format!("Could not serialize config schema: {}", e)))
+}
+
 /// Change to the config file's directory
 pub fn change_to_config_dir(config_path: &str) -> Result<(), ApplicationError> {
     if let Some(config_dir) = Path::new(config_path).parent() {
@@ -90,6 +132,98 @@ pub fn generate_recipe(config: &Config) -> Result<String, ApplicationError> {
     })
 }
 
+/// Describe how a target in a config will be built, without building it
+pub fn explain_target(
+    config_path: &str,
+    format: Option<ConfigFormat>,
+    target: &str,
+) -> Result<String, ApplicationError> {
+    let graph = load_recipe(config_path, format)?.to_graph(false)?;
+    graph.explain(target)
+}
+
+/// Compare the recipes generated by two configs
+pub fn diff_configs(
+    old_config_path: &str,
+    new_config_path: &str,
+    format: Option<ConfigFormat>,
+) -> Result<String, ApplicationError> {
+    let old_recipe = load_recipe(old_config_path, format)?;
+    let new_recipe = load_recipe(new_config_path, format)?;
+    let diff = old_recipe.diff(&new_recipe);
+    if diff.is_empty() {
+        Ok("The recipes are identical".to_string())
+    } else {
+        Ok(diff)
+    }
+}
+
+/// List the targets in a config which would be rebuilt, and why
+///
+/// This compares the sources and steps of each target against what the
+/// build log says it was last built from, without building anything.
+pub fn build_status(
+    config_path: &str,
+    format: Option<ConfigFormat>,
+) -> Result<String, ApplicationError> {
+    let graph = load_recipe(config_path, format)?.to_graph(false)?;
+
+    let _change_back = ChangeDirGuard::new()?;
+    change_to_config_dir(config_path)?;
+    let dirty = buildsystem::BuildLog::load()?.dirty_targets(&graph);
+    let total = graph.target_nodes.len();
+    if dirty.is_empty() {
+        return Ok(format!("All {total} targets are up to date"));
+    }
+    let mut status = String::new();
+    for (target, reasons) in &dirty {
+        let reasons: Vec<String> = reasons.iter().map(ToString::to_string).collect();
+        status.push_str(&format!("{target}: {}\n", reasons.join("; ")));
+    }
+    status.push_str(&format!(
+        "{} of {total} targets would be rebuilt",
+        dirty.len()
+    ));
+    Ok(status)
+}
+
+/// Run a build from a graph written out with `--emit`
+///
+/// The graph's paths are relative to the directory its config file was in,
+/// so we run from `directory` if it's given.
+pub async fn run_graph(
+    graph_path: &str,
+    directory: Option<&str>,
+    options: buildsystem::RunOptions,
+) -> Result<(), ApplicationError> {
+    let text = std::fs::read_to_string(graph_path).map_err(|e| {
+        ApplicationError::InvalidRecipe(format!("Could not read graph {graph_path}: {e}"))
+    })?;
+    let graph = buildsystem::BuildGraph::from_json(&text)?;
+
+    let _change_back = ChangeDirGuard::new()?;
+    if let Some(directory) = directory {
+        std::env::set_current_dir(directory)?;
+    }
+    graph.ensure_directories()?;
+    graph.preflight()?;
+    buildsystem::run(graph, options).await
+}
+
+/// Load a config file and generate its recipe
+fn load_recipe(
+    config_path: &str,
+    format: Option<ConfigFormat>,
+) -> Result<Recipe, ApplicationError> {
+    let config = load_config(config_path, format)?;
+
+    // Recipe generation reads sources relative to the config file
+    let _change_back = ChangeDirGuard::new()?;
+    change_to_config_dir(config_path)?;
+
+    config.recipe()
+}
+
 /// Generate an ASCII graph of the build process
 pub fn generate_ascii_graph(
     recipe: &Recipe,
@@ -109,7 +243,19 @@ pub fn generate_svg_graph(recipe: &Recipe) -> Result<String, ApplicationError> {
 
 /// Main build function that can be called from tests or the binary
 pub async fn build(config: BuildConfig) -> Result<(), ApplicationError> {
-    let config_yaml = load_config(&config.config_path)?;
+    let config_yaml = load_config(&config.config_path, config.config_format)?;
+
+    // Resolve these before we move to the config file's directory
+    let emit_graph = config
+        .emit_graph
+        .as_ref()
+        .map(std::path::absolute)
+        .transpose()?;
+    let failure_report = config
+        .failure_report
+        .as_ref()
+        .map(std::path::absolute)
+        .transpose()?;
 
     // Hold a guard to the current directory
     let _change_back = ChangeDirGuard::new()?;
@@ -121,7 +267,10 @@ pub async fn build(config: BuildConfig) -> Result<(), ApplicationError> {
     // This tells tokio to park the current task and use another thread from the pool.
     // This avoids the "Cannot drop a runtime" panic that occurs when reqwest::blocking
     // creates/drops a runtime inside an async context.
-    let recipe = tokio::task::block_in_place(|| config_yaml.recipe())?;
+    let mut recipe = tokio::task::block_in_place(|| config_yaml.recipe())?;
+    if config.use_external_fix {
+        recipe.use_external_fix();
+    }
 
     if config.generate_only {
         #[derive(serde::Serialize)]
@@ -152,6 +301,15 @@ pub async fn build(config: BuildConfig) -> Result<(), ApplicationError> {
         return Ok(());
     }
 
+    if let Some(path) = emit_graph {
+        let graph = recipe.to_graph(config.debug_intermediates)?;
+        std::fs::write(&path, graph.to_json()?).map_err(|e| {
+            ApplicationError::InvalidRecipe(format!("Could not write graph to file: {}", e))
+        })?;
+        println!("Wrote build graph to {}", path.display());
+        return Ok(());
+    }
+
     // We'll always generate an ASCII graph even if the user doesn't ask
     // for one, because generate_ascii_graph has a nice cycle detector.
     let graph = generate_ascii_graph(&recipe, config.verbosity, config.debug_intermediates)?;
@@ -163,13 +321,205 @@ pub async fn build(config: BuildConfig) -> Result<(), ApplicationError> {
     // Use the config to create a build graph
     let graph = recipe.to_graph(config.debug_intermediates)?;
     graph.ensure_directories()?;
+    graph.preflight()?;
 
     // Run the build
-    buildsystem::run(graph, config.job_limit, config.progress).await?;
+    let options = buildsystem::RunOptions {
+        job_limit: config.job_limit,
+        progress: config.progress,
+        reporter: config.reporter,
+        keep_going: config.keep_going,
+        keep_temps: config.keep_temps,
+        reproducible: config.reproducible,
+    };
+    let result = if config.targets.is_empty() {
+        buildsystem::run(graph, options).await
+    } else {
+        buildsystem::run_targets(graph, &config.targets, options).await
+    };
+    if let (Err(ApplicationError::BuildFailed(report)), Some(path)) = (&result, failure_report) {
+        std::fs::write(&path, report.to_json()?).map_err(|e| {
+            ApplicationError::Other(format!(
+                "Could not write failure report to {}: {e}",
+                path.display()
+            ))
+        })?;
+    }
+    result
+}
+
+/// Build a config twice and check that every target comes out byte-identical
+///
+/// Both are reproducible builds (see [BuildConfig::reproducible]), each in
+/// a fresh copy of the project in a sandbox of its own, so the user's tree
+/// is never touched. The project is the smallest directory holding the
+/// config file, the sources and the targets. The sandboxes are kept if
+/// anything differs, so the two builds can be compared by hand.
+pub async fn verify_reproducible(config: BuildConfig) -> Result<String, ApplicationError> {
+    if config.config_path == loader::STDIN_PATH {
+        return Err(ApplicationError::InvalidRecipe(
+            "Checking a build is reproducible needs a config file, not stdin".to_string(),
+        ));
+    }
+    let graph = load_recipe(&config.config_path, config.config_format)?
+        .to_graph(config.debug_intermediates)?;
+    let mut targets = config.targets.clone();
+    if targets.is_empty() {
+        targets = graph.target_nodes.keys().cloned().collect();
+    }
+    targets.sort();
+
+    // Targets and sources are relative to the config file's directory
+    let config_path = normalize_path(&std::path::absolute(&config.config_path)?);
+    let base = config_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let mut root = base.clone();
+    let sources: Vec<String> = graph
+        .target_dependencies()
+        .into_values()
+        .flat_map(|(_, sources)| sources)
+        .collect();
+    for path in targets.iter().chain(&sources) {
+        if Path::new(path).is_absolute() {
+            if targets.contains(path) {
+                return Err(ApplicationError::InvalidRecipe(format!(
+                    "Target {path} has an absolute path, so it can't be built in a sandbox"
+                )));
+            }
+            continue;
+        }
+        let path = normalize_path(&base.join(path));
+        while !path.starts_with(&root) {
+            root = root.parent().map(Path::to_path_buf).ok_or_else(|| {
+                ApplicationError::InvalidRecipe(
+                    "The sources and targets are too spread out to build in a sandbox".to_string(),
+                )
+            })?;
+        }
+    }
+    if root.parent().is_none() {
+        return Err(ApplicationError::InvalidRecipe(
+            "The sources and targets are too spread out to build in a sandbox".to_string(),
+        ));
+    }
+    let relative = |path: &Path| path.strip_prefix(&root).map(Path::to_path_buf);
+    let sandboxed_config = relative(&config_path).unwrap_or_default();
+
+    let sandbox = tempfile::Builder::new()
+        .prefix("gftools-builder-repro-")
+        .tempdir()?;
+    let runs = ["first", "second"];
+    for run in runs {
+        log::info!("Reproducibility check: {run} build");
+        let project = sandbox.path().join(run);
+        copy_project(&root, &project, sandbox.path())?;
+        build(BuildConfig {
+            config_path: project
+                .join(&sandboxed_config)
+                .to_string_lossy()
+                .to_string(),
+            reproducible: true,
+            ..config.clone()
+        })
+        .await?;
+    }
 
+    let mut differing = vec![];
+    for target in &targets {
+        let Ok(path) = relative(&normalize_path(&base.join(target))) else {
+            continue;
+        };
+        let [first, second] =
+            runs.map(|run| std::fs::read(sandbox.path().join(run).join(&path)).ok());
+        if first == second {
+            continue;
+        }
+        let tables = match (&first, &second) {
+            (Some(first), Some(second)) => differing_tables(first, second),
+            _ => vec![],
+        };
+        if tables.is_empty() {
+            differing.push(target.clone());
+        } else {
+            differing.push(format!("{target} ({})", tables.join(", ")));
+        }
+    }
+    if differing.is_empty() {
+        return Ok(format!(
+            "All {} targets were byte-identical in both builds",
+            targets.len()
+        ));
+    }
+    Err(ApplicationError::NotReproducible {
+        targets: differing,
+        sandbox: sandbox.keep().display().to_string(),
+    })
+}
+
+/// Resolve `.` and `..` in a path without touching the filesystem
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Copy a project into a sandbox, leaving out version control and the
+/// sandboxes themselves
+fn copy_project(from: &Path, to: &Path, sandbox: &Path) -> Result<(), ApplicationError> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)?.flatten() {
+        let path = entry.path();
+        if path == sandbox {
+            continue;
+        }
+        let destination = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if entry.file_name() != ".git" {
+                copy_project(&path, &destination, sandbox)?;
+            }
+        } else if file_type.is_symlink() {
+            std::os::unix::fs::symlink(std::fs::read_link(&path)?, &destination)?;
+        } else {
+            std::fs::copy(&path, &destination)?;
+        }
+    }
     Ok(())
 }
 
+/// The tables which differ between two builds of a font, if they're fonts
+fn differing_tables(first: &[u8], second: &[u8]) -> Vec<String> {
+    let (Ok(first), Ok(second)) = (FontRef::new(first), FontRef::new(second)) else {
+        return vec![];
+    };
+    let mut tags: Vec<_> = first
+        .table_directory
+        .table_records()
+        .iter()
+        .chain(second.table_directory.table_records())
+        .map(|record| record.tag())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags.into_iter()
+        .filter(|tag| {
+            first.table_data(*tag).map(|data| data.as_bytes())
+                != second.table_data(*tag).map(|data| data.as_bytes())
+        })
+        .map(|tag| tag.to_string())
+        .collect()
+}
+
 struct ChangeDirGuard {
     original_dir: PathBuf,
 }
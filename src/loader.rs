@@ -0,0 +1,563 @@
+//! Reading config files from disk
+//!
+//! Config files are first read into a generic YAML value rather than straight
+//! into a [crate::recipe::Config]. This lets us compose several files together
+//! (via `include:`) before serde ever sees the provider options. Because the
+//! YAML value type is just another serde data model, config files may also be
+//! written in TOML or JSON.
+//!
+//! Once the files are composed, `${ENV_VAR}` references in any key or string
+//! value are replaced with the contents of the environment variable, so that
+//! CI can inject things like versioned output directories. `${ENV_VAR:-default}`
+//! supplies a fallback for when the variable is unset.
+//!
+//! Finally, a top-level `vars:` mapping defines user variables, and every
+//! `$name` reference to one of them is substituted throughout the rest of the
+//! config. References to names which are not defined in `vars:` are left alone,
+//! so provider-level placeholders like `$outputDir` keep working.
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use serde_yaml_ng::{Mapping, Value};
+
+use crate::error::ApplicationError;
+
+/// The file formats a config file can be written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Guess the format of a config file from its extension, defaulting to YAML.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => ConfigFormat::Toml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    fn parse(&self, text: &str) -> Result<Value, String> {
+        match self {
+            ConfigFormat::Yaml => serde_yaml_ng::from_str(text).map_err(|e| e.to_string()),
+            ConfigFormat::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+            ConfigFormat::Json => serde_json::from_str(text).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// The config path which means "read the config from standard input"
+pub const STDIN_PATH: &str = "-";
+
+/// Read a config file, resolving any `include:` directives it contains.
+///
+/// A `config_path` of `-` reads the config from standard input; in that case
+/// `format` defaults to JSON and includes are resolved relative to the current
+/// directory. Otherwise, if `format` is not given, it is detected from the
+/// file extension. Included files always have their format detected from their
+/// own extension, so a TOML config can include a YAML one and vice versa.
+///
+/// Included files are merged in the order they are listed, and the including
+/// file is merged last, so its own keys always win. Mappings are merged
+/// recursively; any other value (including lists) is replaced wholesale.
+pub fn read_config_value(
+    config_path: &str,
+    format: Option<ConfigFormat>,
+) -> Result<Value, ApplicationError> {
+    let mut value = read_composed_value(config_path, format)?;
+    map_strings(&mut value, &mut |s| {
+        expand_env(s, |name| std::env::var(name).ok())
+    })?;
+    let vars = take_vars(&mut value)?;
+    if !vars.is_empty() {
+        map_strings(&mut value, &mut |s| Ok(expand_vars(s, &vars)))?;
+    }
+    Ok(value)
+}
+
+fn read_composed_value(
+    config_path: &str,
+    format: Option<ConfigFormat>,
+) -> Result<Value, ApplicationError> {
+    if config_path == STDIN_PATH {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map_err(|e| {
+            ApplicationError::InvalidRecipe(format!("Could not read config from stdin: {}", e))
+        })?;
+        let mut value = format
+            .unwrap_or(ConfigFormat::Json)
+            .parse(&text)
+            .map_err(|e| {
+                ApplicationError::InvalidRecipe(format!("Could not parse config from stdin: {}", e))
+            })?;
+        let includes = take_includes(&mut value, Path::new("<stdin>"))?;
+        return resolve_includes(value, includes, Path::new(""), &mut vec![]);
+    }
+    read_with_includes(Path::new(config_path), format, &mut vec![])
+}
+
+fn read_with_includes(
+    path: &Path,
+    format: Option<ConfigFormat>,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Value, ApplicationError> {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if let Some(start) = stack.iter().position(|file| *file == canonical) {
+        if start + 1 == stack.len() {
+            return Err(ApplicationError::InvalidRecipe(format!(
+                "Config file {} includes itself",
+                path.display()
+            )));
+        }
+        let chain = stack[start..]
+            .iter()
+            .chain(std::iter::once(&canonical))
+            .map(|file| file.display().to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        return Err(ApplicationError::InvalidRecipe(format!(
+            "Config files include each other: {chain}"
+        )));
+    }
+
+    let text = std::fs::read_to_string(path).map_err(|e| {
+        ApplicationError::InvalidRecipe(format!(
+            "Could not read config file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    let format = format.unwrap_or_else(|| ConfigFormat::from_path(path));
+    let mut value = format.parse(&text).map_err(|e| {
+        ApplicationError::InvalidRecipe(format!(
+            "Could not parse config file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    let includes = take_includes(&mut value, path)?;
+    if includes.is_empty() {
+        return Ok(value);
+    }
+
+    // Includes are relative to the file that names them, not to the CWD
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    stack.push(canonical);
+    let merged = resolve_includes(value, includes, base, stack)?;
+    stack.pop();
+    Ok(merged)
+}
+
+/// Merge the given included files, and then `value` on top of them.
+fn resolve_includes(
+    value: Value,
+    includes: Vec<String>,
+    base: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Value, ApplicationError> {
+    let mut merged = Value::Mapping(Mapping::new());
+    for include in includes {
+        let included = read_with_includes(&base.join(include), None, stack)?;
+        merge_values(&mut merged, included);
+    }
+    merge_values(&mut merged, value);
+    Ok(merged)
+}
+
+/// Remove the `include` key from a config and return the files it names.
+fn take_includes(value: &mut Value, path: &Path) -> Result<Vec<String>, ApplicationError> {
+    let Some(mapping) = value.as_mapping_mut() else {
+        return Ok(vec![]);
+    };
+    match mapping.remove("include") {
+        None => Ok(vec![]),
+        Some(Value::String(file)) => Ok(vec![file]),
+        Some(Value::Sequence(files)) => files
+            .into_iter()
+            .map(|file| match file {
+                Value::String(file) => Ok(file),
+                other => Err(ApplicationError::InvalidRecipe(format!(
+                    "Included files in {} must be strings, found {:?}",
+                    path.display(),
+                    other
+                ))),
+            })
+            .collect(),
+        Some(other) => Err(ApplicationError::InvalidRecipe(format!(
+            "`include` in {} must be a filename or a list of filenames, found {:?}",
+            path.display(),
+            other
+        ))),
+    }
+}
+
+/// Remove the `vars` key from a config and return the variables it defines.
+fn take_vars(value: &mut Value) -> Result<HashMap<String, String>, ApplicationError> {
+    let Some(vars) = value.as_mapping_mut().and_then(|m| m.remove("vars")) else {
+        return Ok(HashMap::new());
+    };
+    let Value::Mapping(vars) = vars else {
+        return Err(ApplicationError::InvalidRecipe(
+            "`vars` must be a mapping of variable names to values".to_string(),
+        ));
+    };
+    vars.into_iter()
+        .map(|(name, value)| {
+            let name = name.as_str().map(|s| s.to_string()).ok_or_else(|| {
+                ApplicationError::InvalidRecipe(format!(
+                    "Variable names must be strings, found {:?}",
+                    name
+                ))
+            })?;
+            let value = match value {
+                Value::String(s) => s,
+                Value::Bool(b) => b.to_string(),
+                Value::Number(n) => n.to_string(),
+                other => {
+                    return Err(ApplicationError::InvalidRecipe(format!(
+                        "Variable {} must be a string, number or boolean, found {:?}",
+                        name, other
+                    )));
+                }
+            };
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// Substitute `$name` references to the given variables in a string.
+fn expand_vars(s: &str, vars: &HashMap<String, String>) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let reference = &rest[start + 1..];
+        let end = reference
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(reference.len());
+        match vars.get(&reference[..end]) {
+            Some(value) => result.push_str(value),
+            None => {
+                result.push('$');
+                result.push_str(&reference[..end]);
+            }
+        }
+        rest = &reference[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Replace every string in a config value, including mapping keys.
+fn map_strings(
+    value: &mut Value,
+    f: &mut impl FnMut(&str) -> Result<String, ApplicationError>,
+) -> Result<(), ApplicationError> {
+    match value {
+        Value::String(s) => *s = f(s)?,
+        Value::Sequence(items) => {
+            for item in items {
+                map_strings(item, f)?;
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (mut key, mut item) in std::mem::take(mapping) {
+                map_strings(&mut key, f)?;
+                map_strings(&mut item, f)?;
+                mapping.insert(key, item);
+            }
+        }
+        Value::Tagged(tagged) => map_strings(&mut tagged.value, f)?,
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+    Ok(())
+}
+
+/// Expand `${NAME}` and `${NAME:-default}` references in a string.
+fn expand_env(
+    s: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<String, ApplicationError> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let reference = &rest[start + 2..];
+        let end = reference.find('}').ok_or_else(|| {
+            ApplicationError::InvalidRecipe(format!(
+                "Unterminated environment variable reference in {:?}",
+                s
+            ))
+        })?;
+        let (name, default) = match reference[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&reference[..end], None),
+        };
+        let value = lookup(name)
+            .or_else(|| default.map(|d| d.to_string()))
+            .ok_or_else(|| {
+                ApplicationError::InvalidRecipe(format!(
+                    "Environment variable {} is used in the config but is not set",
+                    name
+                ))
+            })?;
+        result.push_str(&value);
+        rest = &reference[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Merge `overlay` on top of `base`.
+fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Find the line of a YAML config file on which the value at `path` is defined.
+///
+/// This is a best-effort search of the file's text, used to point users at the
+/// source of a parse error: once a config has been composed from several files
+/// we no longer know where each value came from, so this only looks at the
+/// top-level file and returns `None` if the key path cannot be found there.
+pub fn find_key_line(
+    config_path: &str,
+    format: Option<ConfigFormat>,
+    path: &serde_path_to_error::Path,
+) -> Option<usize> {
+    let path_buf = Path::new(config_path);
+    if config_path == STDIN_PATH
+        || format.unwrap_or_else(|| ConfigFormat::from_path(path_buf)) != ConfigFormat::Yaml
+    {
+        return None;
+    }
+    let text = std::fs::read_to_string(path_buf).ok()?;
+    find_key_line_in(&text, path)
+}
+
+fn find_key_line_in(text: &str, path: &serde_path_to_error::Path) -> Option<usize> {
+    let lines: Vec<&str> = text.lines().collect();
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    // Lines are zero-based here; `current` is the line holding the last segment found
+    let mut current: Option<usize> = None;
+    for segment in path.iter() {
+        let start = current.map(|line| line + 1).unwrap_or(0);
+        match segment {
+            serde_path_to_error::Segment::Map { key } => {
+                let found = lines[start..].iter().position(|line| {
+                    let line = line.trim_start().trim_start_matches("- ").trim_start();
+                    [key.to_string(), format!("\"{key}\""), format!("'{key}'")]
+                        .iter()
+                        .any(|k| {
+                            line.strip_prefix(k.as_str())
+                                .is_some_and(|rest| rest.trim_start().starts_with(':'))
+                        })
+                })?;
+                current = Some(start + found);
+            }
+            serde_path_to_error::Segment::Seq { index } => {
+                // The list items are the lines starting with `-` at the
+                // indentation of the first one
+                let first = start
+                    + lines[start..]
+                        .iter()
+                        .position(|line| line.trim_start().starts_with('-'))?;
+                let item_indent = indent(lines[first]);
+                let item = lines[first..]
+                    .iter()
+                    .enumerate()
+                    .take_while(|(_, line)| line.trim().is_empty() || indent(line) >= item_indent)
+                    .filter(|(_, line)| {
+                        indent(line) == item_indent && line.trim_start().starts_with('-')
+                    })
+                    .nth(*index)?
+                    .0;
+                current = Some(first + item);
+            }
+            _ => {}
+        }
+    }
+    current.map(|line| line + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(dir: &TempDir, name: &str, contents: &str) -> String {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_include_merges_with_local_keys_winning() {
+        let dir = TempDir::new().unwrap();
+        write(
+            &dir,
+            "common.yaml",
+            "buildStatic: false\noutputDir: ../common\nextra:\n  a: 1\n  b: 2\n",
+        );
+        let config = write(
+            &dir,
+            "config.yaml",
+            "include: common.yaml\nsources:\n  - Foo.glyphs\noutputDir: ../fonts\nextra:\n  b: 3\n",
+        );
+        let value = read_config_value(&config, None).unwrap();
+        assert_eq!(value["buildStatic"], Value::Bool(false));
+        assert_eq!(value["outputDir"], Value::String("../fonts".to_string()));
+        assert_eq!(value["extra"]["a"], Value::from(1));
+        assert_eq!(value["extra"]["b"], Value::from(3));
+        assert!(value.get("include").is_none());
+    }
+
+    #[test]
+    fn test_nested_includes_are_relative_to_includer() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("shared")).unwrap();
+        write(&dir, "shared/base.yaml", "buildWebfont: false\n");
+        write(
+            &dir,
+            "shared/family.yaml",
+            "include: base.yaml\nbuildStatic: false\n",
+        );
+        let config = write(&dir, "config.yaml", "include: [shared/family.yaml]\n");
+        let value = read_config_value(&config, None).unwrap();
+        assert_eq!(value["buildWebfont"], Value::Bool(false));
+        assert_eq!(value["buildStatic"], Value::Bool(false));
+    }
+
+    #[test]
+    fn test_toml_config_with_yaml_include() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, "common.yaml", "buildStatic: false\n");
+        let config = write(
+            &dir,
+            "config.toml",
+            "include = \"common.yaml\"\nsources = [\"Foo.glyphs\"]\n",
+        );
+        let value = read_config_value(&config, None).unwrap();
+        assert_eq!(value["buildStatic"], Value::Bool(false));
+        assert_eq!(
+            value["sources"],
+            Value::Sequence(vec![Value::String("Foo.glyphs".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_json_config() {
+        let dir = TempDir::new().unwrap();
+        let config = write(
+            &dir,
+            "config.json",
+            r#"{"sources": ["Foo.glyphs"], "buildStatic": false}"#,
+        );
+        let value = read_config_value(&config, None).unwrap();
+        assert_eq!(value["buildStatic"], Value::Bool(false));
+    }
+
+    #[test]
+    fn test_explicit_format_overrides_extension() {
+        let dir = TempDir::new().unwrap();
+        let config = write(&dir, "config.cfg", "buildStatic = false\n");
+        assert!(read_config_value(&config, None).is_err());
+        let value = read_config_value(&config, Some(ConfigFormat::Toml)).unwrap();
+        assert_eq!(value["buildStatic"], Value::Bool(false));
+    }
+
+    #[test]
+    fn test_expand_env() {
+        let lookup = |name: &str| (name == "VERSION").then(|| "1.002".to_string());
+        assert_eq!(
+            expand_env("../fonts/${VERSION}/ttf", lookup).unwrap(),
+            "../fonts/1.002/ttf"
+        );
+        assert_eq!(
+            expand_env("${MISSING:-../fonts}/${VERSION}", lookup).unwrap(),
+            "../fonts/1.002"
+        );
+        assert_eq!(
+            expand_env("$outputDir/ttf", lookup).unwrap(),
+            "$outputDir/ttf"
+        );
+        assert!(expand_env("${MISSING}", lookup).is_err());
+        assert!(expand_env("${VERSION", lookup).is_err());
+    }
+
+    #[test]
+    fn test_vars_are_substituted() {
+        let dir = TempDir::new().unwrap();
+        let config = write(
+            &dir,
+            "config.yaml",
+            r#"
+vars:
+  family: Nunito
+  fonts: ../fonts
+recipe:
+  $fonts/$family.ttf:
+    - source: $family.glyphs
+    - operation: fontc
+    - operation: fix
+      args: --output-dir $outputDir
+"#,
+        );
+        let value = read_config_value(&config, None).unwrap();
+        assert!(value.get("vars").is_none());
+        let target = &value["recipe"]["../fonts/Nunito.ttf"];
+        assert_eq!(target[0]["source"], Value::from("Nunito.glyphs"));
+        assert_eq!(target[2]["args"], Value::from("--output-dir $outputDir"));
+    }
+
+    #[test]
+    fn test_include_cycle_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, "a.yaml", "include: b.yaml\n");
+        let config = write(&dir, "b.yaml", "include: a.yaml\n");
+        let error = read_config_value(&config, None).unwrap_err().to_string();
+        let b = dir.path().join("b.yaml").canonicalize().unwrap();
+        let a = dir.path().join("a.yaml").canonicalize().unwrap();
+        assert!(
+            error.contains(&format!(
+                "{} -> {} -> {}",
+                b.display(),
+                a.display(),
+                b.display()
+            )),
+            "{error}"
+        );
+
+        let config = write(&dir, "c.yaml", "include: c.yaml\n");
+        let error = read_config_value(&config, None).unwrap_err().to_string();
+        assert!(error.contains("includes itself"), "{error}");
+    }
+
+    #[test]
+    fn test_find_key_line() {
+        let text = "sources:\n  - Foo.glyphs\nrecipe:\n  Foo.ttf:\n    - source: Foo.glyphs\n    - operation: fontcc\n";
+        let value: Value = serde_yaml_ng::from_str(text).unwrap();
+        let error = serde_path_to_error::deserialize::<_, crate::recipe::Config>(value)
+            .err()
+            .unwrap();
+        assert_eq!(find_key_line_in(text, error.path()), Some(6));
+    }
+}
@@ -1,4 +1,10 @@
-use gftools_builder::{BuildConfig, build};
+use gftools_builder::{
+    BuildConfig, build, build_status,
+    buildsystem::{ReporterKind, RunOptions},
+    diff_configs, explain_target, generate_schema,
+    loader::ConfigFormat,
+    run_graph, verify_reproducible,
+};
 use tracing_chrome::ChromeLayerBuilder;
 
 use clap::Parser;
@@ -10,7 +16,16 @@ use tokio::{
 use tracing_subscriber::{EnvFilter, prelude::*};
 
 #[derive(clap::Parser)]
+#[command(
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    after_help = "Exit status is 1 if an operation fails, 2 if the config file is invalid, \
+                  3 if an external tool is missing, 4 if a font fails QA checks and 5 if \
+                  verify-repro finds that two builds differ."
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Increase logging
     #[command(flatten)]
     verbosity: clap_verbosity_flag::Verbosity,
@@ -20,6 +35,12 @@ struct Args {
     /// Enable profiling and write trace data to the specified file
     #[clap(long)]
     pub profile: Option<String>,
+    /// Export trace spans over OTLP/HTTP to this URL (for example
+    /// http://localhost:4318/v1/traces); setting OTEL_EXPORTER_OTLP_ENDPOINT
+    /// exports to that collector instead
+    #[cfg(feature = "otlp")]
+    #[clap(long, value_name = "URL")]
+    pub otlp_endpoint: Option<String>,
     #[cfg(feature = "graphviz")]
     /// Draw the graph of the build process
     /// This will create a file named `graph.svg` in the current directory
@@ -27,56 +48,265 @@ struct Args {
     graph: bool,
     #[clap(long)]
     ascii_graph: bool,
+    /// Write the build graph as JSON to the given file instead of building
+    #[clap(long, value_name = "FILE")]
+    emit: Option<String>,
     /// Keep named intermediate files for debugging instead of anonymous temporaries/in-memory edges
     #[clap(long)]
     debug: bool,
     /// Disable progress bars
     #[clap(long)]
     no_progress: bool,
+    /// How to report what the build is doing
+    #[clap(long, value_enum, default_value_t)]
+    reporter: ReporterKind,
+    /// Carry on building other targets when one fails
+    #[clap(long)]
+    keep_going: bool,
+    /// Write a JSON report of any failures to the given file
+    #[clap(long, value_name = "FILE")]
+    failure_report: Option<String>,
+    /// Keep the temporary files of operations which fail, for debugging
+    #[clap(long)]
+    keep_temps: bool,
+    /// Build byte-identical fonts from the same sources: tools get
+    /// SOURCE_DATE_EPOCH (0 unless it's set already), fonts are dated as of
+    /// it and lose any DSIG table, and temporary files are named predictably
+    #[clap(long)]
+    reproducible: bool,
+    /// Use `gftools fix-font` instead of the built-in font fixer
+    #[clap(long)]
+    use_external_fix: bool,
     /// Limit number of parallel jobs (defaults to number of CPU cores)
     #[clap(long)]
     jobs: Option<usize>,
-    config_file: String,
+    /// Only build the given target (and what it depends on); may be repeated
+    #[clap(long = "target", value_name = "TARGET")]
+    targets: Vec<String>,
+    /// Format of the config file (detected from the file extension by default)
+    #[clap(long, value_enum)]
+    format: Option<ConfigFormat>,
+    /// Path to the config file, or `-` to read a JSON config from stdin
+    #[arg(required = true)]
+    config_file: Option<String>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Print the JSON schema for config files
+    Schema,
+    /// Describe how a target will be built, without building it
+    Explain {
+        /// Format of the config file (detected from the file extension by default)
+        #[clap(long, value_enum)]
+        format: Option<ConfigFormat>,
+        /// Path to the config file
+        config_file: String,
+        /// The target to explain, as it appears in the recipe
+        target: String,
+    },
+    /// Compare the recipes generated by two config files
+    Diff {
+        /// Format of the config files (detected from the file extension by default)
+        #[clap(long, value_enum)]
+        format: Option<ConfigFormat>,
+        /// Path to the old config file
+        old_config_file: String,
+        /// Path to the new config file
+        new_config_file: String,
+    },
+    /// List the targets which would be rebuilt, and why, without building
+    #[command(alias = "would-build")]
+    Status {
+        /// Format of the config file (detected from the file extension by default)
+        #[clap(long, value_enum)]
+        format: Option<ConfigFormat>,
+        /// Path to the config file
+        config_file: String,
+    },
+    /// Build everything twice with `--reproducible` and report any target
+    /// which isn't byte-identical in both builds
+    VerifyRepro {
+        /// Format of the config file (detected from the file extension by default)
+        #[clap(long, value_enum)]
+        format: Option<ConfigFormat>,
+        /// Limit number of parallel jobs (defaults to number of CPU cores)
+        #[clap(long)]
+        jobs: Option<usize>,
+        /// Disable progress bars
+        #[clap(long)]
+        no_progress: bool,
+        /// How to report what the builds are doing
+        #[clap(long, value_enum, default_value_t)]
+        reporter: ReporterKind,
+        /// Only check the given target (and what it depends on); may be repeated
+        #[clap(long = "target", value_name = "TARGET")]
+        targets: Vec<String>,
+        /// Path to the config file
+        config_file: String,
+    },
+    /// Run a build from a graph written with `--emit`
+    RunGraph {
+        /// Directory the graph's paths are relative to; that is, the one
+        /// its config file was in (defaults to the current directory)
+        #[clap(long)]
+        directory: Option<String>,
+        /// Limit number of parallel jobs (defaults to number of CPU cores)
+        #[clap(long)]
+        jobs: Option<usize>,
+        /// Disable progress bars
+        #[clap(long)]
+        no_progress: bool,
+        /// How to report what the build is doing
+        #[clap(long, value_enum, default_value_t)]
+        reporter: ReporterKind,
+        /// Carry on building other targets when one fails
+        #[clap(long)]
+        keep_going: bool,
+        /// Keep the temporary files of operations which fail, for debugging
+        #[clap(long)]
+        keep_temps: bool,
+        /// Build byte-identical fonts from the same sources
+        #[clap(long)]
+        reproducible: bool,
+        /// Path to the graph file
+        graph_file: String,
+    },
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
     let mut _guard = None;
-    if let Some(ref profile_file) = args.profile {
-        // Initialize tracing subscriber if profiling is enabled
+    let chrome_layer = args.profile.as_ref().map(|profile_file| {
         let (chrome_layer, guard) = ChromeLayerBuilder::new()
             .include_args(true)
             .include_locations(true)
             .file(profile_file)
             .build();
         _guard = Some(guard);
+        chrome_layer
+    });
+    #[cfg(feature = "otlp")]
+    let otlp = otlp::OtlpGuard::new(args.otlp_endpoint.as_deref());
+    #[cfg(feature = "otlp")]
+    let otlp_layer = otlp.as_ref().map(otlp::OtlpGuard::layer);
+    #[cfg(not(feature = "otlp"))]
+    let otlp_layer: Option<tracing_subscriber::layer::Identity> = None;
 
+    // Initialize the tracing subscriber if profiling or exporting spans
+    if chrome_layer.is_some() || otlp_layer.is_some() {
         let env_filter = EnvFilter::new("gftools_builder=info");
-
-        // Set up the tracing subscriber with JSON output to stderr
         tracing_subscriber::registry()
             .with(env_filter)
             .with(chrome_layer)
+            .with(otlp_layer)
             .init();
     }
     env_logger::Builder::new()
         .filter_level(args.verbosity.into())
         .init();
 
+    if let Some(command) = args.command {
+        let result = match command {
+            Command::Schema => generate_schema(),
+            Command::Explain {
+                format,
+                config_file,
+                target,
+            } => tokio::task::block_in_place(|| explain_target(&config_file, format, &target)),
+            Command::Diff {
+                format,
+                old_config_file,
+                new_config_file,
+            } => tokio::task::block_in_place(|| {
+                diff_configs(&old_config_file, &new_config_file, format)
+            }),
+            Command::Status {
+                format,
+                config_file,
+            } => tokio::task::block_in_place(|| build_status(&config_file, format)),
+            Command::VerifyRepro {
+                format,
+                jobs,
+                no_progress,
+                reporter,
+                targets,
+                config_file,
+            } => {
+                verify_reproducible(BuildConfig {
+                    config_path: config_file,
+                    config_format: format,
+                    job_limit: jobs.unwrap_or_else(num_cpus::get),
+                    targets,
+                    verbosity: args.verbosity.log_level().unwrap_or(log::Level::Info),
+                    progress: !no_progress,
+                    reporter,
+                    ..Default::default()
+                })
+                .await
+            }
+            Command::RunGraph {
+                directory,
+                jobs,
+                no_progress,
+                reporter,
+                keep_going,
+                keep_temps,
+                reproducible,
+                graph_file,
+            } => {
+                let options = RunOptions {
+                    job_limit: jobs.unwrap_or_else(num_cpus::get),
+                    progress: !no_progress,
+                    reporter,
+                    keep_going,
+                    keep_temps,
+                    reproducible,
+                };
+                if let Err(error) = run_graph(&graph_file, directory.as_deref(), options).await {
+                    eprintln!("{error}");
+                    #[cfg(feature = "otlp")]
+                    drop(otlp);
+                    exit(error.exit_code())
+                }
+                return;
+            }
+        };
+        match result {
+            Ok(output) => println!("{output}"),
+            Err(error) => {
+                eprintln!("{error}");
+                exit(error.exit_code())
+            }
+        }
+        return;
+    }
+    // Clap ensures we have a config file when no subcommand is given
+    let config_file = args.config_file.unwrap_or_default();
+
     let job_limit = args.jobs.unwrap_or_else(num_cpus::get);
     log::info!("Starting gftools-builder with {} parallel jobs", job_limit);
 
     let build_config = BuildConfig {
-        config_path: args.config_file.clone(),
+        config_path: config_file,
+        config_format: args.format,
         job_limit,
+        targets: args.targets,
         generate_only: args.generate,
         #[cfg(feature = "graphviz")]
         draw_graph: args.graph,
         ascii_graph: args.ascii_graph,
+        emit_graph: args.emit,
         debug_intermediates: args.debug,
         verbosity: args.verbosity.log_level().unwrap_or(log::Level::Info),
         progress: !args.no_progress,
+        reporter: args.reporter,
+        keep_going: args.keep_going,
+        failure_report: args.failure_report,
+        keep_temps: args.keep_temps,
+        reproducible: args.reproducible,
+        use_external_fix: args.use_external_fix,
     };
 
     if let Err(error) = build(build_config).await {
@@ -88,6 +318,68 @@ async fn main() {
         // Delay for the error message to be written completely hopefully.
         sleep(Duration::from_millis(1)).await;
 
-        exit(1)
+        // Send any spans we're holding before we go
+        #[cfg(feature = "otlp")]
+        drop(otlp);
+        exit(error.exit_code())
+    }
+}
+
+#[cfg(feature = "otlp")]
+mod otlp {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::{
+        Resource,
+        trace::{SdkTracerProvider, Tracer},
+    };
+    use tracing_opentelemetry::OpenTelemetryLayer;
+    use tracing_subscriber::registry::LookupSpan;
+
+    /// Exports trace spans to an OpenTelemetry collector, flushing them when dropped
+    pub struct OtlpGuard(SdkTracerProvider);
+
+    impl OtlpGuard {
+        /// Set up an exporter if we were given an endpoint, either on the
+        /// command line or in the standard environment variables
+        pub fn new(endpoint: Option<&str>) -> Option<Self> {
+            let from_env = std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_some()
+                || std::env::var_os("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_some();
+            if endpoint.is_none() && !from_env {
+                return None;
+            }
+            let mut builder = SpanExporter::builder().with_http();
+            if let Some(endpoint) = endpoint {
+                builder = builder.with_endpoint(endpoint);
+            }
+            let exporter = builder
+                .build()
+                .inspect_err(|e| log::warn!("Could not set up OTLP exporter: {e}"))
+                .ok()?;
+            let provider = SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(
+                    Resource::builder()
+                        .with_service_name("gftools-builder")
+                        .build(),
+                )
+                .build();
+            Some(OtlpGuard(provider))
+        }
+
+        pub fn layer<S>(&self) -> OpenTelemetryLayer<S, Tracer>
+        where
+            S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+        {
+            tracing_opentelemetry::layer().with_tracer(self.0.tracer("gftools-builder"))
+        }
+    }
+
+    impl Drop for OtlpGuard {
+        fn drop(&mut self) {
+            if let Err(e) = self.0.shutdown() {
+                log::warn!("Could not export trace spans: {e}");
+            }
+        }
     }
 }
@@ -2,27 +2,78 @@ use std::collections::HashMap;
 
 use crate::{
     buildsystem::Operation,
-    operations::{addsubset::AddSubsetConfig, fix::FixConfig, fontc::FontcConfig},
+    operations::{
+        addsubset::AddSubsetConfig, avar2::Avar2Config, buildcolor::BuildColorConfig,
+        buildotf::BuildOTFConfig, buildstat::BuildStatConfig, buildupstream::BuildUpstreamConfig,
+        buildvtt::BuildVTTConfig, check::CheckConfig, checksums::ChecksumsConfig,
+        decompose::DecomposeConfig, diffenator::DiffenatorConfig, fix::FixConfig,
+        fontc::FontcConfig, fstype::FsTypeConfig, fvarinstances::FvarInstancesConfig,
+        localizednames::LocalizedNamesConfig, mvar::MvarConfig, oblique::ObliqueConfig,
+        optimize::OptimizeConfig, paintcompiler::PaintcompilerConfig,
+        productionnames::ProductionNamesConfig, proof::ProofConfig, prunetables::PruneTablesConfig,
+        rename::RenameConfig, setversion::SetVersionConfig, subset::SubsetConfig,
+        ttfautohint::TTFAutohintConfig, verticalmetrics::VerticalMetricsConfig,
+    },
     recipe::{ConfigOperation, Step},
 };
 use fontdrasil::coords::UserLocation;
 use itertools::Itertools;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 pub mod addsubset;
 pub mod autohint;
+pub mod autohintvf;
+pub mod avar2;
+pub mod buildcolor;
+pub mod buildmetadata;
+pub mod buildotf;
 pub mod buildstat;
+pub mod buildupstream;
+pub mod buildvtt;
+pub mod check;
+pub mod checkcompatibility;
+pub mod checksums;
+pub mod compilettx;
 pub mod compress;
 pub mod convert;
+pub mod copy;
+pub mod decompose;
+pub mod dehint;
+pub mod diffbinary;
+pub mod diffenator;
 pub mod fix;
+pub mod flatten;
 pub mod fontc;
+pub mod fstype;
+pub mod fvarinstances;
 pub mod glyphs2ufo;
+pub mod instantiate;
+pub mod localizednames;
+pub mod mvar;
+pub mod normalizecmap;
+pub mod oblique;
+pub mod optimize;
+pub mod paintcompiler;
+pub mod productionnames;
+pub mod proof;
+pub mod prunetables;
+pub mod remaplayout;
+pub mod removeoverlaps;
+pub mod rename;
+pub mod setversion;
+pub mod stylelinking;
+pub mod subset;
 pub mod subspace;
+pub mod ttfautohint;
+pub mod validate;
+pub mod verticalmetrics;
+pub mod woff;
 
 /// Enum representing the different operation steps available
 ///
 /// This is used during recipe deserialization to map step names to operation implementations.
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq, Clone)]
 pub(crate) enum OpStep {
     #[serde(rename = "glyphs2ufo")]
     Glyphs2UFO,
@@ -40,6 +91,84 @@ pub(crate) enum OpStep {
     Subspace,
     #[serde(rename = "autohint")]
     Autohint,
+    #[serde(rename = "buildOTF")]
+    BuildOTF,
+    #[serde(rename = "avar2")]
+    Avar2,
+    #[serde(rename = "setVersion")]
+    SetVersion,
+    #[serde(rename = "checkCompatibility")]
+    CheckCompatibility,
+    #[serde(rename = "remapLayout")]
+    RemapLayout,
+    #[serde(rename = "rename")]
+    Rename,
+    #[serde(rename = "localizedNames")]
+    LocalizedNames,
+    #[serde(rename = "buildMetadata")]
+    BuildMetadata,
+    #[serde(rename = "proof")]
+    Proof,
+    #[serde(rename = "ttfautohint")]
+    TTFAutohint,
+    #[serde(rename = "autohintVF")]
+    AutohintVF,
+    #[serde(rename = "instantiate")]
+    Instantiate,
+    #[serde(rename = "subset")]
+    Subset,
+    #[serde(rename = "removeOverlaps")]
+    RemoveOverlaps,
+    #[serde(rename = "decomposeComponents")]
+    Decompose,
+    #[serde(rename = "flattenComponents")]
+    Flatten,
+    #[serde(rename = "fsType")]
+    FsType,
+    #[serde(rename = "verticalMetrics")]
+    VerticalMetrics,
+    #[serde(rename = "pruneTables")]
+    PruneTables,
+    #[serde(rename = "normalizeCmap")]
+    NormalizeCmap,
+    #[serde(rename = "buildVTT")]
+    BuildVTT,
+    #[serde(rename = "compileTTX")]
+    CompileTTX,
+    #[serde(rename = "woff")]
+    Woff,
+    #[serde(rename = "check")]
+    Check,
+    #[serde(rename = "diffenator")]
+    Diffenator,
+    #[serde(rename = "diffBinary")]
+    DiffBinary,
+    #[serde(rename = "mvar")]
+    Mvar,
+    #[serde(rename = "buildColor")]
+    BuildColor,
+    #[serde(rename = "paintcompiler")]
+    Paintcompiler,
+    #[serde(rename = "productionNames")]
+    ProductionNames,
+    #[serde(rename = "oblique")]
+    Oblique,
+    #[serde(rename = "fvarInstances")]
+    FvarInstances,
+    #[serde(rename = "fixStyleLinking")]
+    FixStyleLinking,
+    #[serde(rename = "dehint")]
+    Dehint,
+    #[serde(rename = "optimize")]
+    Optimize,
+    #[serde(rename = "validate")]
+    Validate,
+    #[serde(rename = "checksums")]
+    Checksums,
+    #[serde(rename = "buildUpstream")]
+    BuildUpstream,
+    #[serde(rename = "copy")]
+    Copy,
 }
 
 impl OpStep {
@@ -49,11 +178,50 @@ impl OpStep {
             OpStep::Fix => Box::new(fix::Fix::new()),
             OpStep::Fontc => Box::new(fontc::Fontc::new()),
             OpStep::Glyphs2UFO => Box::new(glyphs2ufo::Glyphs2UFO),
-            OpStep::BuildStat => Box::new(buildstat::BuildStat),
-            OpStep::Compress => Box::new(compress::Compress),
+            OpStep::BuildStat => Box::new(buildstat::BuildStat::new()),
+            OpStep::Compress => Box::new(compress::Compress::new()),
             OpStep::AddSubset => Box::new(addsubset::AddSubset::new()),
             OpStep::Subspace => Box::new(subspace::Subspace::new()),
             OpStep::Autohint => Box::new(autohint::Autohint::new()),
+            OpStep::BuildOTF => Box::new(buildotf::BuildOTF::new()),
+            OpStep::Avar2 => Box::new(avar2::Avar2::new()),
+            OpStep::SetVersion => Box::new(setversion::SetVersion::new()),
+            OpStep::CheckCompatibility => Box::new(checkcompatibility::CheckCompatibility::new()),
+            OpStep::RemapLayout => Box::new(remaplayout::RemapLayout::new()),
+            OpStep::Rename => Box::new(rename::Rename::new()),
+            OpStep::LocalizedNames => Box::new(localizednames::LocalizedNames::new()),
+            OpStep::BuildMetadata => Box::new(buildmetadata::BuildMetadata),
+            OpStep::Proof => Box::new(proof::Proof::new()),
+            OpStep::TTFAutohint => Box::new(ttfautohint::TTFAutohint::new()),
+            OpStep::AutohintVF => Box::new(autohintvf::AutohintVF::new()),
+            OpStep::Instantiate => Box::new(instantiate::Instantiate::new()),
+            OpStep::Subset => Box::new(subset::Subset::new()),
+            OpStep::RemoveOverlaps => Box::new(removeoverlaps::RemoveOverlaps::new()),
+            OpStep::Decompose => Box::new(decompose::Decompose::new()),
+            OpStep::Flatten => Box::new(flatten::Flatten::new()),
+            OpStep::FsType => Box::new(fstype::FsType::new()),
+            OpStep::VerticalMetrics => Box::new(verticalmetrics::VerticalMetrics::new()),
+            OpStep::PruneTables => Box::new(prunetables::PruneTables::new()),
+            OpStep::NormalizeCmap => Box::new(normalizecmap::NormalizeCmap::new()),
+            OpStep::BuildVTT => Box::new(buildvtt::BuildVTT::new()),
+            OpStep::CompileTTX => Box::new(compilettx::CompileTTX::new()),
+            OpStep::Woff => Box::new(woff::Woff),
+            OpStep::Check => Box::new(check::Check::new()),
+            OpStep::Diffenator => Box::new(diffenator::Diffenator::new()),
+            OpStep::DiffBinary => Box::new(diffbinary::DiffBinary::new()),
+            OpStep::Mvar => Box::new(mvar::AddMvar::new()),
+            OpStep::BuildColor => Box::new(buildcolor::BuildColor::new()),
+            OpStep::Paintcompiler => Box::new(paintcompiler::Paintcompiler::new()),
+            OpStep::ProductionNames => Box::new(productionnames::ProductionNames::new()),
+            OpStep::Oblique => Box::new(oblique::Oblique::new()),
+            OpStep::FvarInstances => Box::new(fvarinstances::FvarInstances::new()),
+            OpStep::FixStyleLinking => Box::new(stylelinking::FixStyleLinking),
+            OpStep::Dehint => Box::new(dehint::Dehint),
+            OpStep::Optimize => Box::new(optimize::Optimize::new()),
+            OpStep::Validate => Box::new(validate::Validate),
+            OpStep::Checksums => Box::new(checksums::Checksums::new()),
+            OpStep::BuildUpstream => Box::new(buildupstream::BuildUpstream::new()),
+            OpStep::Copy => Box::new(copy::CopyFile),
         }
     }
 }
@@ -127,6 +295,17 @@ impl ConfigOperationBuilder {
         self
     }
 
+    pub fn check_compatibility(mut self) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::CheckCompatibility,
+            extra: HashMap::new(),
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
     pub fn compress(mut self) -> Self {
         self.steps.push(Step::OperationStep {
             operation: OpStep::Compress,
@@ -138,9 +317,32 @@ impl ConfigOperationBuilder {
         self
     }
 
-    pub fn buildstat(mut self, others: &[String]) -> Self {
+    pub fn woff(mut self) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Woff,
+            extra: HashMap::new(),
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn buildstat(mut self, others: &[String], config: &BuildStatConfig) -> Self {
+        let extra = Self::to_extra(config);
         self.steps.push(Step::OperationStep {
             operation: OpStep::BuildStat,
+            extra,
+            args: None,
+            input_file: None,
+            needs: others.to_vec(),
+        });
+        self
+    }
+
+    pub fn build_metadata(mut self, others: &[String]) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::BuildMetadata,
             extra: HashMap::new(),
             args: None,
             input_file: None,
@@ -149,6 +351,18 @@ impl ConfigOperationBuilder {
         self
     }
 
+    pub fn proof(mut self, config: &ProofConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Proof,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
     pub fn add_subset(mut self, config: &AddSubsetConfig, donor: &str) -> Self {
         let extra = Self::to_extra(config);
         self.steps.push(Step::OperationStep {
@@ -177,6 +391,375 @@ impl ConfigOperationBuilder {
         self
     }
 
+    /// Cut a static font at the given location; axes not mentioned are pinned at their defaults
+    pub fn instantiate(mut self, location: &UserLocation) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Instantiate,
+            args: Some(
+                location
+                    .iter()
+                    .map(|(axis, value)| format!("{}={}", axis, value.to_f64()))
+                    .join(","),
+            ),
+            input_file: None,
+            extra: HashMap::new(),
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn subset(mut self, config: &SubsetConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Subset,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn remove_overlaps(mut self) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::RemoveOverlaps,
+            extra: HashMap::new(),
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn decompose(mut self, config: &DecomposeConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Decompose,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn flatten(mut self) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Flatten,
+            extra: HashMap::new(),
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn fs_type(mut self, config: &FsTypeConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::FsType,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn vertical_metrics(mut self, config: &VerticalMetricsConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::VerticalMetrics,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn prune_tables(mut self, config: &PruneTablesConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::PruneTables,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn normalize_cmap(mut self) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::NormalizeCmap,
+            extra: HashMap::new(),
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn build_vtt(mut self, config: &BuildVTTConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::BuildVTT,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn check(mut self, config: &CheckConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Check,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn diffenator(mut self, config: &DiffenatorConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Diffenator,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn mvar(mut self, config: &MvarConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Mvar,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn build_color(mut self, config: &BuildColorConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::BuildColor,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn paintcompiler(mut self, config: &PaintcompilerConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Paintcompiler,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn production_names(mut self, config: &ProductionNamesConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::ProductionNames,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn oblique(mut self, config: &ObliqueConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Oblique,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn fvar_instances(mut self, config: &FvarInstancesConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::FvarInstances,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn fix_style_linking(mut self) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::FixStyleLinking,
+            extra: HashMap::new(),
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn dehint(mut self) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Dehint,
+            extra: HashMap::new(),
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn optimize(mut self, config: &OptimizeConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Optimize,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn validate(mut self) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Validate,
+            extra: HashMap::new(),
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn copy(mut self) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Copy,
+            extra: HashMap::new(),
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn checksums(mut self, others: &[String], config: &ChecksumsConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Checksums,
+            extra,
+            args: None,
+            input_file: None,
+            needs: others.to_vec(),
+        });
+        self
+    }
+
+    pub fn build_upstream(mut self, others: &[String], config: &BuildUpstreamConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::BuildUpstream,
+            extra,
+            args: None,
+            input_file: None,
+            needs: others.to_vec(),
+        });
+        self
+    }
+
+    pub fn build_otf(mut self, config: &BuildOTFConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::BuildOTF,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn avar2(mut self, config: &Avar2Config) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Avar2,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn remap_layout(mut self, mapping: &str) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::RemapLayout,
+            extra: HashMap::new(),
+            args: Some(mapping.to_string()),
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn rename(mut self, config: &RenameConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::Rename,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn localized_names(mut self, config: &LocalizedNamesConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::LocalizedNames,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn set_version(mut self, config: &SetVersionConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::SetVersion,
+            extra,
+            args: None,
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
     pub fn autohint(mut self) -> Self {
         self.steps.push(Step::OperationStep {
             operation: OpStep::Autohint,
@@ -187,6 +770,29 @@ impl ConfigOperationBuilder {
         });
         self
     }
+
+    pub fn ttfautohint(mut self, args: Option<&str>, config: &TTFAutohintConfig) -> Self {
+        let extra = Self::to_extra(config);
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::TTFAutohint,
+            extra,
+            args: args.map(|args| args.to_string()),
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
+
+    pub fn autohint_vf(mut self, args: Option<&str>) -> Self {
+        self.steps.push(Step::OperationStep {
+            operation: OpStep::AutohintVF,
+            extra: HashMap::new(),
+            args: args.map(|args| args.to_string()),
+            input_file: None,
+            needs: vec![],
+        });
+        self
+    }
 }
 
 impl Default for ConfigOperationBuilder {
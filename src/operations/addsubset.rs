@@ -10,9 +10,16 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct AddSubsetConfig {
+    /// Glyphs to take from the donor
+    #[serde(default)]
     pub include_glyphs: Vec<String>,
+    /// Glyphs not to take from the donor, even if they are otherwise included
+    #[serde(default)]
     pub exclude_glyphs: Vec<String>,
+    /// Codepoints whose glyphs should be taken from the donor
+    #[serde(default)]
     pub include_codepoints: Vec<u32>,
     #[serde(
         default,
@@ -90,6 +97,10 @@ where
     }
 }
 
+/// Merge glyphs from a donor source into the font
+///
+/// The donor is the step's second input, given in a recipe with `from:` (or
+/// `needs:`), and the config chooses which of its glyphs are taken.
 #[derive(PartialEq, Debug)]
 pub(crate) struct AddSubset {
     config: AddSubsetConfig,
@@ -138,7 +149,7 @@ impl Operation for AddSubset {
     }
 
     fn input_kinds(&self) -> Vec<DataKind> {
-        vec![DataKind::SourceFont]
+        vec![DataKind::SourceFont, DataKind::SourceFont]
     }
 
     fn output_kinds(&self) -> Vec<DataKind> {
@@ -184,7 +195,10 @@ impl Operation for AddSubset {
         // Deserialize the extra map into our typed config
         let value = Value::Object(extra.into_iter().collect());
         self.config = serde_json::from_value(value).unwrap_or_else(|e| {
-            log::warn!("Failed to deserialize config: {}. Using defaults.", e);
+            log::warn!(
+                "Failed to deserialize AddSubset config: {}. Using defaults.",
+                e
+            );
             AddSubsetConfig::default()
         });
     }
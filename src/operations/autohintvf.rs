@@ -0,0 +1,68 @@
+use std::process::Output;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// Hint a variable font by shelling out to ttfautohint-vf
+///
+/// The ordinary ttfautohint (and tilvisan) only hint the default master, so
+/// the instructions go wrong away from it; the VF-capable fork writes `cvar`
+/// variations as well. As in gftools, natural stem widths are used for every
+/// rendering mode (`--stem-width-mode nnn`); any arguments come after this,
+/// so they can override it.
+#[derive(PartialEq, Debug)]
+pub(crate) struct AutohintVF {
+    args: Option<String>,
+}
+
+impl AutohintVF {
+    pub fn new() -> Self {
+        AutohintVF { args: None }
+    }
+}
+
+impl Operation for AutohintVF {
+    fn shortname(&self) -> &str {
+        "AutohintVF"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let mut cmd = "ttfautohint-vf --stem-width-mode nnn".to_string();
+        if let Some(args) = &self.args {
+            cmd.push(' ');
+            cmd.push_str(args);
+        }
+        cmd.push_str(&format!(
+            " {} {}",
+            inputs[0].to_filename(Some(".ttf"))?,
+            outputs[0].to_filename(Some(".ttf"))?
+        ));
+        self.run_shell_command(&cmd, outputs)
+    }
+
+    fn description(&self) -> String {
+        "Autohint variable font with ttfautohint-vf".to_string()
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn identifier(&self) -> String {
+        format!("AutohintVF-{}", self.args.as_deref().unwrap_or(""))
+    }
+}
@@ -0,0 +1,400 @@
+use std::{collections::HashMap, os::unix::process::ExitStatusExt, process::Output};
+
+use read_fonts::{FontRef, TableProvider};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use write_fonts::{
+    FontBuilder,
+    from_obj::ToOwnedTable,
+    tables::{
+        avar::{Avar, SegmentMaps},
+        variations::{DeltaSetIndexMap, ivs_builder::VariationStoreBuilder},
+    },
+};
+
+use super::mvar::{AddMvar, Location};
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// A delta-set index meaning "this axis doesn't vary"
+const NO_VARIATION_INDEX: u32 = 0xFFFF_FFFF;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Avar2Config {
+    /// A designspace file whose `<mappings>` define the avar2 table
+    pub designspace: String,
+}
+
+/// A designspace axis: its name, tag and `<map>` as (userspace, designspace) pairs
+#[derive(Debug, PartialEq)]
+struct DesignspaceAxis {
+    name: String,
+    tag: String,
+    map: Vec<(f64, f64)>,
+}
+
+impl DesignspaceAxis {
+    /// Convert a designspace coordinate on this axis to userspace
+    fn to_userspace(&self, value: f64) -> f64 {
+        let mut map = self.map.clone();
+        map.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let (Some(first), Some(last)) = (map.first(), map.last()) else {
+            return value;
+        };
+        if value <= first.1 {
+            return first.0 + (value - first.1);
+        }
+        if value >= last.1 {
+            return last.0 + (value - last.1);
+        }
+        map.windows(2)
+            .find(|pair| pair[0].1 <= value && value <= pair[1].1)
+            .map(|pair| {
+                let ((user0, design0), (user1, design1)) = (pair[0], pair[1]);
+                if design1 == design0 {
+                    user0
+                } else {
+                    user0 + (value - design0) * (user1 - user0) / (design1 - design0)
+                }
+            })
+            .unwrap_or(value)
+    }
+}
+
+/// Add an avar version 2 table built from a designspace's `<mappings>`
+///
+/// Each mapping's input and output are normalized against the font's fvar and
+/// existing avar, and the difference between them is stored in avar's
+/// variation store, one delta set per axis, using the same model as `AddMvar`.
+/// Any avar version 1 segment maps the font has are kept.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Avar2 {
+    config: Avar2Config,
+}
+
+impl Avar2 {
+    pub fn new() -> Self {
+        Avar2 {
+            config: Avar2Config::default(),
+        }
+    }
+
+    /// Each `<name …>` element in `text`, up to its `/>` or `</name>`
+    fn elements<'a>(text: &'a str, name: &str) -> Vec<&'a str> {
+        let open = format!("<{name}");
+        let close = format!("</{name}>");
+        let mut elements = vec![];
+        let mut rest = text;
+        while let Some(start) = rest.find(&open) {
+            let element = &rest[start..];
+            let after = &element[open.len()..];
+            if !after.starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>') {
+                rest = after;
+                continue;
+            }
+            let Some(tag_end) = element.find('>') else {
+                break;
+            };
+            let end = if element[..tag_end].ends_with('/') {
+                tag_end + 1
+            } else {
+                match element.find(&close) {
+                    Some(end) => end + close.len(),
+                    None => break,
+                }
+            };
+            elements.push(&element[..end]);
+            rest = &element[end..];
+        }
+        elements
+    }
+
+    /// The value of an attribute on an element's start tag
+    fn attribute<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+        let tag = &element[..element.find('>').unwrap_or(element.len())];
+        let pattern = format!("{name}=");
+        let start = tag
+            .match_indices(&pattern)
+            .map(|(index, _)| index)
+            .find(|&index| tag[..index].ends_with(char::is_whitespace))?;
+        let value = &tag[start + pattern.len()..];
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &value[1..];
+        Some(&value[..value.find(quote)?])
+    }
+
+    fn number(element: &str, name: &str) -> Result<f64, ApplicationError> {
+        Self::attribute(element, name)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| {
+                ApplicationError::InvalidRecipe(format!(
+                    "Designspace element {element} needs a numeric {name}"
+                ))
+            })
+    }
+
+    fn axes(text: &str) -> Result<Vec<DesignspaceAxis>, ApplicationError> {
+        Self::elements(text, "axis")
+            .into_iter()
+            .map(|axis| {
+                let (Some(name), Some(tag)) =
+                    (Self::attribute(axis, "name"), Self::attribute(axis, "tag"))
+                else {
+                    return Err(ApplicationError::InvalidRecipe(format!(
+                        "Designspace axis {axis} needs a name and a tag"
+                    )));
+                };
+                let map = Self::elements(axis, "map")
+                    .into_iter()
+                    .map(|map| Ok((Self::number(map, "input")?, Self::number(map, "output")?)))
+                    .collect::<Result<Vec<_>, ApplicationError>>()?;
+                Ok(DesignspaceAxis {
+                    name: name.to_string(),
+                    tag: tag.to_string(),
+                    map,
+                })
+            })
+            .collect()
+    }
+
+    /// A mapping's `<input>` or `<output>` as userspace values by axis tag
+    fn location(
+        element: &str,
+        axes: &[DesignspaceAxis],
+    ) -> Result<HashMap<String, f64>, ApplicationError> {
+        Self::elements(element, "dimension")
+            .into_iter()
+            .map(|dimension| {
+                let name = Self::attribute(dimension, "name").unwrap_or_default();
+                let axis = axes.iter().find(|axis| axis.name == name).ok_or_else(|| {
+                    ApplicationError::InvalidRecipe(format!(
+                        "Designspace mapping uses unknown axis {name}"
+                    ))
+                })?;
+                let value = Self::number(dimension, "xvalue")?;
+                Ok((axis.tag.clone(), axis.to_userspace(value)))
+            })
+            .collect()
+    }
+
+    /// The designspace's `<mappings>` as (input, output) userspace locations
+    #[allow(clippy::type_complexity)]
+    fn mappings(
+        text: &str,
+    ) -> Result<Vec<(HashMap<String, f64>, HashMap<String, f64>)>, ApplicationError> {
+        let axes = Self::axes(text)?;
+        Self::elements(text, "mapping")
+            .into_iter()
+            .map(|mapping| {
+                let input = Self::elements(mapping, "input");
+                let output = Self::elements(mapping, "output");
+                let (Some(input), Some(output)) = (input.first(), output.first()) else {
+                    return Err(ApplicationError::InvalidRecipe(format!(
+                        "Designspace mapping {mapping} needs an input and an output"
+                    )));
+                };
+                Ok((
+                    Self::location(input, &axes)?,
+                    Self::location(output, &axes)?,
+                ))
+            })
+            .collect()
+    }
+
+    fn build_avar(&self, font: &FontRef) -> Result<Option<Avar>, ApplicationError> {
+        let text = std::fs::read_to_string(&self.config.designspace).map_err(|e| {
+            ApplicationError::InvalidRecipe(format!(
+                "Failed to read designspace {}: {e}",
+                self.config.designspace
+            ))
+        })?;
+        let mut mappings = vec![];
+        for (input, output) in Self::mappings(&text)? {
+            let input = AddMvar::normalize(font, &input)?;
+            let output = AddMvar::normalize(font, &output)?;
+            // The default location always maps to itself
+            if input.iter().any(|v| *v != 0.0) {
+                mappings.push((input, output));
+            }
+        }
+        if mappings.is_empty() {
+            return Ok(None);
+        }
+        // As with masters, the default first, then by how many axes they are off-default on
+        mappings.sort_by_key(|(input, _)| input.iter().filter(|v| **v != 0.0).count());
+        let axis_count = font.fvar()?.axis_count();
+        let default: Location = vec![0.0; axis_count as usize];
+        let locations = std::iter::once(default)
+            .chain(mappings.iter().map(|(input, _)| input.clone()))
+            .collect::<Vec<_>>();
+        let supports = AddMvar::supports(&locations);
+
+        let mut store = VariationStoreBuilder::new(axis_count);
+        let mut temporary_ids = vec![];
+        for axis in 0..axis_count as usize {
+            // How far each mapping moves this axis, in F2Dot14 units
+            let values = std::iter::once(0.0)
+                .chain(
+                    mappings
+                        .iter()
+                        .map(|(input, output)| (output[axis] - input[axis]) * 16384.0),
+                )
+                .collect::<Vec<_>>();
+            let deltas = AddMvar::deltas(&values, &supports, &locations);
+            let regions = supports
+                .iter()
+                .zip(deltas.iter())
+                .skip(1)
+                .filter(|(_, delta)| delta.round() != 0.0)
+                .map(|(support, delta)| (AddMvar::region(support), delta.round() as i32))
+                .collect::<Vec<_>>();
+            temporary_ids.push((!regions.is_empty()).then(|| store.add_deltas(regions)));
+        }
+        if temporary_ids.iter().all(Option::is_none) {
+            return Ok(None);
+        }
+        let (store, remapping) = store.build();
+        let index_map = temporary_ids
+            .into_iter()
+            .map(|id| {
+                id.and_then(|id| remapping.get(id))
+                    .map(|index| {
+                        ((index.delta_set_outer_index as u32) << 16)
+                            | index.delta_set_inner_index as u32
+                    })
+                    .unwrap_or(NO_VARIATION_INDEX)
+            })
+            .collect::<DeltaSetIndexMap>();
+
+        let mut avar: Avar = match font.avar() {
+            Ok(avar) => avar.to_owned_table(),
+            Err(_) => Avar {
+                axis_segment_maps: (0..axis_count).map(|_| SegmentMaps::default()).collect(),
+                ..Default::default()
+            },
+        };
+        avar.axis_index_map = Some(index_map).into();
+        avar.var_store = Some(store).into();
+        Ok(Some(avar))
+    }
+}
+
+impl Operation for Avar2 {
+    fn shortname(&self) -> &str {
+        "Avar2"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        if self.config.designspace.is_empty() {
+            return Err(ApplicationError::InvalidRecipe(
+                "The avar2 operation needs a designspace".to_string(),
+            ));
+        }
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        match self.build_avar(&font)? {
+            Some(avar) => {
+                let mut builder = FontBuilder::new();
+                builder.add_table(&avar)?;
+                builder.copy_missing_tables(font);
+                outputs[0].set_contents(builder.build())?;
+            }
+            None => {
+                log::warn!(
+                    "{} has no mappings which move any axis; not adding avar2",
+                    self.config.designspace
+                );
+                outputs[0].set_contents(bytes.clone())?;
+            }
+        }
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        format!("Add avar2 table from {}", self.config.designspace)
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!("Failed to deserialize Avar2 config: {}. Using defaults.", e);
+            Avar2Config::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("Avar2-{}", self.config.designspace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESIGNSPACE: &str = r#"<?xml version='1.0' encoding='UTF-8'?>
+<designspace format="5.1">
+  <axes>
+    <axis tag="wght" name="Weight" minimum="100" maximum="900" default="400">
+      <map input="100" output="20"/>
+      <map input="400" output="80"/>
+      <map input="900" output="200"/>
+    </axis>
+    <axis tag="opsz" name="Optical size" minimum="8" maximum="144" default="14"/>
+    <mappings>
+      <mapping>
+        <input>
+          <dimension name="Optical size" xvalue="144"/>
+        </input>
+        <output>
+          <dimension name="Optical size" xvalue="144"/>
+          <dimension name="Weight" xvalue="140"/>
+        </output>
+      </mapping>
+    </mappings>
+  </axes>
+</designspace>
+"#;
+
+    #[test]
+    fn test_designspace_axes() {
+        let axes = Avar2::axes(DESIGNSPACE).unwrap();
+        assert_eq!(axes.len(), 2);
+        assert_eq!(axes[0].tag, "wght");
+        assert_eq!(axes[1].name, "Optical size");
+        assert!(axes[1].map.is_empty());
+        assert_eq!(axes[0].to_userspace(80.0), 400.0);
+        assert_eq!(axes[0].to_userspace(140.0), 650.0);
+        assert_eq!(axes[1].to_userspace(72.0), 72.0);
+    }
+
+    #[test]
+    fn test_designspace_mappings() {
+        let mappings = Avar2::mappings(DESIGNSPACE).unwrap();
+        assert_eq!(mappings.len(), 1);
+        let (input, output) = &mappings[0];
+        assert_eq!(input, &HashMap::from([("opsz".to_string(), 144.0)]));
+        assert_eq!(
+            output,
+            &HashMap::from([("opsz".to_string(), 144.0), ("wght".to_string(), 650.0)])
+        );
+    }
+}
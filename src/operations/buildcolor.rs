@@ -0,0 +1,104 @@
+use std::{collections::HashMap, process::Output};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+    operations::buildotf::BuildOTF,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildColorConfig {
+    /// Compile the masters into a variable font rather than a single static
+    #[serde(default)]
+    pub variable: bool,
+}
+
+/// Compile a source with color layers into a COLRv1/CPAL font with fontmake
+///
+/// fontc ignores color layers, so color sources go through glyphsLib and
+/// ufo2ft instead. Each color glyph's master layer becomes its base glyph
+/// outline, so dropping COLR and CPAL from the result gives a monochrome
+/// fallback.
+#[derive(PartialEq, Debug)]
+pub(crate) struct BuildColor {
+    args: Option<String>,
+    config: BuildColorConfig,
+}
+
+impl BuildColor {
+    pub fn new() -> Self {
+        BuildColor {
+            args: None,
+            config: BuildColorConfig::default(),
+        }
+    }
+}
+
+impl Operation for BuildColor {
+    fn shortname(&self) -> &str {
+        "BuildColor"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let (source, _converted) = BuildOTF::source_args(&inputs[0])?;
+        let mut cmd = format!(
+            "fontmake -o {} --output-path {}",
+            if self.config.variable {
+                "variable"
+            } else {
+                "ttf"
+            },
+            outputs[0].to_filename(Some(".ttf"))?
+        );
+        if let Some(args) = &self.args {
+            cmd.push(' ');
+            cmd.push_str(args);
+        }
+        cmd.push(' ');
+        cmd.push_str(&source);
+        self.run_shell_command(&cmd, outputs)
+    }
+
+    fn description(&self) -> String {
+        "Build color font with fontmake".to_string()
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize BuildColor config: {}. Using defaults.",
+                e
+            );
+            BuildColorConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!(
+            "BuildColor-{}-{}",
+            self.config.variable,
+            self.args.as_deref().unwrap_or("")
+        )
+    }
+}
@@ -0,0 +1,219 @@
+use std::{os::unix::process::ExitStatusExt, path::Path, process::Output};
+
+use read_fonts::{
+    FontRef, TableProvider,
+    tables::os2::SelectionFlags,
+    types::{NameId, Tag},
+};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// A name table entry in US English
+pub(crate) fn english_name(font: &FontRef, name_id: NameId) -> Option<String> {
+    let name = font.name().ok()?;
+    name.name_record()
+        .iter()
+        .filter(|record| record.name_id() == name_id)
+        .find(|record| record.platform_id() == 3 && record.language_id() == 0x0409)
+        .and_then(|record| record.string(name.string_data()).ok())
+        .map(|string| string.chars().collect())
+}
+
+/// What METADATA.pb says about one font file
+struct FontEntry {
+    name: String,
+    style: &'static str,
+    weight: u16,
+    filename: String,
+    post_script_name: String,
+    full_name: String,
+    copyright: String,
+}
+
+/// Write (or update) a Google Fonts METADATA.pb describing the built fonts
+///
+/// The first input is the primary font; the fonts it `needs` arrive in the
+/// following slots and are passed through untouched. Top-level fields of an
+/// existing METADATA.pb which we can't derive from the binaries (designer,
+/// category, subsets and so on) are kept; without one, they are left out
+/// for the onboarder to fill in rather than guessed.
+#[derive(PartialEq, Debug)]
+pub(crate) struct BuildMetadata;
+
+impl BuildMetadata {
+    fn font_entry(filename: String, font: &FontRef) -> Result<FontEntry, ApplicationError> {
+        let name = |name_id| english_name(font, name_id).unwrap_or_default();
+        let family = english_name(font, NameId::TYPOGRAPHIC_FAMILY_NAME)
+            .unwrap_or_else(|| name(NameId::FAMILY_NAME));
+        let os2 = font.os2()?;
+        let style = if os2.fs_selection().contains(SelectionFlags::ITALIC) {
+            "italic"
+        } else {
+            "normal"
+        };
+        Ok(FontEntry {
+            name: family,
+            style,
+            weight: os2.us_weight_class(),
+            filename,
+            post_script_name: name(NameId::POSTSCRIPT_NAME),
+            full_name: name(NameId::FULL_NAME),
+            copyright: name(NameId::COPYRIGHT_NOTICE),
+        })
+    }
+
+    /// Split a text-format protobuf into its top-level fields, as (key, text) pairs
+    fn top_level_fields(text: &str) -> Vec<(String, String)> {
+        let mut fields: Vec<(String, String)> = vec![];
+        let mut depth = 0;
+        for line in text.lines() {
+            if depth == 0 {
+                let key = line
+                    .split([':', ' ', '{'])
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                if key.is_empty() || key.starts_with('#') {
+                    continue;
+                }
+                fields.push((key, String::new()));
+            }
+            if let Some((_, field)) = fields.last_mut() {
+                field.push_str(line);
+                field.push('\n');
+            }
+            if line.trim_end().ends_with('{') {
+                depth += 1;
+            } else if line.trim() == "}" {
+                depth -= 1;
+            }
+        }
+        fields
+    }
+
+    fn quote(s: &str) -> String {
+        format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    fn render(existing: &str, fonts: &[FontEntry], axes: &[(Tag, f64, f64)]) -> String {
+        let family = fonts
+            .first()
+            .map(|font| font.name.clone())
+            .unwrap_or_default();
+        let fonts_text: String = fonts
+            .iter()
+            .map(|font| {
+                format!(
+                    "fonts {{\n  name: {}\n  style: {}\n  weight: {}\n  filename: {}\n  post_script_name: {}\n  full_name: {}\n  copyright: {}\n}}\n",
+                    Self::quote(&font.name),
+                    Self::quote(font.style),
+                    font.weight,
+                    Self::quote(&font.filename),
+                    Self::quote(&font.post_script_name),
+                    Self::quote(&font.full_name),
+                    Self::quote(&font.copyright),
+                )
+            })
+            .collect();
+        let axes_text: String = axes
+            .iter()
+            .map(|(tag, min, max)| {
+                format!(
+                    "axes {{\n  tag: \"{tag}\"\n  min_value: {min:.1}\n  max_value: {max:.1}\n}}\n"
+                )
+            })
+            .collect();
+
+        let fields = Self::top_level_fields(existing);
+        let mut out = format!("name: {}\n", Self::quote(&family));
+        let (mut wrote_fonts, mut wrote_axes) = (false, false);
+        for (key, text) in fields {
+            match key.as_str() {
+                "name" => {}
+                "fonts" if !wrote_fonts => {
+                    out.push_str(&fonts_text);
+                    wrote_fonts = true;
+                }
+                "axes" if !wrote_axes => {
+                    out.push_str(&axes_text);
+                    wrote_axes = true;
+                }
+                "fonts" | "axes" => {}
+                _ => out.push_str(&text),
+            }
+        }
+        if !wrote_fonts {
+            out.push_str(&fonts_text);
+        }
+        if !wrote_axes {
+            out.push_str(&axes_text);
+        }
+        out
+    }
+}
+
+impl Operation for BuildMetadata {
+    fn shortname(&self) -> &str {
+        "BuildMetadata"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn variadic_inputs(&self) -> bool {
+        true
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let mut fonts = vec![];
+        let mut axes: Vec<(Tag, f64, f64)> = vec![];
+        for input in inputs {
+            let filename = input.to_filename(Some(".ttf"))?;
+            let filename = Path::new(&filename)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or(filename);
+            let bytes = input.to_mapped_bytes()?;
+            let font = FontRef::new(&bytes)?;
+            fonts.push(Self::font_entry(filename, &font)?);
+            if let Ok(fvar) = font.fvar() {
+                for axis in fvar.axes()? {
+                    let tag = axis.axis_tag();
+                    if !axes.iter().any(|(other, _, _)| *other == tag) {
+                        axes.push((tag, axis.min_value().to_f64(), axis.max_value().to_f64()));
+                    }
+                }
+            }
+        }
+        fonts.sort_by_key(|font| (font.style == "italic", font.weight));
+        axes.sort_by_key(|(tag, _, _)| *tag);
+
+        let metadata_path = outputs[0].to_filename(Some(".pb"))?;
+        let existing = std::fs::read_to_string(&metadata_path).unwrap_or_default();
+        outputs[0].set_contents(Self::render(&existing, &fonts, &axes).into_bytes())?;
+
+        self.pass_through_needs(inputs, outputs)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Write METADATA.pb".to_string()
+    }
+}
@@ -0,0 +1,133 @@
+use std::{collections::HashMap, process::Output};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tempfile::NamedTempFile;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput, SourceKind},
+    error::ApplicationError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildOTFConfig {
+    /// The style name of the instance to build; if not given, the source's
+    /// masters are compiled instead
+    #[serde(default)]
+    pub instance: Option<String>,
+}
+
+/// Compile a source to CFF-flavoured OpenType by shelling out to fontmake
+///
+/// fontc can't write CFF outlines yet; when it can, this is the operation which
+/// should grow a native path, so recipes using `buildOTF` won't need changing.
+#[derive(PartialEq, Debug)]
+pub(crate) struct BuildOTF {
+    args: Option<String>,
+    config: BuildOTFConfig,
+}
+
+impl BuildOTF {
+    pub fn new() -> Self {
+        BuildOTF {
+            args: None,
+            config: BuildOTFConfig::default(),
+        }
+    }
+
+    /// fontmake's arguments for the source, converting it to a Glyphs file
+    /// if fontmake can't read it as it is
+    pub(crate) fn source_args(
+        source: &OperationOutput,
+    ) -> Result<(String, Option<NamedTempFile>), ApplicationError> {
+        let (filename, converted) = source.to_source_filename(SourceKind::fontmake_reads)?;
+        let flag = SourceKind::from_path(&filename)
+            .fontmake_flag()
+            .unwrap_or("-g");
+        Ok((format!("{flag} {filename}"), converted))
+    }
+
+    /// A fontmake `-i` argument selecting the instance with the given style name
+    ///
+    /// fontmake matches instance names, which are usually "Family Style", so
+    /// we anchor the style name at the end.
+    fn instance_selector(style: &str) -> String {
+        let escaped: String = style
+            .chars()
+            .flat_map(|ch| {
+                let escape = r"\.^$|?*+()[]{}".contains(ch);
+                escape.then_some('\\').into_iter().chain([ch])
+            })
+            .collect::<String>()
+            .replace('\'', r"'\''");
+        format!("-i '.* {escaped}$'")
+    }
+}
+
+impl Operation for BuildOTF {
+    fn shortname(&self) -> &str {
+        "BuildOTF"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let (source, _converted) = Self::source_args(&inputs[0])?;
+        let mut cmd = format!(
+            "fontmake -o otf --output-path {}",
+            outputs[0].to_filename(Some(".otf"))?
+        );
+        if let Some(style) = &self.config.instance {
+            cmd.push(' ');
+            cmd.push_str(&Self::instance_selector(style));
+        }
+        if let Some(args) = &self.args {
+            cmd.push(' ');
+            cmd.push_str(args);
+        }
+        cmd.push(' ');
+        cmd.push_str(&source);
+        self.run_shell_command(&cmd, outputs)
+    }
+
+    fn description(&self) -> String {
+        match &self.config.instance {
+            Some(style) => format!("Build {style} OTF with fontmake"),
+            None => "Build OTF with fontmake".to_string(),
+        }
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize BuildOTF config: {}. Using defaults.",
+                e
+            );
+            BuildOTFConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!(
+            "BuildOTF-{}-{}",
+            self.config.instance.as_deref().unwrap_or(""),
+            self.args.as_deref().unwrap_or("")
+        )
+    }
+}
@@ -1,4 +1,18 @@
-use std::{os::unix::process::ExitStatusExt, process::Output};
+use std::{collections::HashMap, os::unix::process::ExitStatusExt, path::Path, process::Output};
+
+use read_fonts::{TableProvider, tables::stat::AxisValueTableFlags, types::NameId};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use write_fonts::{
+    FontBuilder,
+    from_obj::ToOwnedTable,
+    tables::{
+        name::{Name, NameRecord},
+        stat::{AxisRecord, AxisValue, Stat},
+    },
+    types::{Fixed, Tag},
+};
 
 use crate::{
     buildsystem::{DataKind, Operation, OperationOutput},
@@ -6,8 +20,201 @@ use crate::{
 };
 use google_fonts_axisregistry::{FontRef, build_stat};
 
+/// One value of a STAT axis
+///
+/// A value with `rangeMinValue` and `rangeMaxValue` becomes a format 2 axis
+/// value, one with `linkedValue` a format 3, and anything else a format 1.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatValue {
+    pub name: String,
+    /// The value, or for a range, its nominal value
+    pub value: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linked_value: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range_min_value: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range_max_value: Option<f64>,
+    /// Axis value flags; 2 marks the value as elidable
+    #[serde(default)]
+    pub flags: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatAxis {
+    pub name: String,
+    pub tag: String,
+    /// The axis' position in the ordering of style name parts; defaults to
+    /// its position in the list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ordering: Option<u16>,
+    #[serde(default)]
+    pub values: Vec<StatValue>,
+}
+
+/// A STAT table description, either one list of axes for every font or a
+/// list per font keyed by filename (e.g. `Family[wght].ttf`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(untagged)]
+pub enum StatSpec {
+    AllFonts(Vec<StatAxis>),
+    PerFont(HashMap<String, Vec<StatAxis>>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildStatConfig {
+    /// The STAT table to build; fonts it doesn't describe get one derived
+    /// from the axis registry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stat: Option<StatSpec>,
+    /// The name used when every part of a style name is elided; defaults to
+    /// the font's subfamily name (name ID 2)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elided_fallback_name: Option<String>,
+}
+
+/// Add STAT tables to a family of variable fonts
+///
+/// The first input is the font whose target this is; its siblings arrive
+/// through `siblings:` (or `needs:`), so that each font's STAT table can
+/// refer to the others (e.g. linking Roman to Italic).
 #[derive(PartialEq, Debug)]
-pub(crate) struct BuildStat;
+pub(crate) struct BuildStat {
+    config: BuildStatConfig,
+}
+
+impl BuildStat {
+    pub fn new() -> Self {
+        BuildStat {
+            config: BuildStatConfig::default(),
+        }
+    }
+
+    /// The configured axes for the font which will be written to `output`, if any
+    fn axes_for(&self, output: &OperationOutput) -> Result<Option<&[StatAxis]>, ApplicationError> {
+        match &self.config.stat {
+            None => Ok(None),
+            Some(StatSpec::AllFonts(axes)) => Ok(Some(axes)),
+            Some(StatSpec::PerFont(fonts)) => {
+                // Fonts are picked out by the name of the file we write
+                if !output.is_named_file() {
+                    return Err(ApplicationError::InvalidRecipe(
+                        "A per-font STAT needs buildStat to be the last step of its target"
+                            .to_string(),
+                    ));
+                }
+                let filename = output.to_filename(None)?;
+                let basename = Path::new(&filename)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or(filename);
+                Ok(fonts.get(&basename).map(Vec::as_slice))
+            }
+        }
+    }
+
+    /// Find an English name with the given string, or add one
+    pub(crate) fn name_id(name: &mut Name, string: &str) -> NameId {
+        let existing = name.name_record.iter().find(|record| {
+            record.platform_id == 3
+                && record.language_id == 0x0409
+                && record.name_id.to_u16() >= 256
+                && record.string.as_str() == string
+        });
+        if let Some(record) = existing {
+            return record.name_id;
+        }
+        let next = name
+            .name_record
+            .iter()
+            .map(|record| record.name_id.to_u16())
+            .max()
+            .unwrap_or(0)
+            .max(255)
+            + 1;
+        let name_id = NameId::new(next);
+        let mut records = std::mem::take(&mut name.name_record)
+            .into_iter()
+            .collect::<Vec<_>>();
+        records.push(NameRecord::new(
+            3,
+            1,
+            0x0409,
+            name_id,
+            string.to_string().into(),
+        ));
+        name.name_record = records.into_iter().collect();
+        name_id
+    }
+
+    /// Build a STAT table from the config, adding the names it needs
+    fn configured_stat(
+        &self,
+        font_bytes: &[u8],
+        axes: &[StatAxis],
+    ) -> Result<Vec<u8>, ApplicationError> {
+        let font = read_fonts::FontRef::new(font_bytes)?;
+        let mut name: Name = font.name()?.to_owned_table();
+
+        let mut design_axes = vec![];
+        let mut axis_values = vec![];
+        for (index, axis) in axes.iter().enumerate() {
+            let tag = Tag::new_checked(axis.tag.as_bytes()).map_err(|e| {
+                ApplicationError::InvalidRecipe(format!("Bad STAT axis tag {}: {e}", axis.tag))
+            })?;
+            let axis_name = Self::name_id(&mut name, &axis.name);
+            design_axes.push(AxisRecord::new(
+                tag,
+                axis_name,
+                axis.ordering.unwrap_or(index as u16),
+            ));
+            for value in &axis.values {
+                let flags = AxisValueTableFlags::from_bits_truncate(value.flags);
+                let value_name = Self::name_id(&mut name, &value.name);
+                let axis_value = match (value.range_min_value, value.range_max_value) {
+                    (Some(min), Some(max)) => AxisValue::format_2(
+                        index as u16,
+                        flags,
+                        value_name,
+                        Fixed::from_f64(value.value),
+                        Fixed::from_f64(min),
+                        Fixed::from_f64(max),
+                    ),
+                    _ => match value.linked_value {
+                        Some(linked) => AxisValue::format_3(
+                            index as u16,
+                            flags,
+                            value_name,
+                            Fixed::from_f64(value.value),
+                            Fixed::from_f64(linked),
+                        ),
+                        None => AxisValue::format_1(
+                            index as u16,
+                            flags,
+                            value_name,
+                            Fixed::from_f64(value.value),
+                        ),
+                    },
+                };
+                axis_values.push(axis_value);
+            }
+        }
+        let elided_fallback = match &self.config.elided_fallback_name {
+            Some(fallback) => Self::name_id(&mut name, fallback),
+            None => NameId::SUBFAMILY_NAME,
+        };
+        let stat = Stat::new(design_axes, axis_values, elided_fallback);
+
+        let mut builder = FontBuilder::new();
+        builder.add_table(&stat)?;
+        builder.add_table(&name)?;
+        builder.copy_missing_tables(font);
+        Ok(builder.build())
+    }
+}
 
 impl Operation for BuildStat {
     fn shortname(&self) -> &str {
@@ -18,6 +225,10 @@ impl Operation for BuildStat {
         vec![DataKind::Bytes]
     }
 
+    fn variadic_inputs(&self) -> bool {
+        true
+    }
+
     fn output_kinds(&self) -> Vec<DataKind> {
         vec![DataKind::Bytes]
     }
@@ -33,6 +244,11 @@ impl Operation for BuildStat {
             .map(|input| input.to_bytes())
             .collect::<Result<Vec<_>, _>>()?;
         for index in 0..inputs.len() {
+            if let Some(axes) = self.axes_for(&outputs[index])? {
+                let with_stat = self.configured_stat(&all_siblings_bytes[index], axes)?;
+                outputs[index].set_contents(with_stat)?;
+                continue;
+            }
             let font = FontRef::new(&all_siblings_bytes[index])
                 .map_err(|e| ApplicationError::Other(format!("Failed to read font: {}", e)))?;
             let others: Vec<FontRef> = all_siblings_bytes
@@ -60,4 +276,23 @@ impl Operation for BuildStat {
     fn description(&self) -> String {
         "Add STAT tables".to_string()
     }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize BuildStat config: {}. Using defaults.",
+                e
+            );
+            BuildStatConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        if self.config == BuildStatConfig::default() {
+            "BuildStat".to_string()
+        } else {
+            format!("BuildStat-{:?}", self.config)
+        }
+    }
 }
@@ -0,0 +1,208 @@
+use std::{
+    collections::HashMap,
+    os::unix::process::ExitStatusExt,
+    path::Path,
+    process::{Command, Output},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_yaml_ng::{Mapping, Value as YamlValue};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildUpstreamConfig {
+    /// The URL of the upstream repository; if not given, we keep the one
+    /// in an existing upstream.yaml or ask git for the `origin` remote
+    #[serde(default)]
+    pub repository_url: Option<String>,
+    /// The branch Google Fonts should pull from; defaults as above
+    #[serde(default)]
+    pub branch: Option<String>,
+}
+
+/// Write (or update) a Google Fonts upstream.yaml mapping the built fonts
+///
+/// The first input is the first font; the fonts it `needs` arrive in the
+/// following slots and are passed through untouched. Each font is listed in
+/// `files` under its path relative to the upstream.yaml, mapped to its bare
+/// filename in the google/fonts family directory. Other keys and non-font
+/// entries in `files` (OFL.txt, DESCRIPTION.en_us.html and so on) of an
+/// existing upstream.yaml are kept.
+#[derive(PartialEq, Debug)]
+pub(crate) struct BuildUpstream {
+    config: BuildUpstreamConfig,
+}
+
+impl BuildUpstream {
+    pub fn new() -> Self {
+        BuildUpstream {
+            config: BuildUpstreamConfig::default(),
+        }
+    }
+
+    fn git(dir: &Path, args: &[&str]) -> Option<String> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            })
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// Turn an SSH remote into the https URL Google Fonts expects
+    fn https_url(remote: &str) -> String {
+        let url = match remote.strip_prefix("git@") {
+            Some(rest) => format!("https://{}", rest.replacen(':', "/", 1)),
+            None => remote.to_string(),
+        };
+        url.strip_suffix(".git").unwrap_or(&url).to_string()
+    }
+
+    fn is_font(path: &str) -> bool {
+        Path::new(path)
+            .extension()
+            .is_some_and(|ext| ["ttf", "otf", "woff", "woff2"].contains(&&*ext.to_string_lossy()))
+    }
+}
+
+impl Operation for BuildUpstream {
+    fn shortname(&self) -> &str {
+        "BuildUpstream"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn variadic_inputs(&self) -> bool {
+        true
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let upstream_path = outputs[0].to_filename(Some(".yaml"))?;
+        let upstream_dir = Path::new(&upstream_path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let mut upstream: Mapping = match std::fs::read_to_string(&upstream_path) {
+            Ok(text) => serde_yaml_ng::from_str(&text).map_err(|e| {
+                ApplicationError::Other(format!("Could not parse {upstream_path}: {e}"))
+            })?,
+            Err(_) => Mapping::new(),
+        };
+
+        let repository_url = self
+            .config
+            .repository_url
+            .clone()
+            .or_else(|| upstream.get("repository_url")?.as_str().map(String::from))
+            .or_else(|| {
+                Self::git(&upstream_dir, &["remote", "get-url", "origin"])
+                    .map(|remote| Self::https_url(&remote))
+            });
+        let branch = self
+            .config
+            .branch
+            .clone()
+            .or_else(|| upstream.get("branch")?.as_str().map(String::from))
+            .or_else(|| Self::git(&upstream_dir, &["rev-parse", "--abbrev-ref", "HEAD"]))
+            .unwrap_or_else(|| "main".to_string());
+        if let Some(url) = repository_url {
+            upstream.insert("repository_url".into(), url.into());
+        }
+        upstream.insert("branch".into(), branch.into());
+
+        // Keep the non-font files, replace the fonts with what we built
+        let mut files: Vec<(String, String)> = upstream
+            .get("files")
+            .and_then(YamlValue::as_mapping)
+            .into_iter()
+            .flatten()
+            .filter_map(|(source, dest)| Some((source.as_str()?, dest.as_str()?)))
+            .filter(|(source, _)| !Self::is_font(source))
+            .map(|(source, dest)| (source.to_string(), dest.to_string()))
+            .collect();
+        for input in inputs {
+            let filename = input.to_filename(None)?;
+            let path = Path::new(&filename);
+            let relative = path
+                .strip_prefix(&upstream_dir)
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_else(|_| filename.clone());
+            let dest = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| filename.clone());
+            files.push((relative, dest));
+        }
+        files.sort();
+        files.dedup();
+        upstream.insert(
+            "files".into(),
+            YamlValue::Mapping(
+                files
+                    .into_iter()
+                    .map(|(source, dest)| (source.into(), dest.into()))
+                    .collect(),
+            ),
+        );
+
+        let text = serde_yaml_ng::to_string(&upstream).map_err(|e| {
+            ApplicationError::Other(format!("Could not serialize upstream.yaml: {e}"))
+        })?;
+        outputs[0].set_contents(text.into_bytes())?;
+
+        self.pass_through_needs(inputs, outputs)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Write upstream.yaml".to_string()
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize BuildUpstream config: {}. Using defaults.",
+                e
+            );
+            BuildUpstreamConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!(
+            "BuildUpstream-{}-{}",
+            self.config.repository_url.as_deref().unwrap_or(""),
+            self.config.branch.as_deref().unwrap_or("")
+        )
+    }
+}
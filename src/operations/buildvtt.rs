@@ -0,0 +1,108 @@
+use std::{collections::HashMap, process::Output};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildVTTConfig {
+    /// The TTX file holding the VTT sources (the `TSI*` tables)
+    pub vtt_source: String,
+    /// Keep the VTT source tables in the output rather than shipping it
+    #[serde(default)]
+    pub keep_sources: bool,
+}
+
+/// Compile VTT hinting sources and merge them into a TTF
+///
+/// This shells out to `gftools build-vtt`, which merges the `TSI*` tables from
+/// a TTX file into the font and compiles them with VTT's compiler. Unless
+/// `keepSources` is set, the source tables are then dropped so the font can
+/// be shipped.
+#[derive(PartialEq, Debug)]
+pub(crate) struct BuildVTT {
+    args: Option<String>,
+    config: BuildVTTConfig,
+}
+
+impl BuildVTT {
+    pub fn new() -> Self {
+        BuildVTT {
+            args: None,
+            config: BuildVTTConfig::default(),
+        }
+    }
+}
+
+impl Operation for BuildVTT {
+    fn shortname(&self) -> &str {
+        "BuildVTT"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        if self.config.vtt_source.is_empty() {
+            return Err(ApplicationError::InvalidRecipe(
+                "buildVTT needs a vttSource".to_string(),
+            ));
+        }
+        let mut cmd = "gftools build-vtt".to_string();
+        if !self.config.keep_sources {
+            cmd.push_str(" --ship");
+        }
+        if let Some(args) = &self.args {
+            cmd.push(' ');
+            cmd.push_str(args);
+        }
+        cmd.push_str(&format!(
+            " {} {} -o {}",
+            inputs[0].to_filename(Some(".ttf"))?,
+            self.config.vtt_source,
+            outputs[0].to_filename(Some(".ttf"))?
+        ));
+        self.run_shell_command(&cmd, outputs)
+    }
+
+    fn description(&self) -> String {
+        format!("Merge VTT hinting from {}", self.config.vtt_source)
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize BuildVTT config: {}. Using defaults.",
+                e
+            );
+            BuildVTTConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!(
+            "BuildVTT-{:?}-{}",
+            self.config,
+            self.args.as_deref().unwrap_or("")
+        )
+    }
+}
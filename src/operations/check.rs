@@ -0,0 +1,156 @@
+use std::{collections::HashMap, os::unix::process::ExitStatusExt, process::Output};
+
+use serde::{Deserialize, Serialize};
+use serde_inline_default::serde_inline_default;
+use serde_json::Value;
+
+use crate::{
+    buildsystem::{
+        DataKind, Operation, OperationOutput, command_path, report_warning, scratch_file,
+    },
+    error::ApplicationError,
+};
+
+#[serde_inline_default]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckConfig {
+    /// The Fontspector profile to check against
+    #[serde_inline_default("googlefonts".to_string())]
+    pub profile: String,
+    /// How many FAILs (and ERRORs) to put up with before failing the target
+    #[serde(default)]
+    pub max_fails: usize,
+    /// Where to write the JSON report; if not given, it is thrown away
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<String>,
+}
+
+impl Default for CheckConfig {
+    fn default() -> Self {
+        // Just deserialize nothing and let serde fill in the defaults
+        serde_json::from_str("{}").unwrap()
+    }
+}
+
+/// Run Fontspector's QA checks on a built font
+///
+/// The font is passed through unchanged, so a check can sit at the end of a
+/// target's steps and stop the build if there are more FAILs than allowed.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Check {
+    args: Option<String>,
+    config: CheckConfig,
+}
+
+impl Check {
+    pub fn new() -> Self {
+        Check {
+            args: None,
+            config: CheckConfig::default(),
+        }
+    }
+
+    /// Count the results with the given status in a Fontspector JSON report
+    fn count(report: &Value, status: &str) -> usize {
+        report
+            .get("summary")
+            .and_then(|summary| summary.get(status))
+            .and_then(Value::as_u64)
+            .unwrap_or(0) as usize
+    }
+}
+
+impl Operation for Check {
+    fn shortname(&self) -> &str {
+        "Check"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let font = inputs[0].to_filename(Some(".ttf"))?;
+        let temp_report = scratch_file(None)?;
+        let report_path = match &self.config.report {
+            Some(report) => command_path(report),
+            None => temp_report.path().to_string_lossy().to_string(),
+        };
+        let mut cmd = format!(
+            "fontspector --profile \"{}\" --json \"{}\"",
+            self.config.profile, report_path
+        );
+        if let Some(args) = &self.args {
+            cmd.push(' ');
+            cmd.push_str(args);
+        }
+        cmd.push_str(&format!(" \"{font}\""));
+        // Fontspector exits unsuccessfully when anything FAILs, so we go by the
+        // report rather than the exit status, unless it couldn't be run at all
+        let output = self.run_shell_command(&cmd, outputs)?;
+        if output.status.code() == Some(127) {
+            return Err(ApplicationError::ToolNotFound("fontspector".to_string()));
+        }
+        let report: Value = std::fs::read_to_string(&report_path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .ok_or_else(|| {
+                ApplicationError::Other(format!(
+                    "fontspector did not write a report: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))
+            })?;
+        let fails = Self::count(&report, "FAIL") + Self::count(&report, "ERROR");
+        if fails > self.config.max_fails {
+            return Err(ApplicationError::ChecksFailed(format!(
+                "{font} has {fails} FAIL(s) against the {} profile, but only {} allowed",
+                self.config.profile, self.config.max_fails
+            )));
+        }
+        if fails > 0 {
+            report_warning(format!(
+                "{font} has {fails} FAIL(s) against the {} profile",
+                self.config.profile
+            ));
+        }
+        outputs[0].set_path(&font)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+
+    fn description(&self) -> String {
+        format!("Check against {} profile", self.config.profile)
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!("Failed to deserialize Check config: {}. Using defaults.", e);
+            CheckConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!(
+            "Check-{:?}-{}",
+            self.config,
+            self.args.as_deref().unwrap_or("")
+        )
+    }
+}
@@ -0,0 +1,79 @@
+use std::process::Output;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// Check that a source's masters are interpolation-compatible
+///
+/// This shells out to `fonttools varLib.interpolatable`, which understands
+/// Glyphs, designspace and UFO sources. The source itself is passed through
+/// unchanged, so the check can sit in front of the compiler and stop the
+/// build with a readable report rather than a compiler error.
+#[derive(PartialEq, Debug)]
+pub(crate) struct CheckCompatibility {
+    args: Option<String>,
+}
+
+impl CheckCompatibility {
+    pub fn new() -> Self {
+        CheckCompatibility { args: None }
+    }
+}
+
+impl Operation for CheckCompatibility {
+    fn shortname(&self) -> &str {
+        "CheckCompatibility"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let source = inputs[0].to_filename(None)?;
+        let mut cmd = "fonttools varLib.interpolatable".to_string();
+        if let Some(args) = &self.args {
+            cmd.push(' ');
+            cmd.push_str(args);
+        }
+        cmd.push(' ');
+        cmd.push_str(&source);
+        let output = self.run_shell_command(&cmd, outputs)?;
+        if !output.status.success() {
+            let mut report = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if report.is_empty() {
+                report = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            }
+            return Err(ApplicationError::IncompatibleMasters(format!(
+                "{source}:\n{report}"
+            )));
+        }
+        // Pass the source through untouched. Designspaces refer to their UFOs
+        // by relative path, so we point at the original file rather than
+        // copying it somewhere else.
+        outputs[0].set_path(&source)?;
+        Ok(output)
+    }
+
+    fn description(&self) -> String {
+        "Check masters are compatible".to_string()
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn identifier(&self) -> String {
+        format!("CheckCompatibility-{}", self.args.as_deref().unwrap_or(""))
+    }
+}
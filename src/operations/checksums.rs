@@ -0,0 +1,158 @@
+use std::{collections::HashMap, os::unix::process::ExitStatusExt, path::Path, process::Output};
+
+use read_fonts::{FontRef, TableProvider};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// How to write the checksums of the built files
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumFormat {
+    /// A `sha256sum`-compatible list of hashes and filenames
+    #[default]
+    Sha256sums,
+    /// A JSON list with each file's size, hash and font version
+    Json,
+}
+
+impl ChecksumFormat {
+    /// The conventional name of the manifest file
+    pub fn filename(&self) -> &'static str {
+        match self {
+            ChecksumFormat::Sha256sums => "SHA256SUMS",
+            ChecksumFormat::Json => "manifest.json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChecksumsConfig {
+    #[serde(default)]
+    pub format: ChecksumFormat,
+}
+
+/// Write a manifest of checksums for a set of built files
+///
+/// The first input is the first file, and the others arrive through
+/// `needs:` and are passed through untouched. Paths in the manifest are
+/// relative to the manifest's own directory where possible, so that
+/// `sha256sum -c` can be run from there.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Checksums {
+    config: ChecksumsConfig,
+}
+
+impl Checksums {
+    pub fn new() -> Self {
+        Checksums {
+            config: ChecksumsConfig::default(),
+        }
+    }
+}
+
+impl Operation for Checksums {
+    fn shortname(&self) -> &str {
+        "Checksums"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn variadic_inputs(&self) -> bool {
+        true
+    }
+
+    fn reads_finished_targets(&self) -> bool {
+        true
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let manifest_path = outputs[0].to_filename(None)?;
+        let manifest_dir = Path::new(&manifest_path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let mut entries = vec![];
+        for input in inputs {
+            let filename = input.to_filename(None)?;
+            let relative = Path::new(&filename)
+                .strip_prefix(&manifest_dir)
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_else(|_| filename.clone());
+            let bytes = input.to_mapped_bytes()?;
+            let version = FontRef::new(&bytes)
+                .and_then(|font| font.head())
+                .map(|head| format!("{:.3}", head.font_revision().to_f64()))
+                .ok();
+            entries.push((
+                relative,
+                bytes.len(),
+                format!("{:x}", Sha256::digest(&bytes)),
+                version,
+            ));
+        }
+        entries.sort();
+
+        let manifest = match self.config.format {
+            ChecksumFormat::Sha256sums => entries
+                .iter()
+                .map(|(path, _, hash, _)| format!("{hash}  {path}\n"))
+                .collect::<String>(),
+            ChecksumFormat::Json => {
+                let files = entries
+                    .iter()
+                    .map(|(path, size, hash, version)| {
+                        json!({"path": path, "size": size, "sha256": hash, "version": version})
+                    })
+                    .collect::<Vec<_>>();
+                serde_json::to_string_pretty(&files).map_err(|e| {
+                    ApplicationError::Other(format!("Could not serialize manifest: {e}"))
+                })? + "\n"
+            }
+        };
+        outputs[0].set_contents(manifest.into_bytes())?;
+
+        self.pass_through_needs(inputs, outputs)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        format!("Write {}", self.config.format.filename())
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize Checksums config: {}. Using defaults.",
+                e
+            );
+            ChecksumsConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("Checksums-{:?}", self.config.format)
+    }
+}
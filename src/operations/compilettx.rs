@@ -0,0 +1,67 @@
+use std::process::Output;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// Compile a TTX (XML) file into a binary font
+///
+/// This shells out to fontTools' `ttx`. Any arguments are passed before the
+/// file names, so `-m base.ttf` merges the TTX's tables into an existing font
+/// instead of building one from scratch; that way a recipe can patch a few
+/// tables with a hand-edited TTX in the middle of a pipeline.
+#[derive(PartialEq, Debug)]
+pub(crate) struct CompileTTX {
+    args: Option<String>,
+}
+
+impl CompileTTX {
+    pub fn new() -> Self {
+        CompileTTX { args: None }
+    }
+}
+
+impl Operation for CompileTTX {
+    fn shortname(&self) -> &str {
+        "CompileTTX"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let mut cmd = "ttx -q".to_string();
+        if let Some(args) = &self.args {
+            cmd.push(' ');
+            cmd.push_str(args);
+        }
+        cmd.push_str(&format!(
+            " -o {} {}",
+            outputs[0].to_filename(Some(".ttf"))?,
+            inputs[0].to_filename(Some(".ttx"))?
+        ));
+        self.run_shell_command(&cmd, outputs)
+    }
+
+    fn description(&self) -> String {
+        "Compile TTX".to_string()
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn identifier(&self) -> String {
+        format!("CompileTTX-{}", self.args.as_deref().unwrap_or(""))
+    }
+}
@@ -1,18 +1,122 @@
 use std::{
+    collections::HashMap,
+    io::Write,
     os::unix::process::ExitStatusExt,
     process::{ExitStatus, Output},
 };
 
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tracing::info_span;
 use ttf2woff2::{BrotliQuality, encode};
 
 use crate::{
-    buildsystem::{DataKind, Operation, OperationOutput},
+    buildsystem::{DataKind, Operation, OperationOutput, scratch_file},
     error::ApplicationError,
 };
 
+fn default_transform() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressConfig {
+    /// Brotli quality from 0 (fastest) to 11 (smallest, the default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+    /// Apply the WOFF2 glyf/loca transform; turning this off gives bigger
+    /// files which some broken decoders handle better
+    #[serde(default = "default_transform")]
+    pub transform: bool,
+    /// An XML file to store as the WOFF2 extended metadata block
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<String>,
+}
+
+impl Default for CompressConfig {
+    fn default() -> Self {
+        Self {
+            quality: None,
+            transform: true,
+            metadata: None,
+        }
+    }
+}
+
+/// Offsets of the metadata fields in the WOFF2 header
+const WOFF2_LENGTH_OFFSET: usize = 8;
+const WOFF2_META_OFFSET: usize = 28;
+
 #[derive(PartialEq, Debug)]
-pub(crate) struct Compress;
+pub(crate) struct Compress {
+    config: CompressConfig,
+}
+
+impl Compress {
+    pub fn new() -> Self {
+        Compress {
+            config: CompressConfig::default(),
+        }
+    }
+
+    fn quality(&self) -> Result<BrotliQuality, ApplicationError> {
+        match self.config.quality {
+            None => Ok(BrotliQuality::default()),
+            Some(quality) => BrotliQuality::new(quality).ok_or_else(|| {
+                ApplicationError::InvalidRecipe(format!(
+                    "WOFF2 quality must be between 0 and 11, not {quality}"
+                ))
+            }),
+        }
+    }
+
+    /// Compress without the glyf/loca transform, which ttf2woff2 always applies
+    fn encode_untransformed(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Vec<u8>, ApplicationError> {
+        if self.config.quality.is_some() {
+            log::warn!("WOFF2 quality is ignored when the glyf transform is turned off");
+        }
+        let temp = scratch_file(None)?;
+        let cmd = format!(
+            "fonttools ttLib.woff2 compress --no-glyf-transform -o {} {}",
+            temp.path().display(),
+            inputs[0].to_filename(Some(".ttf"))?
+        );
+        let output = self.run_shell_command(&cmd, outputs)?;
+        if !output.status.success() {
+            return Err(ApplicationError::CompressionError(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(std::fs::read(temp.path())?)
+    }
+
+    /// Append a Brotli-compressed extended metadata block to a WOFF2 file
+    fn add_metadata(mut woff2: Vec<u8>, xml: &[u8]) -> Result<Vec<u8>, ApplicationError> {
+        let mut compressed = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 11, 22);
+            writer.write_all(xml)?;
+        }
+        // The metadata block starts on a four-byte boundary after the font data
+        woff2.resize((woff2.len() + 3) & !3, 0);
+        let meta_offset = woff2.len() as u32;
+        woff2.extend_from_slice(&compressed);
+        let length = woff2.len() as u32;
+
+        woff2[WOFF2_LENGTH_OFFSET..WOFF2_LENGTH_OFFSET + 4].copy_from_slice(&length.to_be_bytes());
+        let fields = [meta_offset, compressed.len() as u32, xml.len() as u32];
+        for (index, field) in fields.iter().enumerate() {
+            let start = WOFF2_META_OFFSET + index * 4;
+            woff2[start..start + 4].copy_from_slice(&field.to_be_bytes());
+        }
+        Ok(woff2)
+    }
+}
 
 impl Operation for Compress {
     fn shortname(&self) -> &str {
@@ -36,9 +140,21 @@ impl Operation for Compress {
         let input_file = inputs
             .first()
             .ok_or_else(|| ApplicationError::WrongInputs("No input file provided".to_string()))?;
-        let ttf_data = input_file.to_bytes()?;
 
-        let compressed = encode(&ttf_data, BrotliQuality::default())?;
+        let mut compressed = if self.config.transform {
+            let ttf_data = input_file.to_mapped_bytes()?;
+            encode(&ttf_data, self.quality()?)?
+        } else {
+            self.encode_untransformed(inputs, outputs)?
+        };
+        if let Some(metadata) = &self.config.metadata {
+            let xml = std::fs::read(metadata).map_err(|e| {
+                ApplicationError::InvalidRecipe(format!(
+                    "Could not read WOFF2 metadata {metadata}: {e}"
+                ))
+            })?;
+            compressed = Self::add_metadata(compressed, &xml)?;
+        }
         outputs[0].set_contents(compressed)?;
         Ok(Output {
             status: ExitStatus::from_raw(0),
@@ -50,4 +166,23 @@ impl Operation for Compress {
     fn description(&self) -> String {
         "Convert to woff2".to_string()
     }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize Compress config: {}. Using defaults.",
+                e
+            );
+            CompressConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        if self.config == CompressConfig::default() {
+            "Compress".to_string()
+        } else {
+            format!("Compress-{:?}", self.config)
+        }
+    }
 }
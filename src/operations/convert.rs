@@ -4,12 +4,31 @@ use std::{
 };
 
 use async_trait::async_trait;
+use read_fonts::FontRef;
 
 use crate::{
     buildsystem::{DataKind, Operation, OperationOutput},
     error::ApplicationError,
 };
 
+/// Look up a converter by its identifier, as found in a serialized graph
+pub fn converter_named(identifier: &str) -> Option<Box<dyn Operation>> {
+    if let Some(suffix) = identifier.strip_prefix("LoadSourceBytes-") {
+        return Some(Box::new(BytesToSourceFont {
+            suffix: suffix.to_string(),
+        }));
+    }
+    Some(match identifier {
+        "ToBytes" => Box::new(FileToBytes),
+        "ToTempFile" => Box::new(BytesToTempFile),
+        "LoadSource" => Box::new(PathToSourceFont),
+        "SaveSource" => Box::new(SourceFontToBytes),
+        "ParseFont" => Box::new(BytesToBinaryFont),
+        "SerializeFont" => Box::new(BinaryFontToBytes),
+        _ => return None,
+    })
+}
+
 #[derive(PartialEq, Debug)]
 pub struct FileToBytes;
 
@@ -137,3 +156,183 @@ impl Operation for PathToSourceFont {
         true
     }
 }
+
+/// Load a font source from bytes
+///
+/// babelfont goes by the file extension, so we need to know what format the
+/// bytes are in: that of the source file they were read from, or Glyphs if a
+/// source font was serialized to get them.
+#[derive(PartialEq, Debug)]
+pub struct BytesToSourceFont {
+    /// The extension of the source format, with its dot
+    pub suffix: String,
+}
+
+#[async_trait]
+impl Operation for BytesToSourceFont {
+    fn shortname(&self) -> &str {
+        "LoadSourceBytes"
+    }
+    fn description(&self) -> String {
+        "Load font source from bytes".to_string()
+    }
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::SourceFont]
+    }
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let input = inputs
+            .first()
+            .ok_or_else(|| ApplicationError::WrongInputs("No input".into()))?;
+        let filename = input.to_filename(Some(&self.suffix))?;
+        let font = babelfont::load(&filename).map_err(|e| {
+            ApplicationError::Other(format!("Failed to load font from bytes: {}", e))
+        })?;
+        outputs
+            .first()
+            .ok_or_else(|| ApplicationError::WrongOutputs("Missing output slot 0".into()))?
+            .set_font_source(Box::new(font))?;
+        Ok(Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+    fn identifier(&self) -> String {
+        format!("LoadSourceBytes-{}", self.suffix)
+    }
+    fn hidden(&self) -> bool {
+        true
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct SourceFontToBytes;
+
+#[async_trait]
+impl Operation for SourceFontToBytes {
+    fn shortname(&self) -> &str {
+        "SaveSource"
+    }
+    fn description(&self) -> String {
+        "Serialize font source to bytes".to_string()
+    }
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::SourceFont]
+    }
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let input = inputs
+            .first()
+            .ok_or_else(|| ApplicationError::WrongInputs("No input".into()))?;
+        let bytes = input.to_bytes()?;
+        outputs
+            .first()
+            .ok_or_else(|| ApplicationError::WrongOutputs("Missing output slot 0".into()))?
+            .set_bytes(bytes)?;
+        Ok(Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+    fn hidden(&self) -> bool {
+        true
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct BytesToBinaryFont;
+
+#[async_trait]
+impl Operation for BytesToBinaryFont {
+    fn shortname(&self) -> &str {
+        "ParseFont"
+    }
+    fn description(&self) -> String {
+        "Parse bytes as a binary font".to_string()
+    }
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::BinaryFont]
+    }
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let input = inputs
+            .first()
+            .ok_or_else(|| ApplicationError::WrongInputs("No input".into()))?;
+        let bytes = input.to_bytes()?;
+        // Fail here, rather than in whatever wanted a font, if it isn't one
+        FontRef::new(&bytes)?;
+        outputs
+            .first()
+            .ok_or_else(|| ApplicationError::WrongOutputs("Missing output slot 0".into()))?
+            .set_binary_font(bytes)?;
+        Ok(Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+    fn hidden(&self) -> bool {
+        true
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct BinaryFontToBytes;
+
+#[async_trait]
+impl Operation for BinaryFontToBytes {
+    fn shortname(&self) -> &str {
+        "SerializeFont"
+    }
+    fn description(&self) -> String {
+        "Serialize binary font to bytes".to_string()
+    }
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::BinaryFont]
+    }
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let input = inputs
+            .first()
+            .ok_or_else(|| ApplicationError::WrongInputs("No input".into()))?;
+        let bytes = input.to_bytes()?;
+        outputs
+            .first()
+            .ok_or_else(|| ApplicationError::WrongOutputs("Missing output slot 0".into()))?
+            .set_bytes(bytes)?;
+        Ok(Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+    fn hidden(&self) -> bool {
+        true
+    }
+}
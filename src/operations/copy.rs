@@ -0,0 +1,44 @@
+use std::{os::unix::process::ExitStatusExt, process::Output};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// Copy the input, unchanged, to the target
+///
+/// Use this to install a built file into a second location (a website's
+/// assets folder, say) by giving the original target as the `source:`.
+#[derive(PartialEq, Debug)]
+pub(crate) struct CopyFile;
+
+impl Operation for CopyFile {
+    fn shortname(&self) -> &str {
+        "Copy"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        outputs[0].set_contents(inputs[0].to_bytes()?)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Copy".to_string()
+    }
+}
@@ -0,0 +1,630 @@
+use std::{collections::HashMap, os::unix::process::ExitStatusExt, process::Output};
+
+use read_fonts::{
+    FontRef, TableProvider,
+    tables::glyf::{Anchor, CompositeGlyphFlags, Glyph, Transform},
+    types::GlyphId,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use write_fonts::{
+    FontBuilder,
+    from_obj::ToOwnedTable,
+    tables::{
+        glyf::{
+            Bbox, Contour, CurvePoint, GlyfLocaBuilder, Glyph as OwnedGlyph, LocaFormat,
+            SimpleGlyph,
+        },
+        head::Head,
+        hhea::Hhea,
+        hmtx::Hmtx,
+        maxp::Maxp,
+    },
+    types::FWord,
+};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DecomposeConfig {
+    /// Only decompose glyphs which use a scaled, flipped or rotated component
+    #[serde(default)]
+    pub only_transformed: bool,
+}
+
+/// Decompose components in the `glyf` table of a static TTF
+///
+/// TrueType glyphs can't mix contours and components, so a glyph with any
+/// component to decompose is decomposed completely. Its hinting instructions
+/// are dropped, as they no longer refer to the right points.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Decompose {
+    config: DecomposeConfig,
+}
+
+/// A contour as a list of (x, y, on-curve) points
+pub(crate) type Points = Vec<(f64, f64, bool)>;
+
+fn is_identity(transform: &Transform) -> bool {
+    transform.xx.to_f32() == 1.0
+        && transform.yy.to_f32() == 1.0
+        && transform.xy.to_f32() == 0.0
+        && transform.yx.to_f32() == 0.0
+}
+
+/// Read a glyph and fully resolve its components into contours
+pub(crate) fn outline(
+    font: &FontRef,
+    gid: GlyphId,
+    depth: usize,
+) -> Result<Vec<Points>, ApplicationError> {
+    if depth > 64 {
+        return Err(ApplicationError::Other(format!(
+            "Components of glyph {gid} are nested too deeply"
+        )));
+    }
+    let glyf = font.glyf()?;
+    let loca = font.loca(None)?;
+    match loca.get_glyf(gid, &glyf)? {
+        None => Ok(vec![]),
+        Some(Glyph::Simple(simple)) => {
+            let points = simple.points().collect::<Vec<_>>();
+            let mut contours = vec![];
+            let mut start = 0;
+            for end in simple.end_pts_of_contours() {
+                let end = end.get() as usize;
+                contours.push(
+                    points[start..=end]
+                        .iter()
+                        .map(|p| (p.x as f64, p.y as f64, p.on_curve))
+                        .collect(),
+                );
+                start = end + 1;
+            }
+            Ok(contours)
+        }
+        Some(Glyph::Composite(composite)) => {
+            let mut contours: Vec<Points> = vec![];
+            for component in composite.components() {
+                let t = &component.transform;
+                let (xx, xy, yx, yy) = (
+                    t.xx.to_f32() as f64,
+                    t.xy.to_f32() as f64,
+                    t.yx.to_f32() as f64,
+                    t.yy.to_f32() as f64,
+                );
+                let child = outline(font, component.glyph.into(), depth + 1)?
+                    .into_iter()
+                    .map(|contour| {
+                        contour
+                            .into_iter()
+                            .map(|(x, y, on)| (xx * x + xy * y, yx * x + yy * y, on))
+                            .collect::<Points>()
+                    })
+                    .collect::<Vec<_>>();
+                let (dx, dy) = match component.anchor {
+                    // Apple-style offsets are in the component's transformed space
+                    Anchor::Offset { x, y }
+                        if component
+                            .flags
+                            .contains(CompositeGlyphFlags::SCALED_COMPONENT_OFFSET)
+                            && !component
+                                .flags
+                                .contains(CompositeGlyphFlags::UNSCALED_COMPONENT_OFFSET) =>
+                    {
+                        let (x, y) = (x as f64, y as f64);
+                        (xx * x + xy * y, yx * x + yy * y)
+                    }
+                    Anchor::Offset { x, y } => (x as f64, y as f64),
+                    Anchor::Point { base, component } => {
+                        let point = |contours: &[Points], index: u16| {
+                            contours
+                                .iter()
+                                .flatten()
+                                .nth(index as usize)
+                                .map(|(x, y, _)| (*x, *y))
+                                .ok_or_else(|| {
+                                    ApplicationError::Other(format!(
+                                        "Glyph {gid} anchors a component to missing point {index}"
+                                    ))
+                                })
+                        };
+                        let (bx, by) = point(&contours, base)?;
+                        let (cx, cy) = point(&child, component)?;
+                        (bx - cx, by - cy)
+                    }
+                };
+                contours.extend(child.into_iter().map(|contour| {
+                    contour
+                        .into_iter()
+                        .map(|(x, y, on)| (x + dx, y + dy, on))
+                        .collect()
+                }));
+            }
+            Ok(contours)
+        }
+    }
+}
+
+/// Turn resolved contours back into a simple glyph
+pub(crate) fn simple_glyph(contours: Vec<Points>) -> SimpleGlyph {
+    let contours: Vec<Contour> = contours
+        .into_iter()
+        .map(|contour| {
+            contour
+                .into_iter()
+                .map(|(x, y, on)| CurvePoint::new(x.round() as i16, y.round() as i16, on))
+                .collect::<Vec<_>>()
+                .into()
+        })
+        .collect();
+    let points = contours.iter().flat_map(|c| c.iter());
+    let bbox = points.fold(None::<Bbox>, |bbox, p| {
+        Some(match bbox {
+            None => Bbox {
+                x_min: p.x,
+                y_min: p.y,
+                x_max: p.x,
+                y_max: p.y,
+            },
+            Some(b) => Bbox {
+                x_min: b.x_min.min(p.x),
+                y_min: b.y_min.min(p.y),
+                x_max: b.x_max.max(p.x),
+                y_max: b.y_max.max(p.y),
+            },
+        })
+    });
+    SimpleGlyph {
+        bbox: bbox.unwrap_or_default(),
+        contours,
+        instructions: vec![],
+    }
+}
+
+/// Rebuild the `glyf` and `loca` tables of a static font, replacing the glyphs
+/// for which `replace` returns a new one
+///
+/// `head` and the glyph limits in `maxp` are updated to match.
+pub(crate) fn rewrite_glyphs(
+    font: &FontRef,
+    replace: impl FnMut(GlyphId, &Glyph) -> Result<Option<OwnedGlyph>, ApplicationError>,
+) -> Result<Vec<u8>, ApplicationError> {
+    if font.gvar().is_ok() {
+        return Err(ApplicationError::InvalidRecipe(
+            "Components can only be rewritten in static fonts".to_string(),
+        ));
+    }
+    rebuild_glyf(font, replace)
+}
+
+/// Rebuild `glyf` and `loca` as [`rewrite_glyphs`] does, but in any font
+///
+/// The font's bounding box in `head`, the glyph limits in `maxp`, the left
+/// side bearings in `hmtx` and the extents in `hhea` are recalculated from
+/// the new glyphs. The
+/// replacement glyphs must keep the same points, or the font's `gvar`
+/// deltas will no longer match them.
+pub(crate) fn rebuild_glyf(
+    font: &FontRef,
+    mut replace: impl FnMut(GlyphId, &Glyph) -> Result<Option<OwnedGlyph>, ApplicationError>,
+) -> Result<Vec<u8>, ApplicationError> {
+    let glyf = font.glyf()?;
+    let loca = font.loca(None)?;
+    let mut maxp: Maxp = font.maxp()?.to_owned_table();
+    let mut builder = GlyfLocaBuilder::new();
+    let mut bboxes = vec![];
+    let mut glyphs = vec![];
+    for gid in 0..maxp.num_glyphs {
+        let gid = GlyphId::new(gid as u32);
+        let glyph = match loca.get_glyf(gid, &glyf)? {
+            None => OwnedGlyph::Empty,
+            Some(glyph) => match replace(gid, &glyph)? {
+                Some(new_glyph) => new_glyph,
+                None => glyph.to_owned_table(),
+            },
+        };
+        match &glyph {
+            OwnedGlyph::Simple(simple) if !simple.contours.is_empty() => {
+                bboxes.push((gid.to_u32() as usize, simple.bbox));
+            }
+            OwnedGlyph::Composite(composite) => {
+                bboxes.push((gid.to_u32() as usize, composite.bbox));
+            }
+            _ => {}
+        }
+        builder
+            .add_glyph(&glyph)
+            .map_err(|e| ApplicationError::Other(format!("Could not add glyph {gid}: {e}")))?;
+        glyphs.push(glyph);
+    }
+    update_maxp(&mut maxp, &glyphs);
+    let (glyf, loca, loca_format) = builder.build();
+    let mut head: Head = font.head()?.to_owned_table();
+    head.index_to_loc_format = match loca_format {
+        LocaFormat::Short => 0,
+        LocaFormat::Long => 1,
+    };
+    if !bboxes.is_empty() {
+        head.x_min = bboxes.iter().map(|(_, bbox)| bbox.x_min).min().unwrap_or(0);
+        head.y_min = bboxes.iter().map(|(_, bbox)| bbox.y_min).min().unwrap_or(0);
+        head.x_max = bboxes.iter().map(|(_, bbox)| bbox.x_max).max().unwrap_or(0);
+        head.y_max = bboxes.iter().map(|(_, bbox)| bbox.y_max).max().unwrap_or(0);
+    }
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(&glyf)?;
+    font_builder.add_table(&loca)?;
+    font_builder.add_table(&head)?;
+    font_builder.add_table(&maxp)?;
+    if let (Ok(hmtx), Ok(hhea)) = (font.hmtx(), font.hhea()) {
+        let mut hmtx: Hmtx = hmtx.to_owned_table();
+        let mut hhea: Hhea = hhea.to_owned_table();
+        update_horizontal_metrics(&mut hmtx, &mut hhea, &bboxes);
+        font_builder.add_table(&hmtx)?;
+        font_builder.add_table(&hhea)?;
+    }
+    font_builder.copy_missing_tables(font.clone());
+    Ok(font_builder.build())
+}
+
+/// The points and contours of a glyph once its components are resolved, and
+/// how deeply its components nest
+fn resolved_size(glyphs: &[OwnedGlyph], gid: usize, depth: usize) -> (u16, u16, u16) {
+    match glyphs.get(gid) {
+        Some(OwnedGlyph::Simple(simple)) => (
+            simple.contours.iter().map(|c| c.len()).sum::<usize>() as u16,
+            simple.contours.len() as u16,
+            0,
+        ),
+        Some(OwnedGlyph::Composite(composite)) if depth <= 64 => composite
+            .components()
+            .iter()
+            .map(|component| resolved_size(glyphs, component.glyph.to_u32() as usize, depth + 1))
+            .fold((0, 0, 0), |(points, contours, nesting), (p, c, n)| {
+                (
+                    points.saturating_add(p),
+                    contours.saturating_add(c),
+                    nesting.max(n + 1),
+                )
+            }),
+        _ => (0, 0, 0),
+    }
+}
+
+/// Recalculate the glyph limits of a version 1.0 `maxp` from the glyphs
+fn update_maxp(maxp: &mut Maxp, glyphs: &[OwnedGlyph]) {
+    if maxp.max_points.is_none() {
+        // Version 0.5, which only has the number of glyphs
+        return;
+    }
+    let (mut points, mut contours) = (0, 0);
+    let (mut composite_points, mut composite_contours) = (0, 0);
+    let (mut elements, mut depth) = (0, 0);
+    for (gid, glyph) in glyphs.iter().enumerate() {
+        let (p, c, d) = resolved_size(glyphs, gid, 0);
+        match glyph {
+            OwnedGlyph::Simple(_) => {
+                points = points.max(p);
+                contours = contours.max(c);
+            }
+            OwnedGlyph::Composite(composite) => {
+                composite_points = composite_points.max(p);
+                composite_contours = composite_contours.max(c);
+                elements = elements.max(composite.components().len() as u16);
+                depth = depth.max(d);
+            }
+            OwnedGlyph::Empty => {}
+        }
+    }
+    maxp.max_points = Some(points);
+    maxp.max_contours = Some(contours);
+    maxp.max_composite_points = Some(composite_points);
+    maxp.max_composite_contours = Some(composite_contours);
+    maxp.max_component_elements = Some(elements);
+    maxp.max_component_depth = Some(depth);
+}
+
+/// Set each outlined glyph's left side bearing to the left of its bounding
+/// box, and recalculate the extents in `hhea` to match
+fn update_horizontal_metrics(hmtx: &mut Hmtx, hhea: &mut Hhea, bboxes: &[(usize, Bbox)]) {
+    let long_metrics = hmtx.h_metrics.len();
+    let (mut min_lsb, mut min_rsb, mut max_extent) = (i16::MAX, i16::MAX, i16::MIN);
+    for (gid, bbox) in bboxes {
+        if *gid < long_metrics {
+            hmtx.h_metrics[*gid].side_bearing = bbox.x_min;
+        } else if let Some(lsb) = hmtx.left_side_bearings.get_mut(gid - long_metrics) {
+            *lsb = bbox.x_min;
+        }
+        // Glyphs past the long metrics share the last advance
+        let advance = hmtx
+            .h_metrics
+            .get(*gid)
+            .or(hmtx.h_metrics.last())
+            .map(|metric| metric.advance as i32)
+            .unwrap_or(0);
+        min_lsb = min_lsb.min(bbox.x_min);
+        min_rsb = min_rsb
+            .min((advance - bbox.x_max as i32).clamp(i16::MIN as i32, i16::MAX as i32) as i16);
+        max_extent = max_extent.max(bbox.x_max);
+    }
+    if !bboxes.is_empty() {
+        hhea.min_left_side_bearing = FWord::new(min_lsb);
+        hhea.min_right_side_bearing = FWord::new(min_rsb);
+        hhea.x_max_extent = FWord::new(max_extent);
+    }
+}
+
+impl Decompose {
+    pub fn new() -> Self {
+        Decompose {
+            config: DecomposeConfig::default(),
+        }
+    }
+
+    /// Decompose the components of a static TTF, returning the new font
+    fn decompose(&self, bytes: &[u8]) -> Result<Vec<u8>, ApplicationError> {
+        let font = FontRef::new(bytes)?;
+        rewrite_glyphs(&font, |gid, glyph| {
+            let Glyph::Composite(composite) = glyph else {
+                return Ok(None);
+            };
+            if self.config.only_transformed
+                && composite
+                    .components()
+                    .all(|component| is_identity(&component.transform))
+            {
+                return Ok(None);
+            }
+            let contours = outline(&font, gid, 0)?;
+            Ok(Some(OwnedGlyph::Simple(simple_glyph(contours))))
+        })
+    }
+}
+
+impl Operation for Decompose {
+    fn shortname(&self) -> &str {
+        "Decompose"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        outputs[0].set_contents(self.decompose(&bytes)?)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        if self.config.only_transformed {
+            "Decompose transformed components".to_string()
+        } else {
+            "Decompose components".to_string()
+        }
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize Decompose config: {}. Using defaults.",
+                e
+            );
+            DecomposeConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("Decompose-{:?}", self.config)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use read_fonts::types::{F2Dot14, GlyphId16, NameId};
+    use write_fonts::tables::{
+        glyf::{Component, ComponentFlags, CompositeGlyph},
+        hmtx::LongMetric,
+        name::{Name, NameRecord},
+        os2::Os2,
+        post::Post,
+    };
+
+    use super::*;
+
+    /// A minimal static font named "Test Regular" with the given glyphs,
+    /// each 600 units wide
+    pub(crate) fn test_font(glyphs: &[OwnedGlyph]) -> Vec<u8> {
+        let mut glyf_builder = GlyfLocaBuilder::new();
+        for glyph in glyphs {
+            glyf_builder.add_glyph(glyph).unwrap();
+        }
+        let (glyf, loca, loca_format) = glyf_builder.build();
+        let head = Head {
+            units_per_em: 1000,
+            index_to_loc_format: match loca_format {
+                LocaFormat::Short => 0,
+                LocaFormat::Long => 1,
+            },
+            ..Default::default()
+        };
+        let maxp = Maxp {
+            num_glyphs: glyphs.len() as u16,
+            max_points: Some(0),
+            max_contours: Some(0),
+            max_composite_points: Some(0),
+            max_composite_contours: Some(0),
+            max_zones: Some(1),
+            max_twilight_points: Some(0),
+            max_storage: Some(0),
+            max_function_defs: Some(0),
+            max_instruction_defs: Some(0),
+            max_stack_elements: Some(0),
+            max_size_of_instructions: Some(0),
+            max_component_elements: Some(0),
+            max_component_depth: Some(0),
+        };
+        let hhea = Hhea {
+            number_of_h_metrics: glyphs.len() as u16,
+            ..Default::default()
+        };
+        let hmtx = Hmtx::new(
+            glyphs.iter().map(|_| LongMetric::new(600, 0)).collect(),
+            vec![],
+        );
+        let name = Name::new(
+            [
+                (NameId::FAMILY_NAME, "Test"),
+                (NameId::SUBFAMILY_NAME, "Regular"),
+                (NameId::UNIQUE_ID, "1.000;NONE;Test-Regular"),
+                (NameId::FULL_NAME, "Test Regular"),
+                (NameId::POSTSCRIPT_NAME, "Test-Regular"),
+            ]
+            .into_iter()
+            .map(|(id, string)| NameRecord::new(3, 1, 0x409, id, string.to_string().into()))
+            .collect(),
+        );
+        let mut builder = FontBuilder::new();
+        builder.add_table(&glyf).unwrap();
+        builder.add_table(&loca).unwrap();
+        builder.add_table(&head).unwrap();
+        builder.add_table(&maxp).unwrap();
+        builder.add_table(&hhea).unwrap();
+        builder.add_table(&hmtx).unwrap();
+        builder.add_table(&Post::default()).unwrap();
+        builder.add_table(&Os2::default()).unwrap();
+        builder.add_table(&name).unwrap();
+        builder.build()
+    }
+
+    /// A closed rectangle as resolved contours
+    pub(crate) fn rectangle(x_min: f64, y_min: f64, x_max: f64, y_max: f64) -> Vec<Points> {
+        vec![vec![
+            (x_min, y_min, true),
+            (x_min, y_max, true),
+            (x_max, y_max, true),
+            (x_max, y_min, true),
+        ]]
+    }
+
+    /// A component of `gid` at the given offset, scaled uniformly
+    pub(crate) fn component(
+        gid: u16,
+        (x, y): (i16, i16),
+        scale: f32,
+        flags: ComponentFlags,
+    ) -> Component {
+        Component::new(
+            GlyphId16::new(gid),
+            Anchor::Offset { x, y },
+            Transform {
+                xx: F2Dot14::from_f32(scale),
+                yx: F2Dot14::from_f32(0.0),
+                xy: F2Dot14::from_f32(0.0),
+                yy: F2Dot14::from_f32(scale),
+            },
+            flags,
+        )
+    }
+
+    pub(crate) fn composite(components: Vec<Component>) -> OwnedGlyph {
+        let mut components = components.into_iter();
+        let mut glyph = CompositeGlyph::new(components.next().unwrap(), Bbox::default());
+        for component in components {
+            glyph.add_component(component, Bbox::default());
+        }
+        glyph.into()
+    }
+
+    fn square() -> OwnedGlyph {
+        OwnedGlyph::Simple(simple_glyph(rectangle(0.0, 0.0, 100.0, 100.0)))
+    }
+
+    #[test]
+    fn test_decompose_scaled_offsets() {
+        let scaled = ComponentFlags {
+            scaled_component_offset: true,
+            ..Default::default()
+        };
+        let bytes = test_font(&[
+            OwnedGlyph::Empty,
+            square(),
+            composite(vec![component(1, (50, 0), 1.5, scaled)]),
+            composite(vec![component(1, (50, 0), 1.5, ComponentFlags::default())]),
+        ]);
+        let decomposed = Decompose::new().decompose(&bytes).unwrap();
+        let font = FontRef::new(&decomposed).unwrap();
+        let glyf = font.glyf().unwrap();
+        let loca = font.loca(None).unwrap();
+        let x_range = |gid: u32| match loca.get_glyf(GlyphId::new(gid), &glyf).unwrap() {
+            Some(Glyph::Simple(simple)) => (simple.x_min(), simple.x_max()),
+            _ => panic!("glyph {gid} was not decomposed"),
+        };
+        // A scaled offset is scaled along with the outline
+        assert_eq!(x_range(2), (75, 225));
+        assert_eq!(x_range(3), (50, 200));
+    }
+
+    #[test]
+    fn test_decompose_recalculates_maxp() {
+        let plain = ComponentFlags::default;
+        let glyphs = [
+            OwnedGlyph::Empty,
+            square(),
+            composite(vec![
+                component(1, (0, 0), 1.0, plain()),
+                component(1, (200, 0), 1.0, plain()),
+            ]),
+            composite(vec![component(2, (0, 300), 1.0, plain())]),
+            composite(vec![component(1, (0, 0), 1.5, plain())]),
+        ];
+        let bytes = test_font(&glyphs);
+        let limits = |bytes: &[u8]| {
+            let font = FontRef::new(bytes).unwrap();
+            let maxp = font.maxp().unwrap();
+            [
+                maxp.max_points(),
+                maxp.max_contours(),
+                maxp.max_composite_points(),
+                maxp.max_composite_contours(),
+                maxp.max_component_elements(),
+                maxp.max_component_depth(),
+            ]
+            .map(Option::unwrap)
+        };
+
+        let transformed = Decompose {
+            config: DecomposeConfig {
+                only_transformed: true,
+            },
+        };
+        // Only glyph 4 is decomposed; glyph 3 nests glyph 2
+        assert_eq!(
+            limits(&transformed.decompose(&bytes).unwrap()),
+            [4, 1, 8, 2, 2, 2]
+        );
+        // No composites are left, and the limits shrink to match
+        assert_eq!(
+            limits(&Decompose::new().decompose(&bytes).unwrap()),
+            [8, 2, 0, 0, 0, 0]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::{os::unix::process::ExitStatusExt, process::Output};
+
+use read_fonts::{FontRef, TableProvider, tables::glyf::Glyph, types::Tag};
+use write_fonts::{
+    FontBuilder,
+    from_obj::ToOwnedTable,
+    tables::{
+        gasp::{Gasp, GaspRange, GaspRangeBehavior},
+        glyf::{CompositeGlyph, Glyph as OwnedGlyph},
+        maxp::Maxp,
+    },
+};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+    operations::decompose::rebuild_glyf,
+};
+
+/// Tables which only exist to hold or support TrueType hinting
+const HINTING_TABLES: [Tag; 8] = [
+    Tag::new(b"fpgm"),
+    Tag::new(b"prep"),
+    Tag::new(b"cvt "),
+    Tag::new(b"cvar"),
+    Tag::new(b"hdmx"),
+    Tag::new(b"LTSH"),
+    Tag::new(b"VDMX"),
+    Tag::new(b"TTFA"),
+];
+
+/// Remove TrueType hinting from a font
+///
+/// Glyph instructions are stripped and the hinting tables dropped, along with
+/// ttfautohint's `TTFA` table. The `gasp` table is replaced with a single
+/// range asking for smoothing and symmetric gridfitting at every size, which
+/// is what unhinted fonts should have, and the hinting limits in `maxp` are
+/// reset. Works on variable fonts too, since no points are changed.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Dehint;
+
+impl Operation for Dehint {
+    fn shortname(&self) -> &str {
+        "Dehint"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        let stripped = rebuild_glyf(&font, |_gid, glyph| {
+            Ok(match glyph {
+                Glyph::Simple(simple) if !simple.instructions().is_empty() => {
+                    let OwnedGlyph::Simple(mut simple) = glyph.to_owned_table() else {
+                        unreachable!()
+                    };
+                    simple.instructions.clear();
+                    Some(OwnedGlyph::Simple(simple))
+                }
+                Glyph::Composite(composite)
+                    if composite.instructions().is_some_and(|i| !i.is_empty()) =>
+                {
+                    let OwnedGlyph::Composite(owned) = glyph.to_owned_table() else {
+                        unreachable!()
+                    };
+                    // Rebuild from the components alone, leaving the instructions behind
+                    let mut components = owned.components().iter().cloned();
+                    let Some(first) = components.next() else {
+                        return Ok(None);
+                    };
+                    let mut rebuilt = CompositeGlyph::new(first, owned.bbox);
+                    for component in components {
+                        rebuilt.add_component(component, owned.bbox);
+                    }
+                    Some(OwnedGlyph::Composite(rebuilt))
+                }
+                _ => None,
+            })
+        })?;
+
+        let font = FontRef::new(&stripped)?;
+        let mut maxp: Maxp = font.maxp()?.to_owned_table();
+        maxp.max_zones = maxp.max_zones.map(|_| 1);
+        maxp.max_twilight_points = maxp.max_twilight_points.map(|_| 0);
+        maxp.max_storage = maxp.max_storage.map(|_| 0);
+        maxp.max_function_defs = maxp.max_function_defs.map(|_| 0);
+        maxp.max_instruction_defs = maxp.max_instruction_defs.map(|_| 0);
+        maxp.max_stack_elements = maxp.max_stack_elements.map(|_| 0);
+        maxp.max_size_of_instructions = maxp.max_size_of_instructions.map(|_| 0);
+        let gasp = Gasp::new(
+            1,
+            vec![GaspRange::new(
+                0xFFFF,
+                GaspRangeBehavior::GASP_GRIDFIT
+                    | GaspRangeBehavior::GASP_DOGRAY
+                    | GaspRangeBehavior::GASP_SYMMETRIC_GRIDFIT
+                    | GaspRangeBehavior::GASP_SYMMETRIC_SMOOTHING,
+            )],
+        );
+
+        let mut builder = FontBuilder::new();
+        builder.add_table(&maxp)?;
+        builder.add_table(&gasp)?;
+        for record in font.table_directory.table_records() {
+            let tag = record.tag();
+            if HINTING_TABLES.contains(&tag) || tag == Tag::new(b"maxp") || tag == Tag::new(b"gasp")
+            {
+                continue;
+            }
+            if let Some(data) = font.table_data(tag) {
+                builder.add_raw(tag, data);
+            }
+        }
+        outputs[0].set_contents(builder.build())?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Remove hinting".to_string()
+    }
+
+    fn identifier(&self) -> String {
+        "Dehint".to_string()
+    }
+}
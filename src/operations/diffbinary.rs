@@ -0,0 +1,227 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    os::unix::process::ExitStatusExt,
+    process::Output,
+};
+
+use read_fonts::{FontRef, types::Tag};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput, report_warning},
+    error::ApplicationError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffBinaryConfig {
+    /// The reference build to compare against
+    pub reference: String,
+    /// Where to write the JSON report; if not given, only a summary is logged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<String>,
+    /// Fail the target if any table differs from the reference
+    #[serde(default)]
+    pub fail_on_change: bool,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum TableStatus {
+    Same,
+    Changed,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Serialize)]
+struct TableSummary {
+    length: usize,
+    checksum: String,
+}
+
+#[derive(Debug, Serialize)]
+struct TableDiff {
+    status: TableStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reference: Option<TableSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new: Option<TableSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct DiffReport {
+    reference: String,
+    identical: bool,
+    tables: BTreeMap<String, TableDiff>,
+}
+
+/// Compare the tables of a font with those of a reference build
+///
+/// Tables are compared by length and checksum. In `head`, the modification
+/// date and checksum adjustment are ignored, as they change on every build.
+/// The font is passed through unchanged.
+#[derive(PartialEq, Debug)]
+pub(crate) struct DiffBinary {
+    config: DiffBinaryConfig,
+}
+
+impl DiffBinary {
+    pub fn new() -> Self {
+        DiffBinary {
+            config: DiffBinaryConfig::default(),
+        }
+    }
+
+    fn checksum(data: &[u8]) -> u32 {
+        data.chunks(4).fold(0u32, |sum, chunk| {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            sum.wrapping_add(u32::from_be_bytes(word))
+        })
+    }
+
+    /// Each table's length and checksum, keyed by tag
+    fn summarize(font: &FontRef) -> BTreeMap<String, TableSummary> {
+        let head = Tag::new(b"head");
+        font.table_directory
+            .table_records()
+            .iter()
+            .filter_map(|record| {
+                let tag = record.tag();
+                let mut data = font.table_data(tag)?.as_bytes().to_vec();
+                if tag == head && data.len() >= 36 {
+                    // checkSumAdjustment and modified
+                    data[8..12].fill(0);
+                    data[28..36].fill(0);
+                }
+                Some((
+                    tag.to_string(),
+                    TableSummary {
+                        length: data.len(),
+                        checksum: format!("{:08x}", Self::checksum(&data)),
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    fn diff(&self, font: &FontRef, reference: &FontRef) -> DiffReport {
+        let mut reference_tables = Self::summarize(reference);
+        let mut tables = BTreeMap::new();
+        for (tag, new) in Self::summarize(font) {
+            let diff = match reference_tables.remove(&tag) {
+                None => TableDiff {
+                    status: TableStatus::Added,
+                    reference: None,
+                    new: Some(new),
+                },
+                Some(old) => TableDiff {
+                    status: if old.length == new.length && old.checksum == new.checksum {
+                        TableStatus::Same
+                    } else {
+                        TableStatus::Changed
+                    },
+                    reference: Some(old),
+                    new: Some(new),
+                },
+            };
+            tables.insert(tag, diff);
+        }
+        for (tag, old) in reference_tables {
+            tables.insert(
+                tag,
+                TableDiff {
+                    status: TableStatus::Removed,
+                    reference: Some(old),
+                    new: None,
+                },
+            );
+        }
+        DiffReport {
+            reference: self.config.reference.clone(),
+            identical: tables.values().all(|diff| diff.status == TableStatus::Same),
+            tables,
+        }
+    }
+}
+
+impl Operation for DiffBinary {
+    fn shortname(&self) -> &str {
+        "DiffBinary"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        let reference_bytes = std::fs::read(&self.config.reference).map_err(|e| {
+            ApplicationError::InvalidRecipe(format!(
+                "Could not read reference font {}: {e}",
+                self.config.reference
+            ))
+        })?;
+        let reference = FontRef::new(&reference_bytes)?;
+        let report = self.diff(&font, &reference);
+
+        let changed = report
+            .tables
+            .iter()
+            .filter(|(_, diff)| diff.status != TableStatus::Same)
+            .map(|(tag, _)| tag.as_str())
+            .collect::<Vec<_>>();
+        if let Some(path) = &self.config.report {
+            let json = serde_json::to_string_pretty(&report)
+                .map_err(|e| ApplicationError::Other(e.to_string()))?;
+            std::fs::write(path, json)?;
+        }
+        if !changed.is_empty() {
+            let message = format!(
+                "Tables differ from {}: {}",
+                self.config.reference,
+                changed.join(", ")
+            );
+            if self.config.fail_on_change {
+                return Err(ApplicationError::Other(message));
+            }
+            report_warning(message);
+        }
+        outputs[0].set_contents(bytes)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        format!("Compare with {}", self.config.reference)
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize DiffBinary config: {}. Using defaults.",
+                e
+            );
+            DiffBinaryConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("DiffBinary-{:?}", self.config)
+    }
+}
@@ -0,0 +1,127 @@
+use std::{collections::HashMap, process::Output};
+
+use serde::{Deserialize, Serialize};
+use serde_inline_default::serde_inline_default;
+use serde_json::Value;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput, command_path},
+    error::ApplicationError,
+};
+
+#[serde_inline_default]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffenatorConfig {
+    /// The font to compare against, e.g. the previous release; if not given,
+    /// the font arriving from the step's `needs:` is used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    /// The directory to write the HTML report into
+    #[serde_inline_default("diffenator".to_string())]
+    pub output_dir: String,
+}
+
+impl Default for DiffenatorConfig {
+    fn default() -> Self {
+        // Just deserialize nothing and let serde fill in the defaults
+        serde_json::from_str("{}").unwrap()
+    }
+}
+
+/// Compare a font against an earlier version of it with diffenator2
+///
+/// The HTML report covers glyphs, kerning and names. The font itself is passed
+/// through unchanged, as is the "before" font if it came from `needs:`.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Diffenator {
+    args: Option<String>,
+    config: DiffenatorConfig,
+}
+
+impl Diffenator {
+    pub fn new() -> Self {
+        Diffenator {
+            args: None,
+            config: DiffenatorConfig::default(),
+        }
+    }
+}
+
+impl Operation for Diffenator {
+    fn shortname(&self) -> &str {
+        "Diffenator"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn variadic_inputs(&self) -> bool {
+        true
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let after = inputs[0].to_filename(Some(".ttf"))?;
+        let before = match (&self.config.before, inputs.get(1)) {
+            (Some(before), _) => command_path(before),
+            (None, Some(input)) => input.to_filename(Some(".ttf"))?,
+            (None, None) => {
+                return Err(ApplicationError::InvalidRecipe(
+                    "diffenator needs a `before` font to compare against".to_string(),
+                ));
+            }
+        };
+        let mut cmd = format!(
+            "diffenator2 diff --fonts-before \"{}\" --fonts-after \"{}\" --out \"{}\"",
+            before,
+            after,
+            command_path(&self.config.output_dir)
+        );
+        if let Some(args) = &self.args {
+            cmd.push(' ');
+            cmd.push_str(args);
+        }
+        let output = self.run_shell_command(&cmd, outputs)?;
+        if output.status.success() {
+            outputs[0].set_path(&after)?;
+            self.pass_through_needs(inputs, outputs)?;
+        }
+        Ok(output)
+    }
+
+    fn description(&self) -> String {
+        "Compare with earlier version".to_string()
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize Diffenator config: {}. Using defaults.",
+                e
+            );
+            DiffenatorConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!(
+            "Diffenator-{:?}-{}",
+            self.config,
+            self.args.as_deref().unwrap_or("")
+        )
+    }
+}
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{collections::HashMap, os::unix::process::ExitStatusExt};
@@ -10,11 +11,18 @@ use crate::{
 use gftools::fix_font;
 use std::process::Output;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct FixConfig {
     #[serde(default)]
     pub include_source_fixes: bool,
+    /// Extra arguments for `gftools fix-font`; if given, we shell out to it
+    /// rather than using the built-in fixer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix_args: Option<String>,
+    /// Shell out to `gftools fix-font` even without extra arguments
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub use_external: bool,
     // #[serde(default)]
     // pub fvar_instance_axis_dflts: HashMap<String, f32>,
 }
@@ -53,6 +61,24 @@ impl Operation for Fix {
         outputs: &[OperationOutput],
     ) -> Result<Output, ApplicationError> {
         let _span = info_span!("gftools-fix-font").entered();
+        if self.config.fix_args.is_some() || self.config.use_external {
+            let cmd = format!(
+                "gftools fix-font {}{}-o {} {}",
+                if self.config.include_source_fixes {
+                    "--include-source-fixes "
+                } else {
+                    ""
+                },
+                self.config
+                    .fix_args
+                    .as_ref()
+                    .map(|args| format!("{args} "))
+                    .unwrap_or_default(),
+                outputs[0].to_filename(Some(".ttf"))?,
+                inputs[0].to_filename(Some(".ttf"))?
+            );
+            return self.run_shell_command(&cmd, outputs);
+        }
         match fix_font(
             &inputs[0].to_filename(Some(".ttf"))?,
             &outputs[0].to_filename(Some(".ttf"))?,
@@ -0,0 +1,300 @@
+use std::{os::unix::process::ExitStatusExt, process::Output};
+
+use read_fonts::{
+    FontRef, TableProvider,
+    tables::glyf::{Anchor, CompositeGlyphFlags, Glyph, Transform},
+    types::{F2Dot14, GlyphId},
+};
+use write_fonts::tables::glyf::{Bbox, Component, ComponentFlags, CompositeGlyph};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+    operations::decompose::rewrite_glyphs,
+};
+
+/// Flatten nested components in the `glyf` table of a static TTF
+///
+/// A component which refers to another composite glyph is replaced by that
+/// glyph's own components, so every composite refers only to simple glyphs.
+/// Glyphs whose components are positioned by point matching, or whose combined
+/// transforms are too large to store, are left alone with a warning. A glyph
+/// which took its metrics from a nested composite takes them from the
+/// component that composite took its own metrics from.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Flatten {}
+
+/// A component as (glyph, [xx, xy, yx, yy], (dx, dy), flags)
+type Leaf = (GlyphId, [f64; 4], (f64, f64), CompositeGlyphFlags);
+
+fn matrix(transform: &Transform) -> [f64; 4] {
+    [
+        transform.xx.to_f32() as f64,
+        transform.xy.to_f32() as f64,
+        transform.yx.to_f32() as f64,
+        transform.yy.to_f32() as f64,
+    ]
+}
+
+/// The simple glyphs a composite glyph is made of, with their positions
+/// relative to it, or `None` if it can't be flattened
+fn leaves(
+    font: &FontRef,
+    gid: GlyphId,
+    depth: usize,
+) -> Result<Option<Vec<Leaf>>, ApplicationError> {
+    let glyf = font.glyf()?;
+    let loca = font.loca(None)?;
+    let Some(Glyph::Composite(composite)) = loca.get_glyf(gid, &glyf)? else {
+        return Ok(Some(vec![]));
+    };
+    if depth > 64 {
+        return Ok(None);
+    }
+    let mut result = vec![];
+    for component in composite.components() {
+        let Anchor::Offset { x, y } = component.anchor else {
+            return Ok(None);
+        };
+        let [a, b, c, d] = matrix(&component.transform);
+        let (x, y) = (x as f64, y as f64);
+        // Leaves' offsets are always unscaled
+        let mut flags = component.flags;
+        let (x, y) = if flags.contains(CompositeGlyphFlags::SCALED_COMPONENT_OFFSET)
+            && !flags.contains(CompositeGlyphFlags::UNSCALED_COMPONENT_OFFSET)
+        {
+            (a * x + b * y, c * x + d * y)
+        } else {
+            (x, y)
+        };
+        flags.remove(CompositeGlyphFlags::SCALED_COMPONENT_OFFSET);
+        flags.remove(CompositeGlyphFlags::UNSCALED_COMPONENT_OFFSET);
+        let child = component.glyph.into();
+        let is_composite = matches!(loca.get_glyf(child, &glyf)?, Some(Glyph::Composite(_)));
+        if !is_composite {
+            result.push((child, [a, b, c, d], (x, y), flags));
+            continue;
+        }
+        let Some(grandchildren) = leaves(font, child, depth + 1)? else {
+            return Ok(None);
+        };
+        for (leaf, [e, f, g, h], (dx, dy), mut leaf_flags) in grandchildren {
+            // Only the leaf the flagged composite took its metrics from passes them on
+            if !flags.contains(CompositeGlyphFlags::USE_MY_METRICS) {
+                leaf_flags.remove(CompositeGlyphFlags::USE_MY_METRICS);
+            }
+            result.push((
+                leaf,
+                [a * e + b * g, a * f + b * h, c * e + d * g, c * f + d * h],
+                (a * dx + b * dy + x, c * dx + d * dy + y),
+                leaf_flags,
+            ));
+        }
+    }
+    Ok(Some(result))
+}
+
+fn to_component((gid, [xx, xy, yx, yy], (dx, dy), flags): Leaf) -> Option<Component> {
+    let in_range = |v: f64| (-2.0..2.0).contains(&v);
+    let offset = |v: f64| (i16::MIN as f64..=i16::MAX as f64).contains(&v.round());
+    if ![xx, xy, yx, yy].into_iter().all(in_range) || !offset(dx) || !offset(dy) {
+        return None;
+    }
+    Some(Component::new(
+        gid.try_into().ok()?,
+        Anchor::Offset {
+            x: dx.round() as i16,
+            y: dy.round() as i16,
+        },
+        Transform {
+            xx: F2Dot14::from_f32(xx as f32),
+            yx: F2Dot14::from_f32(yx as f32),
+            xy: F2Dot14::from_f32(xy as f32),
+            yy: F2Dot14::from_f32(yy as f32),
+        },
+        ComponentFlags {
+            round_xy_to_grid: flags.contains(CompositeGlyphFlags::ROUND_XY_TO_GRID),
+            use_my_metrics: flags.contains(CompositeGlyphFlags::USE_MY_METRICS),
+            scaled_component_offset: flags.contains(CompositeGlyphFlags::SCALED_COMPONENT_OFFSET),
+            unscaled_component_offset: flags
+                .contains(CompositeGlyphFlags::UNSCALED_COMPONENT_OFFSET),
+            overlap_compound: flags.contains(CompositeGlyphFlags::OVERLAP_COMPOUND),
+        },
+    ))
+}
+
+impl Flatten {
+    pub fn new() -> Self {
+        Flatten {}
+    }
+
+    /// Flatten the components of a static TTF, returning the new font
+    fn flatten(&self, bytes: &[u8]) -> Result<Vec<u8>, ApplicationError> {
+        let font = FontRef::new(bytes)?;
+        let glyf = font.glyf()?;
+        let loca = font.loca(None)?;
+        rewrite_glyphs(&font, |gid, glyph| {
+            let Glyph::Composite(composite) = glyph else {
+                return Ok(None);
+            };
+            let nested = composite.components().any(|component| {
+                matches!(
+                    loca.get_glyf(component.glyph.into(), &glyf),
+                    Ok(Some(Glyph::Composite(_)))
+                )
+            });
+            if !nested {
+                return Ok(None);
+            }
+            let components = leaves(&font, gid, 0)?
+                .unwrap_or_default()
+                .into_iter()
+                .map(to_component)
+                .collect::<Option<Vec<_>>>()
+                .filter(|components| !components.is_empty());
+            let Some(components) = components else {
+                log::warn!("Could not flatten the components of glyph {gid}");
+                return Ok(None);
+            };
+            let bbox = Bbox {
+                x_min: composite.x_min(),
+                y_min: composite.y_min(),
+                x_max: composite.x_max(),
+                y_max: composite.y_max(),
+            };
+            let mut components = components.into_iter();
+            let mut flat = CompositeGlyph::new(components.next().unwrap(), bbox);
+            for component in components {
+                flat.add_component(component, bbox);
+            }
+            Ok(Some(flat.into()))
+        })
+    }
+}
+
+impl Operation for Flatten {
+    fn shortname(&self) -> &str {
+        "Flatten"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        outputs[0].set_contents(self.flatten(&bytes)?)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Flatten components".to_string()
+    }
+
+    fn identifier(&self) -> String {
+        "Flatten".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::tables::glyf::{ComponentFlags, Glyph as OwnedGlyph};
+
+    use super::*;
+    use crate::operations::decompose::{
+        simple_glyph,
+        tests::{component, composite, rectangle, test_font},
+    };
+
+    /// The components of a glyph as (glyph, offset, scale, uses its metrics)
+    fn components(font: &FontRef, gid: u32) -> Vec<(u32, (i16, i16), f32, bool)> {
+        let glyf = font.glyf().unwrap();
+        let loca = font.loca(None).unwrap();
+        let Some(Glyph::Composite(composite)) = loca.get_glyf(GlyphId::new(gid), &glyf).unwrap()
+        else {
+            panic!("glyph {gid} is not a composite");
+        };
+        composite
+            .components()
+            .map(|component| {
+                let Anchor::Offset { x, y } = component.anchor else {
+                    panic!("glyph {gid} has a point-matched component");
+                };
+                (
+                    component.glyph.to_u32(),
+                    (x, y),
+                    component.transform.xx.to_f32(),
+                    component
+                        .flags
+                        .contains(CompositeGlyphFlags::USE_MY_METRICS),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_flatten() {
+        let plain = ComponentFlags::default;
+        let metrics = || ComponentFlags {
+            use_my_metrics: true,
+            ..Default::default()
+        };
+        let scaled = ComponentFlags {
+            scaled_component_offset: true,
+            ..Default::default()
+        };
+        let bytes = test_font(&[
+            OwnedGlyph::Empty,
+            OwnedGlyph::Simple(simple_glyph(rectangle(0.0, 0.0, 100.0, 100.0))),
+            composite(vec![
+                component(1, (0, 0), 1.0, metrics()),
+                component(1, (200, 0), 1.0, plain()),
+            ]),
+            composite(vec![
+                component(2, (10, 20), 1.0, metrics()),
+                component(1, (500, 0), 1.0, plain()),
+            ]),
+            composite(vec![
+                component(2, (0, 0), 1.0, plain()),
+                component(1, (500, 0), 1.0, metrics()),
+            ]),
+            composite(vec![component(2, (100, 0), 1.5, scaled)]),
+        ]);
+        let flattened = Flatten::new().flatten(&bytes).unwrap();
+        let font = FontRef::new(&flattened).unwrap();
+
+        assert_eq!(
+            components(&font, 3),
+            vec![
+                (1, (10, 20), 1.0, true),
+                (1, (210, 20), 1.0, false),
+                (1, (500, 0), 1.0, false),
+            ]
+        );
+        // The nested composite's flagged component doesn't take over
+        assert_eq!(
+            components(&font, 4),
+            vec![
+                (1, (0, 0), 1.0, false),
+                (1, (200, 0), 1.0, false),
+                (1, (500, 0), 1.0, true),
+            ]
+        );
+        assert_eq!(
+            components(&font, 5),
+            vec![(1, (150, 0), 1.5, false), (1, (450, 0), 1.5, false)]
+        );
+    }
+}
@@ -8,20 +8,25 @@ use std::{
 };
 
 use crate::{
-    buildsystem::{DataKind, Operation, OperationOutput},
+    buildsystem::{DataKind, Operation, OperationOutput, SourceKind},
     error::ApplicationError,
 };
 use fontc::{Flags, generate_font};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::info_span;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 #[serde_inline_default]
 #[serde(rename_all = "camelCase")]
 pub struct FontcConfig {
+    /// Flatten nested components while compiling; the `flattenComponents`
+    /// operation does the same to an already-built static font
     #[serde(default)]
     pub flatten_components: bool,
 
+    /// Decompose scaled, flipped or rotated components while compiling; see
+    /// also the `decomposeComponents` operation
     #[serde(default)]
     pub decompose_transformed_components: bool,
 
@@ -94,10 +99,10 @@ impl Operation for Fontc {
         outputs: &[OperationOutput],
     ) -> Result<Output, ApplicationError> {
         let _span = info_span!("fontc").entered();
-        let input_font = inputs
+        let (input_font, _converted) = inputs
             .first()
             .ok_or_else(|| ApplicationError::WrongInputs("No input file provided".to_string()))?
-            .to_filename(Some(".glyphs"))?;
+            .to_source_filename(SourceKind::fontc_reads)?;
 
         let input = fontc::Input::new(&PathBuf::from(input_font))
             .map_err(|e| ApplicationError::Other(e.to_string()))?
@@ -0,0 +1,89 @@
+use std::{collections::HashMap, os::unix::process::ExitStatusExt, process::Output};
+
+use read_fonts::{FontRef, TableProvider};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use write_fonts::{FontBuilder, from_obj::ToOwnedTable, tables::os2::Os2};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FsTypeConfig {
+    /// The embedding bits to set; defaults to 0, installable embedding
+    #[serde(default)]
+    pub fs_type: u16,
+}
+
+/// Set the embedding permissions in OS/2.fsType
+///
+/// Only the fsType field is written; the rest of the font is copied as is.
+#[derive(PartialEq, Debug)]
+pub(crate) struct FsType {
+    config: FsTypeConfig,
+}
+
+impl FsType {
+    pub fn new() -> Self {
+        FsType {
+            config: FsTypeConfig::default(),
+        }
+    }
+}
+
+impl Operation for FsType {
+    fn shortname(&self) -> &str {
+        "FsType"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        let mut os2: Os2 = font.os2()?.to_owned_table();
+        os2.fs_type = self.config.fs_type;
+
+        let mut builder = FontBuilder::new();
+        builder.add_table(&os2)?;
+        builder.copy_missing_tables(font);
+        outputs[0].set_contents(builder.build())?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        format!("Set fsType to {}", self.config.fs_type)
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize FsType config: {}. Using defaults.",
+                e
+            );
+            FsTypeConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("FsType-{}", self.config.fs_type)
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    os::unix::process::ExitStatusExt,
+    process::Output,
+};
+
+use read_fonts::{
+    FontRef, TableProvider,
+    tables::{os2::SelectionFlags, stat::AxisValueTableFlags},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use write_fonts::{
+    FontBuilder,
+    from_obj::ToOwnedTable,
+    tables::{
+        fvar::{Fvar, InstanceRecord},
+        name::Name,
+        stat::{AxisValue, Stat},
+    },
+    types::Fixed,
+};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+    operations::buildstat::BuildStat,
+};
+
+/// A named instance to write into fvar
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FvarInstance {
+    /// The instance's style name, e.g. "Bold Italic"
+    pub name: String,
+    /// Userspace coordinates by axis tag; axes not given take their default
+    #[serde(default)]
+    pub coordinates: BTreeMap<String, f64>,
+    /// The instance's PostScript name
+    #[serde(default)]
+    pub postscript_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FvarInstancesConfig {
+    /// The named instances; if empty, they are derived from the STAT table
+    #[serde(default)]
+    pub instances: Vec<FvarInstance>,
+}
+
+/// Replace the named instances in a variable font's fvar table
+///
+/// Without a list of instances, one instance is made for each weight value
+/// in the STAT table (which `buildStat` fills in from the axis registry's
+/// fallback names), with the other axes at their defaults, and "Italic"
+/// added to the names if the font is italic. Name records used by the old
+/// instances are left in place.
+#[derive(PartialEq, Debug)]
+pub(crate) struct FvarInstances {
+    config: FvarInstancesConfig,
+}
+
+impl FvarInstances {
+    pub fn new() -> Self {
+        FvarInstances {
+            config: FvarInstancesConfig::default(),
+        }
+    }
+
+    /// Make the instances from the weight values in STAT
+    fn instances_from_stat(
+        font: &FontRef,
+        name: &Name,
+    ) -> Result<Vec<FvarInstance>, ApplicationError> {
+        let stat: Stat = font.stat()?.to_owned_table();
+        let italic = font.os2()?.fs_selection().contains(SelectionFlags::ITALIC);
+        let Some(weight_index) = stat
+            .design_axes
+            .iter()
+            .position(|axis| axis.axis_tag == "wght")
+        else {
+            return Err(ApplicationError::InvalidRecipe(
+                "Can't derive instances: no weight axis in STAT".to_string(),
+            ));
+        };
+        let (min, max) = font
+            .fvar()?
+            .axes()?
+            .iter()
+            .find(|axis| axis.axis_tag() == "wght")
+            .map(|axis| (axis.min_value().to_f64(), axis.max_value().to_f64()))
+            .unwrap_or((f64::MIN, f64::MAX));
+        let string_for = |name_id| {
+            name.name_record
+                .iter()
+                .find(|record| record.name_id == name_id)
+                .map(|record| record.string.as_str().to_string())
+        };
+        let mut instances = vec![];
+        for axis_value in stat.offset_to_axis_values.iter().flatten() {
+            let (axis_index, flags, name_id, value) = match axis_value.as_ref() {
+                AxisValue::Format1(v) => (v.axis_index, v.flags, v.value_name_id, v.value),
+                AxisValue::Format2(v) => (v.axis_index, v.flags, v.value_name_id, v.nominal_value),
+                AxisValue::Format3(v) => (v.axis_index, v.flags, v.value_name_id, v.value),
+                AxisValue::Format4(_) => continue,
+            };
+            let value = value.to_f64();
+            if axis_index as usize != weight_index || value < min || value > max {
+                continue;
+            }
+            let Some(weight) = string_for(name_id) else {
+                continue;
+            };
+            let name = match (italic, weight.as_str()) {
+                (true, "Regular") => "Italic".to_string(),
+                (true, _) => format!("{weight} Italic"),
+                (false, _) if flags.contains(AxisValueTableFlags::ELIDABLE_AXIS_VALUE_NAME) => {
+                    "Regular".to_string()
+                }
+                (false, _) => weight,
+            };
+            instances.push(FvarInstance {
+                name,
+                coordinates: [("wght".to_string(), value)].into_iter().collect(),
+                postscript_name: None,
+            });
+        }
+        instances.sort_by(|a, b| a.coordinates["wght"].total_cmp(&b.coordinates["wght"]));
+        Ok(instances)
+    }
+}
+
+impl Operation for FvarInstances {
+    fn shortname(&self) -> &str {
+        "FvarInstances"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        let mut name: Name = font.name()?.to_owned_table();
+        let mut fvar: Fvar = font.fvar()?.to_owned_table();
+        let instances = if self.config.instances.is_empty() {
+            Self::instances_from_stat(&font, &name)?
+        } else {
+            self.config.instances.clone()
+        };
+
+        let axes = &fvar.axis_instance_arrays.axes;
+        if let Some(tag) = instances
+            .iter()
+            .flat_map(|instance| instance.coordinates.keys())
+            .find(|tag| !axes.iter().any(|axis| axis.axis_tag == tag.as_str()))
+        {
+            return Err(ApplicationError::InvalidRecipe(format!(
+                "Instance coordinate for {tag}, which is not an axis of this font"
+            )));
+        }
+        let records = instances
+            .iter()
+            .map(|instance| {
+                let coordinates = axes
+                    .iter()
+                    .map(|axis| {
+                        instance
+                            .coordinates
+                            .get(&axis.axis_tag.to_string())
+                            .map(|value| Fixed::from_f64(*value))
+                            .unwrap_or(axis.default_value)
+                    })
+                    .collect();
+                InstanceRecord {
+                    subfamily_name_id: BuildStat::name_id(&mut name, &instance.name),
+                    flags: 0,
+                    coordinates,
+                    post_script_name_id: instance
+                        .postscript_name
+                        .as_ref()
+                        .map(|psname| BuildStat::name_id(&mut name, psname)),
+                }
+            })
+            .collect();
+        fvar.axis_instance_arrays.instances = records;
+
+        let mut builder = FontBuilder::new();
+        builder.add_table(&fvar)?;
+        builder.add_table(&name)?;
+        builder.copy_missing_tables(font);
+        outputs[0].set_contents(builder.build())?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Rewrite fvar instances".to_string()
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize FvarInstances config: {}. Using defaults.",
+                e
+            );
+            FvarInstancesConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("FvarInstances-{:?}", self.config.instances)
+    }
+}
@@ -1,20 +1,43 @@
-use std::process::Output;
+use std::{os::unix::process::ExitStatusExt, path::Path, process::Output};
 
 use crate::{
     buildsystem::{DataKind, Operation, OperationOutput},
     error::ApplicationError,
 };
 
+/// Convert a Glyphs source to a designspace and UFOs with babelfont
+///
+/// The source is loaded in-process and saved in the format the output's
+/// extension names; an unnamed output becomes a `.designspace` with the
+/// master UFOs written alongside it, so later steps are handed the
+/// designspace rather than a Glyphs file. Unlike `fontmake -o ufo -i`, no
+/// instance UFOs are written.
 #[derive(PartialEq, Debug)]
 pub(crate) struct Glyphs2UFO;
 
+impl Glyphs2UFO {
+    /// Save `font` as a designspace (or whatever `path` names) and its UFOs
+    fn write(font: &babelfont::Font, path: &Path) -> Result<(), ApplicationError> {
+        if !font.instances.is_empty() {
+            log::warn!(
+                "{}: glyphs2ufo writes no instance UFOs, skipping {} instances",
+                path.display(),
+                font.instances.len()
+            );
+        }
+        font.save(path).map_err(|e| {
+            ApplicationError::Other(format!("Failed to save {}: {}", path.display(), e))
+        })
+    }
+}
+
 impl Operation for Glyphs2UFO {
     fn shortname(&self) -> &str {
         "Glyphs2UFO"
     }
 
     fn input_kinds(&self) -> Vec<DataKind> {
-        vec![DataKind::Path]
+        vec![DataKind::SourceFont]
     }
 
     fn output_kinds(&self) -> Vec<DataKind> {
@@ -26,13 +49,48 @@ impl Operation for Glyphs2UFO {
         inputs: &[OperationOutput],
         outputs: &[OperationOutput],
     ) -> Result<Output, ApplicationError> {
-        let cmd = format!(
-            "fontmake -o ufo -i --instance-dir instance_ufo -g {}",
-            inputs[0].to_filename(Some(".glyphs"))?
-        );
-        self.run_shell_command(&cmd, outputs)
+        let font = inputs[0].to_font_source()?;
+        let designspace = outputs[0].to_filename(Some(".designspace"))?;
+        Self::write(&font, Path::new(&designspace))?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
     }
     fn description(&self) -> String {
         "Convert glyphs file to UFO format".to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyphs2ufo_writes_designspace_and_ufos() {
+        let source = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/resources/radio-canada/RadioCanadaDisplay.glyphs"
+        );
+        let font = babelfont::load(source).unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let designspace = dir.path().join("RadioCanadaDisplay.designspace");
+        Glyphs2UFO::write(&font, &designspace).unwrap();
+
+        let text = std::fs::read_to_string(&designspace).unwrap();
+        assert!(text.contains("<designspace"));
+        let ufos = std::fs::read_dir(dir.path())
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ufo"))
+            .collect::<Vec<_>>();
+        assert_eq!(ufos.len(), font.masters.len());
+        for ufo in ufos {
+            assert!(ufo.join("metainfo.plist").exists());
+            let name = ufo.file_name().unwrap().to_string_lossy().to_string();
+            assert!(text.contains(&name), "{name} is not in the designspace");
+        }
+    }
+}
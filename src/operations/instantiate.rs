@@ -0,0 +1,111 @@
+use std::{os::unix::process::ExitStatusExt, process::Output};
+
+use read_fonts::{FontRef, TableProvider};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+    operations::subspace::instance_font,
+};
+
+/// Cut a static font out of a variable font
+///
+/// The arguments give axis locations such as `wght=700,wdth=75`; any axis not
+/// mentioned is pinned at its default, so the result is always fully static.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Instantiate {
+    args: Option<String>,
+}
+
+impl Instantiate {
+    pub fn new() -> Self {
+        Instantiate { args: None }
+    }
+
+    /// The axis locations asked for, as (tag, value) pairs
+    fn locations(&self) -> Result<Vec<(String, f64)>, ApplicationError> {
+        self.args
+            .as_deref()
+            .unwrap_or("")
+            .split([',', ' '])
+            .filter(|location| !location.is_empty())
+            .map(|location| {
+                location
+                    .split_once('=')
+                    .and_then(|(tag, value)| Some((tag.to_string(), value.parse().ok()?)))
+                    .ok_or_else(|| {
+                        ApplicationError::InvalidRecipe(format!(
+                            "Bad axis location '{location}' for instantiate; expected e.g. wght=700"
+                        ))
+                    })
+            })
+            .collect()
+    }
+}
+
+impl Operation for Instantiate {
+    fn shortname(&self) -> &str {
+        "Instantiate"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        let locations = self.locations()?;
+        let axes = font.fvar()?.axes()?;
+        if let Some((tag, _)) = locations
+            .iter()
+            .find(|(tag, _)| !axes.iter().any(|axis| axis.axis_tag().to_string() == *tag))
+        {
+            return Err(ApplicationError::InvalidRecipe(format!(
+                "Can't instantiate at {tag}: the font has no such axis"
+            )));
+        }
+        let spec = axes
+            .iter()
+            .map(|axis| {
+                let tag = axis.axis_tag().to_string();
+                let value = locations
+                    .iter()
+                    .find(|(other, _)| *other == tag)
+                    .map(|(_, value)| *value)
+                    .unwrap_or_else(|| axis.default_value().to_f64());
+                format!("{tag}={value}")
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        outputs[0].set_contents(instance_font(&bytes, &spec)?)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Instantiate at {}",
+            self.args.as_deref().unwrap_or("defaults")
+        )
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn identifier(&self) -> String {
+        format!("Instantiate-{}", self.args.as_deref().unwrap_or(""))
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::BTreeMap, collections::HashMap, os::unix::process::ExitStatusExt, process::Output,
+};
+
+use read_fonts::{FontRef, TableProvider, types::NameId};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use write_fonts::{
+    FontBuilder,
+    from_obj::ToOwnedTable,
+    tables::name::{Name, NameRecord},
+};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+const WINDOWS_PLATFORM: u16 = 3;
+const WINDOWS_UNICODE_BMP: u16 = 1;
+const WINDOWS_ENGLISH: u16 = 0x0409;
+const MAC_PLATFORM: u16 = 1;
+const MAC_ROMAN: u16 = 0;
+
+/// Language tags we know how to write, with their Windows language ID and,
+/// for languages which can be written in Mac Roman, their Mac language code
+const LANGUAGES: &[(&str, u16, Option<u16>)] = &[
+    ("ar", 0x0401, None),
+    ("cs", 0x0405, None),
+    ("da", 0x0406, Some(7)),
+    ("de", 0x0407, Some(2)),
+    ("el", 0x0408, None),
+    ("en", 0x0409, Some(0)),
+    ("es", 0x0C0A, Some(6)),
+    ("fi", 0x040B, Some(13)),
+    ("fr", 0x040C, Some(1)),
+    ("he", 0x040D, None),
+    ("hi", 0x0439, None),
+    ("hu", 0x040E, None),
+    ("is", 0x040F, Some(15)),
+    ("it", 0x0410, Some(3)),
+    ("ja", 0x0411, None),
+    ("ko", 0x0412, None),
+    ("nb", 0x0414, Some(9)),
+    ("nl", 0x0413, Some(4)),
+    ("pl", 0x0415, None),
+    ("pt", 0x0416, Some(8)),
+    ("ru", 0x0419, None),
+    ("sv", 0x041D, Some(5)),
+    ("th", 0x041E, None),
+    ("tr", 0x041F, None),
+    ("uk", 0x0422, None),
+    ("vi", 0x042A, None),
+    ("zh-Hans", 0x0804, None),
+    ("zh-Hant", 0x0404, None),
+    ("zh-HK", 0x0C04, None),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalizedNamesConfig {
+    /// Family names keyed by language tag, e.g. `ja: "ノトサンズ"`
+    #[serde(default)]
+    pub family_names: BTreeMap<String, String>,
+}
+
+/// Add localized family names to the name table
+#[derive(PartialEq, Debug)]
+pub(crate) struct LocalizedNames {
+    config: LocalizedNamesConfig,
+}
+
+impl LocalizedNames {
+    pub fn new() -> Self {
+        LocalizedNames {
+            config: LocalizedNamesConfig::default(),
+        }
+    }
+
+    fn language_ids(language: &str) -> Result<(u16, Option<u16>), ApplicationError> {
+        LANGUAGES
+            .iter()
+            .find(|(tag, _, _)| tag.eq_ignore_ascii_case(language))
+            .map(|(_, windows, mac)| (*windows, *mac))
+            .ok_or_else(|| {
+                ApplicationError::InvalidRecipe(format!(
+                    "Don't know how to write names in language '{language}'; known languages are {}",
+                    LANGUAGES
+                        .iter()
+                        .map(|(tag, _, _)| *tag)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            })
+    }
+}
+
+impl Operation for LocalizedNames {
+    fn shortname(&self) -> &str {
+        "LocalizedNames"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        let mut name: Name = font.name()?.to_owned_table();
+
+        let english = |name_id: NameId| {
+            name.name_record
+                .iter()
+                .find(|record| {
+                    record.platform_id == WINDOWS_PLATFORM
+                        && record.language_id == WINDOWS_ENGLISH
+                        && record.name_id == name_id
+                })
+                .map(|record| record.string.to_string())
+        };
+        // The English family name, which we replace in each localized record
+        let family = english(NameId::TYPOGRAPHIC_FAMILY_NAME)
+            .or_else(|| english(NameId::FAMILY_NAME))
+            .ok_or_else(|| {
+                ApplicationError::Other("Font has no English family name".to_string())
+            })?;
+        let english_records: Vec<(NameId, String)> =
+            [NameId::FAMILY_NAME, NameId::TYPOGRAPHIC_FAMILY_NAME]
+                .into_iter()
+                .filter_map(|name_id| english(name_id).map(|string| (name_id, string)))
+                .collect();
+
+        for (language, localized_family) in &self.config.family_names {
+            let (windows_language, mac_language) = Self::language_ids(language)?;
+            for (name_id, string) in &english_records {
+                let localized = string.replacen(&family, localized_family, 1);
+                let mut records = vec![NameRecord::new(
+                    WINDOWS_PLATFORM,
+                    WINDOWS_UNICODE_BMP,
+                    windows_language,
+                    *name_id,
+                    localized.clone().into(),
+                )];
+                if let Some(mac_language) = mac_language {
+                    records.push(NameRecord::new(
+                        MAC_PLATFORM,
+                        MAC_ROMAN,
+                        mac_language,
+                        *name_id,
+                        localized.into(),
+                    ));
+                }
+                for record in records {
+                    name.name_record.retain(|existing| {
+                        (
+                            existing.platform_id,
+                            existing.encoding_id,
+                            existing.language_id,
+                            existing.name_id,
+                        ) != (
+                            record.platform_id,
+                            record.encoding_id,
+                            record.language_id,
+                            record.name_id,
+                        )
+                    });
+                    name.name_record.insert(record);
+                }
+            }
+        }
+
+        let mut builder = FontBuilder::new();
+        builder.add_table(&name)?;
+        builder.copy_missing_tables(font);
+        outputs[0].set_contents(builder.build())?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Add localized names ({})",
+            self.config
+                .family_names
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize LocalizedNames config: {}. Using defaults.",
+                e
+            );
+            LocalizedNamesConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("LocalizedNames-{:?}", self.config)
+    }
+}
@@ -0,0 +1,438 @@
+use std::{collections::HashMap, os::unix::process::ExitStatusExt, process::Output};
+
+use babelfont::MetricType;
+use read_fonts::{FontRef, TableProvider, types::Fixed};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use write_fonts::{
+    FontBuilder,
+    tables::{
+        mvar::{Mvar, ValueRecord},
+        variations::{RegionAxisCoordinates, VariationRegion, ivs_builder::VariationStoreBuilder},
+    },
+    types::{F2Dot14, MajorMinor, Tag},
+};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// The metrics which MVAR can vary, and the source metrics they come from,
+/// in order of preference
+const MVAR_METRICS: &[(&str, &[MetricType])] = &[
+    ("hasc", &[MetricType::TypoAscender, MetricType::Ascender]),
+    ("hdsc", &[MetricType::TypoDescender, MetricType::Descender]),
+    ("hlgp", &[MetricType::TypoLineGap]),
+    ("hcla", &[MetricType::WinAscent]),
+    ("hcld", &[MetricType::WinDescent]),
+    ("xhgt", &[MetricType::XHeight]),
+    ("cpht", &[MetricType::CapHeight]),
+    ("undo", &[MetricType::UnderlinePosition]),
+    ("unds", &[MetricType::UnderlineThickness]),
+    ("stro", &[MetricType::StrikeoutPosition]),
+    ("strs", &[MetricType::StrikeoutSize]),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MvarConfig {
+    /// The source the font was compiled from, whose masters' metrics are used
+    pub source: String,
+}
+
+/// A master's location in normalized coordinates, one per fvar axis
+pub(super) type Location = Vec<f64>;
+
+/// A region of the designspace as (start, peak, end) per axis
+pub(super) type Support = Vec<(f64, f64, f64)>;
+
+/// Add an MVAR table so that the font's metrics vary as its masters' do
+///
+/// A font which already has an MVAR, such as one compiled by fontc, is left
+/// alone, since the compiler knows better than we do.
+///
+/// Deltas are worked out with the same model as fontTools' `VariationModel`:
+/// the masters are ordered by how many axes they sit off-default on, each gets
+/// a region of influence, and each delta is what is left after the earlier
+/// masters' deltas have been applied.
+#[derive(PartialEq, Debug)]
+pub(crate) struct AddMvar {
+    config: MvarConfig,
+}
+
+impl AddMvar {
+    pub fn new() -> Self {
+        AddMvar {
+            config: MvarConfig::default(),
+        }
+    }
+
+    /// How much a delta with the given support applies at a location
+    pub(super) fn scalar(support: &Support, location: &Location) -> f64 {
+        support
+            .iter()
+            .zip(location)
+            .fold(1.0, |scalar, (&(lower, peak, upper), &v)| {
+                if peak == 0.0 || v == peak {
+                    scalar
+                } else if v <= lower || v >= upper {
+                    0.0
+                } else if v < peak {
+                    scalar * (v - lower) / (peak - lower)
+                } else {
+                    scalar * (upper - v) / (upper - peak)
+                }
+            })
+    }
+
+    /// Work out each master's region of influence
+    pub(super) fn supports(locations: &[Location]) -> Vec<Support> {
+        let axis_count = locations.first().map(Vec::len).unwrap_or(0);
+        let min = (0..axis_count)
+            .map(|axis| locations.iter().map(|l| l[axis]).fold(0.0, f64::min))
+            .collect::<Vec<_>>();
+        let max = (0..axis_count)
+            .map(|axis| locations.iter().map(|l| l[axis]).fold(0.0, f64::max))
+            .collect::<Vec<_>>();
+        let mut supports: Vec<Support> = vec![];
+        for location in locations {
+            let mut region: Support = location
+                .iter()
+                .enumerate()
+                .map(|(axis, &v)| {
+                    if v > 0.0 {
+                        (0.0, v, max[axis])
+                    } else if v < 0.0 {
+                        (min[axis], v, 0.0)
+                    } else {
+                        (0.0, 0.0, 0.0)
+                    }
+                })
+                .collect();
+            let on_axes = |region: &Support| {
+                region
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, peak, _))| *peak != 0.0)
+                    .map(|(axis, _)| axis)
+                    .collect::<Vec<_>>()
+            };
+            let axes = on_axes(&region);
+            // Earlier masters inside this one's box split it
+            for previous in &supports {
+                let previous_axes = on_axes(previous);
+                if !previous_axes.iter().all(|axis| axes.contains(axis)) {
+                    continue;
+                }
+                let relevant = axes.iter().all(|&axis| {
+                    let (lower, peak, upper) = region[axis];
+                    let v = previous[axis].1;
+                    v == peak || (lower < v && v < upper)
+                });
+                if !relevant {
+                    continue;
+                }
+                let mut best_ratio = -1.0;
+                let mut best_axes: Vec<(usize, (f64, f64, f64))> = vec![];
+                for &axis in &previous_axes {
+                    let v = previous[axis].1;
+                    let (lower, peak, upper) = region[axis];
+                    let (triple, ratio) = if v < peak {
+                        ((v, peak, upper), (v - peak) / (lower - peak))
+                    } else if peak < v {
+                        ((lower, peak, v), (v - peak) / (upper - peak))
+                    } else {
+                        continue;
+                    };
+                    if ratio > best_ratio {
+                        best_ratio = ratio;
+                        best_axes.clear();
+                    }
+                    if ratio == best_ratio {
+                        best_axes.push((axis, triple));
+                    }
+                }
+                for (axis, triple) in best_axes {
+                    region[axis] = triple;
+                }
+            }
+            supports.push(region);
+        }
+        supports
+    }
+
+    /// Each master's delta: what's left of its value once earlier deltas are applied
+    pub(super) fn deltas(values: &[f64], supports: &[Support], locations: &[Location]) -> Vec<f64> {
+        let mut deltas: Vec<f64> = vec![];
+        for (index, value) in values.iter().enumerate() {
+            let earlier = deltas
+                .iter()
+                .enumerate()
+                .map(|(j, delta)| delta * Self::scalar(&supports[j], &locations[index]))
+                .sum::<f64>();
+            deltas.push(value - earlier);
+        }
+        deltas
+    }
+
+    /// The variation region a support covers
+    pub(super) fn region(support: &Support) -> VariationRegion {
+        VariationRegion::new(
+            support
+                .iter()
+                .map(|&(start, peak, end)| {
+                    RegionAxisCoordinates::new(
+                        F2Dot14::from_f32(start as f32),
+                        F2Dot14::from_f32(peak as f32),
+                        F2Dot14::from_f32(end as f32),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Normalize a master's userspace location against the font's fvar and avar
+    pub(super) fn normalize(
+        font: &FontRef,
+        user: &HashMap<String, f64>,
+    ) -> Result<Location, ApplicationError> {
+        let fvar = font.fvar()?;
+        let avar = font.avar().ok();
+        let segment_maps = avar
+            .as_ref()
+            .map(|avar| avar.axis_segment_maps().iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        fvar.axes()?
+            .iter()
+            .enumerate()
+            .map(|(index, axis)| {
+                let tag = axis.axis_tag().to_string();
+                let default = axis.default_value().to_f64();
+                let v = user.get(&tag).copied().unwrap_or(default);
+                let normalized = if v < default {
+                    (v - default) / (default - axis.min_value().to_f64())
+                } else if v > default {
+                    (v - default) / (axis.max_value().to_f64() - default)
+                } else {
+                    0.0
+                };
+                let normalized = Fixed::from_f64(normalized.clamp(-1.0, 1.0));
+                let mapped = match segment_maps.get(index) {
+                    Some(Ok(map)) => map.apply(normalized),
+                    _ => normalized,
+                };
+                // Round to the precision regions are stored at
+                Ok(F2Dot14::from_f32(mapped.to_f64() as f32).to_f32() as f64)
+            })
+            .collect()
+    }
+
+    fn build_mvar(&self, font: &FontRef) -> Result<Option<Mvar>, ApplicationError> {
+        let source = babelfont::load(&self.config.source).map_err(|e| {
+            ApplicationError::InvalidRecipe(format!(
+                "Failed to load source {}: {e}",
+                self.config.source
+            ))
+        })?;
+        // Masters in userspace, then normalized
+        let mut masters = vec![];
+        for master in &source.masters {
+            let mut user = HashMap::new();
+            for (tag, value) in master.location.iter() {
+                let Some(axis) = source.axes.iter().find(|axis| axis.tag == *tag) else {
+                    continue;
+                };
+                let value = axis.designspace_to_userspace(*value).map_err(|e| {
+                    ApplicationError::InvalidRecipe(format!("Bad master location: {e}"))
+                })?;
+                user.insert(tag.to_string(), value.to_f64());
+            }
+            masters.push((Self::normalize(font, &user)?, master));
+        }
+        // Default master first, then by how many axes they are off-default on
+        masters.sort_by_key(|(location, _)| location.iter().filter(|v| **v != 0.0).count());
+        if masters
+            .first()
+            .is_none_or(|(location, _)| location.iter().any(|v| *v != 0.0))
+        {
+            return Err(ApplicationError::InvalidRecipe(format!(
+                "No master of {} is at the default location",
+                self.config.source
+            )));
+        }
+        let locations = masters
+            .iter()
+            .map(|(location, _)| location.clone())
+            .collect::<Vec<_>>();
+        let supports = Self::supports(&locations);
+
+        let axis_count = locations[0].len() as u16;
+        let mut store = VariationStoreBuilder::new(axis_count);
+        let mut temporary_ids = vec![];
+        for (tag, metric_types) in MVAR_METRICS {
+            let values = masters
+                .iter()
+                .map(|(_, master)| {
+                    metric_types
+                        .iter()
+                        .find_map(|metric_type| master.metrics.get(metric_type))
+                        .map(|value| *value as f64)
+                })
+                .collect::<Option<Vec<_>>>();
+            let Some(values) = values else {
+                continue;
+            };
+            let deltas = Self::deltas(&values, &supports, &locations);
+            let regions = supports
+                .iter()
+                .zip(deltas.iter())
+                .skip(1)
+                .filter(|(_, delta)| delta.round() != 0.0)
+                .map(|(support, delta)| (Self::region(support), delta.round() as i32))
+                .collect::<Vec<_>>();
+            if regions.is_empty() {
+                continue;
+            }
+            temporary_ids.push((
+                Tag::new(tag.as_bytes().try_into().unwrap()),
+                store.add_deltas(regions),
+            ));
+        }
+        if temporary_ids.is_empty() {
+            return Ok(None);
+        }
+        let (store, remapping) = store.build();
+        let mut records = temporary_ids
+            .into_iter()
+            .filter_map(|(tag, id)| {
+                let index = remapping.get(id)?;
+                Some(ValueRecord::new(
+                    tag,
+                    index.delta_set_outer_index,
+                    index.delta_set_inner_index,
+                ))
+            })
+            .collect::<Vec<_>>();
+        records.sort_by_key(|record| record.value_tag);
+        Ok(Some(Mvar::new(
+            MajorMinor::VERSION_1_0,
+            records,
+            Some(store),
+        )))
+    }
+}
+
+impl Operation for AddMvar {
+    fn shortname(&self) -> &str {
+        "AddMvar"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        if font.mvar().is_ok() {
+            log::info!("Font already has an MVAR table; leaving it alone");
+            outputs[0].set_contents(bytes.clone())?;
+            return Ok(Output {
+                status: std::process::ExitStatus::from_raw(0),
+                stdout: vec![],
+                stderr: vec![],
+            });
+        }
+        match self.build_mvar(&font)? {
+            Some(mvar) => {
+                let mut builder = FontBuilder::new();
+                builder.add_table(&mvar)?;
+                builder.copy_missing_tables(font);
+                outputs[0].set_contents(builder.build())?;
+            }
+            None => {
+                log::info!("Metrics don't vary between masters; not adding MVAR");
+                outputs[0].set_contents(bytes.clone())?;
+            }
+        }
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Add MVAR table".to_string()
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize AddMvar config: {}. Using defaults.",
+                e
+            );
+            MvarConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("AddMvar-{}", self.config.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supports_split_by_intermediate_master() {
+        let locations = vec![vec![0.0], vec![0.5], vec![1.0], vec![-1.0]];
+        assert_eq!(
+            AddMvar::supports(&locations),
+            vec![
+                vec![(0.0, 0.0, 0.0)],
+                vec![(0.0, 0.5, 1.0)],
+                vec![(0.5, 1.0, 1.0)],
+                vec![(-1.0, -1.0, 0.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_supports_corner_master() {
+        let locations = vec![
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+        ];
+        let supports = AddMvar::supports(&locations);
+        assert_eq!(supports[1], vec![(0.0, 1.0, 1.0), (0.0, 0.0, 0.0)]);
+        assert_eq!(supports[2], vec![(0.0, 0.0, 0.0), (0.0, 1.0, 1.0)]);
+        assert_eq!(supports[3], vec![(0.0, 1.0, 1.0), (0.0, 1.0, 1.0)]);
+    }
+
+    #[test]
+    fn test_scalar() {
+        let support = vec![(0.0, 0.5, 1.0)];
+        assert_eq!(AddMvar::scalar(&support, &vec![0.5]), 1.0);
+        assert_eq!(AddMvar::scalar(&support, &vec![0.25]), 0.5);
+        assert_eq!(AddMvar::scalar(&support, &vec![0.75]), 0.5);
+        assert_eq!(AddMvar::scalar(&support, &vec![1.0]), 0.0);
+        assert_eq!(AddMvar::scalar(&support, &vec![-0.5]), 0.0);
+        // An axis the region doesn't touch doesn't matter
+        let support = vec![(0.0, 1.0, 1.0), (0.0, 0.0, 0.0)];
+        assert_eq!(AddMvar::scalar(&support, &vec![1.0, -1.0]), 1.0);
+    }
+}
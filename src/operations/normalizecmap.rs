@@ -0,0 +1,103 @@
+use std::{collections::BTreeMap, os::unix::process::ExitStatusExt, process::Output};
+
+use read_fonts::{
+    FontRef, TableProvider,
+    tables::cmap::{CmapSubtable, PlatformId},
+};
+use write_fonts::{FontBuilder, tables::cmap::Cmap};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// Rebuild a font's cmap as format 4 and (if needed) format 12 subtables
+///
+/// The Unicode mappings from every format 4 and format 12 subtable are merged
+/// and written out as the standard set of subtables: format 4 for the BMP,
+/// plus format 12 when there are supplementary-plane codepoints. Anything
+/// else, such as Mac Roman format 0 or format 6 subtables, is dropped.
+/// Format 14 (variation sequences) can't be rebuilt yet, so fonts which have
+/// one lose it with a warning.
+#[derive(PartialEq, Debug)]
+pub(crate) struct NormalizeCmap {}
+
+impl NormalizeCmap {
+    pub fn new() -> Self {
+        NormalizeCmap {}
+    }
+}
+
+impl Operation for NormalizeCmap {
+    fn shortname(&self) -> &str {
+        "NormalizeCmap"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        let cmap = font.cmap()?;
+
+        let mut mappings = BTreeMap::new();
+        for record in cmap.encoding_records() {
+            if !matches!(
+                record.platform_id(),
+                PlatformId::Unicode | PlatformId::Windows
+            ) {
+                continue;
+            }
+            match record.subtable(cmap.offset_data())? {
+                CmapSubtable::Format4(subtable) => {
+                    for (codepoint, gid) in subtable.iter() {
+                        mappings.entry(codepoint).or_insert(gid);
+                    }
+                }
+                CmapSubtable::Format12(subtable) => {
+                    for (codepoint, gid) in subtable.iter() {
+                        mappings.entry(codepoint).or_insert(gid);
+                    }
+                }
+                CmapSubtable::Format14(_) => {
+                    log::warn!("Dropping cmap format 14 (variation sequences) subtable");
+                }
+                _ => {}
+            }
+        }
+        let new_cmap = Cmap::from_mappings(
+            mappings
+                .into_iter()
+                .filter_map(|(codepoint, gid)| char::from_u32(codepoint).map(|ch| (ch, gid))),
+        )
+        .map_err(|e| ApplicationError::Other(format!("Could not build cmap: {e}")))?;
+
+        let mut builder = FontBuilder::new();
+        builder.add_table(&new_cmap)?;
+        builder.copy_missing_tables(font);
+        outputs[0].set_contents(builder.build())?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Normalize cmap".to_string()
+    }
+
+    fn identifier(&self) -> String {
+        "NormalizeCmap".to_string()
+    }
+}
@@ -0,0 +1,267 @@
+use std::{collections::HashMap, os::unix::process::ExitStatusExt, process::Output};
+
+use read_fonts::{
+    FontRef, TableProvider,
+    tables::{glyf::Glyph, head::MacStyle, os2::SelectionFlags},
+    types::{NameId, Tag},
+};
+use serde::{Deserialize, Serialize};
+use serde_inline_default::serde_inline_default;
+use serde_json::Value;
+use write_fonts::{
+    FontBuilder,
+    from_obj::ToOwnedTable,
+    tables::{glyf::Glyph as OwnedGlyph, head::Head, hhea::Hhea, name::Name, os2::Os2, post::Post},
+    types::Fixed,
+};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+    operations::decompose::{outline, rewrite_glyphs, simple_glyph},
+};
+
+#[serde_inline_default]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ObliqueConfig {
+    /// How far to slant the glyphs, in degrees clockwise from upright
+    #[serde_inline_default(12.0)]
+    pub angle: f64,
+}
+
+impl Default for ObliqueConfig {
+    fn default() -> Self {
+        // Just deserialize nothing and let serde fill in the defaults
+        serde_json::from_str("{}").unwrap()
+    }
+}
+
+/// Make an oblique companion of a static TTF by slanting its outlines
+///
+/// Every glyph is skewed about the baseline. Components are decomposed on the
+/// way, since a scaled or rotated component can't be slanted in place, and
+/// the bounding box and side bearings are recalculated. Hinting is dropped,
+/// so this should come before any hinting step. The italic angle, caret
+/// slope and italic bits are set to match, and the style names (including
+/// the unique ID) gain "Italic".
+#[derive(PartialEq, Debug)]
+pub(crate) struct Oblique {
+    config: ObliqueConfig,
+}
+
+impl Oblique {
+    pub fn new() -> Self {
+        Oblique {
+            config: ObliqueConfig::default(),
+        }
+    }
+
+    /// "Bold" becomes "Bold Italic", "Regular" becomes just "Italic"
+    fn italic_style(style: &str) -> String {
+        match style {
+            "Regular" | "" => "Italic".to_string(),
+            _ => format!("{style} Italic"),
+        }
+    }
+
+    /// The PostScript name of the italic
+    fn italic_postscript_name(old: &str) -> String {
+        match old.split_once('-') {
+            Some((family, "Regular")) => format!("{family}-Italic"),
+            Some(_) => format!("{old}Italic"),
+            None => format!("{old}-Italic"),
+        }
+    }
+
+    fn italic_names(name: &mut Name) {
+        let postscript_name = name
+            .name_record
+            .iter()
+            .find(|record| record.name_id == NameId::POSTSCRIPT_NAME)
+            .map(|record| record.string.as_str().to_string());
+        for record in name.name_record.iter_mut() {
+            let old = record.string.as_str();
+            if old.contains("Italic") || old.contains("Oblique") {
+                continue;
+            }
+            let new = match record.name_id {
+                NameId::SUBFAMILY_NAME | NameId::TYPOGRAPHIC_SUBFAMILY_NAME => {
+                    Self::italic_style(old)
+                }
+                NameId::FULL_NAME => {
+                    format!("{} Italic", old.strip_suffix(" Regular").unwrap_or(old))
+                }
+                NameId::POSTSCRIPT_NAME => Self::italic_postscript_name(old),
+                // Usually "version;vendor;PostScript name"
+                NameId::UNIQUE_ID => match &postscript_name {
+                    Some(postscript_name) if old.contains(postscript_name.as_str()) => old.replace(
+                        postscript_name.as_str(),
+                        &Self::italic_postscript_name(postscript_name),
+                    ),
+                    _ => format!("{old} Italic"),
+                },
+                _ => continue,
+            };
+            record.string = new.into();
+        }
+    }
+
+    /// Slant a static TTF, returning the bytes of the oblique
+    fn slant(&self, bytes: &[u8]) -> Result<Vec<u8>, ApplicationError> {
+        let font = FontRef::new(bytes)?;
+        let slant = self.config.angle.to_radians().tan();
+        let slanted = rewrite_glyphs(&font, |gid, glyph| {
+            if let Glyph::Simple(simple) = glyph
+                && simple.num_points() == 0
+            {
+                return Ok(None);
+            }
+            let contours = outline(&font, gid, 0)?
+                .into_iter()
+                .map(|contour| {
+                    contour
+                        .into_iter()
+                        .map(|(x, y, on)| (x + y * slant, y, on))
+                        .collect()
+                })
+                .collect();
+            Ok(Some(OwnedGlyph::Simple(simple_glyph(contours))))
+        })?;
+
+        let font = FontRef::new(&slanted)?;
+        let mut head: Head = font.head()?.to_owned_table();
+        head.mac_style |= MacStyle::ITALIC;
+        let mut post: Post = font.post()?.to_owned_table();
+        post.italic_angle = Fixed::from_f64(-self.config.angle);
+        let mut hhea: Hhea = font.hhea()?.to_owned_table();
+        hhea.caret_slope_rise = head.units_per_em as i16;
+        hhea.caret_slope_run = (head.units_per_em as f64 * slant).round() as i16;
+        let mut os2: Os2 = font.os2()?.to_owned_table();
+        os2.fs_selection |= SelectionFlags::ITALIC | SelectionFlags::OBLIQUE;
+        os2.fs_selection.remove(SelectionFlags::REGULAR);
+        let mut name: Name = font.name()?.to_owned_table();
+        Self::italic_names(&mut name);
+
+        let mut builder = FontBuilder::new();
+        builder.add_table(&head)?;
+        builder.add_table(&post)?;
+        builder.add_table(&hhea)?;
+        builder.add_table(&os2)?;
+        builder.add_table(&name)?;
+        // The hinting no longer fits the outlines
+        let hinting = [
+            b"fpgm", b"prep", b"cvt ", b"TTFA", b"hdmx", b"LTSH", b"VDMX",
+        ]
+        .map(Tag::new);
+        for record in font.table_directory.table_records() {
+            let tag = record.tag();
+            if !hinting.contains(&tag)
+                && !builder.contains(tag)
+                && let Some(data) = font.table_data(tag)
+            {
+                builder.add_raw(tag, data);
+            }
+        }
+        Ok(builder.build())
+    }
+}
+
+impl Operation for Oblique {
+    fn shortname(&self) -> &str {
+        "Oblique"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        outputs[0].set_contents(self.slant(&bytes)?)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        format!("Slant by {}°", self.config.angle)
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize Oblique config: {}. Using defaults.",
+                e
+            );
+            ObliqueConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("Oblique-{}", self.config.angle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use read_fonts::types::GlyphId;
+
+    use super::*;
+    use crate::operations::decompose::tests::{rectangle, test_font};
+
+    #[test]
+    fn test_oblique_metrics() {
+        let bytes = test_font(&[
+            OwnedGlyph::Empty,
+            OwnedGlyph::Simple(simple_glyph(rectangle(100.0, 100.0, 500.0, 700.0))),
+        ]);
+        let oblique = Oblique {
+            config: ObliqueConfig { angle: 45.0 },
+        };
+        let slanted = oblique.slant(&bytes).unwrap();
+        let font = FontRef::new(&slanted).unwrap();
+
+        // At 45° each point moves right by its height
+        let head = font.head().unwrap();
+        assert_eq!((head.x_min(), head.x_max()), (200, 1200));
+        assert_eq!(
+            font.hmtx().unwrap().side_bearing(GlyphId::new(1)),
+            Some(200)
+        );
+        let hhea = font.hhea().unwrap();
+        assert_eq!(hhea.min_left_side_bearing().to_i16(), 200);
+        assert_eq!(hhea.min_right_side_bearing().to_i16(), -600);
+        assert_eq!(hhea.x_max_extent().to_i16(), 1200);
+
+        let name = font.name().unwrap();
+        let string = |id: NameId| {
+            name.name_record()
+                .iter()
+                .find(|record| record.name_id() == id)
+                .and_then(|record| record.string(name.string_data()).ok())
+                .map(|string| string.to_string())
+        };
+        assert_eq!(string(NameId::SUBFAMILY_NAME).as_deref(), Some("Italic"));
+        assert_eq!(string(NameId::FULL_NAME).as_deref(), Some("Test Italic"));
+        assert_eq!(
+            string(NameId::UNIQUE_ID).as_deref(),
+            Some("1.000;NONE;Test-Italic")
+        );
+        assert_eq!(
+            string(NameId::POSTSCRIPT_NAME).as_deref(),
+            Some("Test-Italic")
+        );
+    }
+}
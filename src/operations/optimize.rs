@@ -0,0 +1,126 @@
+use std::{collections::HashMap, io::Write, process::Output};
+
+use serde::{Deserialize, Serialize};
+use serde_inline_default::serde_inline_default;
+use serde_json::Value;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput, scratch_file},
+    error::ApplicationError,
+};
+
+/// The fontTools script which does the work: `optimize.py IN OUT TOLERANCE LEVEL`
+const OPTIMIZE_SCRIPT: &str = r#"
+import sys
+from fontTools.ttLib import TTFont
+from fontTools.otlLib.optimize import compact
+
+infile, outfile, tolerance, level = sys.argv[1], sys.argv[2], float(sys.argv[3]), int(sys.argv[4])
+font = TTFont(infile)
+if "gvar" in font and "glyf" in font:
+    glyf = font["glyf"]
+    hmtx = font["hmtx"].metrics
+    vmtx = font["vmtx"].metrics if "vmtx" in font else None
+    for name, variations in font["gvar"].variations.items():
+        coords, controls = glyf._getCoordinatesAndControls(name, hmtx, vmtx)
+        is_composite = glyf[name].isComposite()
+        for variation in variations:
+            variation.calcInferredDeltas(coords, controls.endPts)
+            variation.optimize(coords, controls.endPts, tolerance=tolerance, isComposite=is_composite)
+if level and "GPOS" in font:
+    compact(font, level)
+font.save(outfile)
+"#;
+
+#[serde_inline_default]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizeConfig {
+    /// How far (in font units) an interpolated point may stray from its
+    /// explicit delta before the delta has to be kept
+    #[serde_inline_default(0.5)]
+    pub tolerance: f64,
+    /// How hard to compact GPOS, from 1 to 9; 0 leaves it alone
+    #[serde_inline_default(9)]
+    pub gpos_compression: u8,
+}
+
+impl Default for OptimizeConfig {
+    fn default() -> Self {
+        // Just deserialize nothing and let serde fill in the defaults
+        serde_json::from_str("{}").unwrap()
+    }
+}
+
+/// Shrink a font before it is compressed for the web
+///
+/// The `gvar` deltas of every glyph are re-optimized so that points which IUP
+/// can interpolate are dropped, and GPOS lookups are compacted. fontTools does
+/// the work, and repacks GSUB and GPOS with HarfBuzz's repacker when
+/// `uharfbuzz` is installed.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Optimize {
+    config: OptimizeConfig,
+}
+
+impl Optimize {
+    pub fn new() -> Self {
+        Optimize {
+            config: OptimizeConfig::default(),
+        }
+    }
+}
+
+impl Operation for Optimize {
+    fn shortname(&self) -> &str {
+        "Optimize"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let mut script = scratch_file(Some(".py"))?;
+        script.write_all(OPTIMIZE_SCRIPT.as_bytes())?;
+        let cmd = format!(
+            "python3 {} {} {} {} {}",
+            script.path().display(),
+            inputs[0].to_filename(Some(".ttf"))?,
+            outputs[0].to_filename(Some(".ttf"))?,
+            self.config.tolerance,
+            self.config.gpos_compression
+        );
+        self.run_shell_command(&cmd, outputs)
+    }
+
+    fn description(&self) -> String {
+        "Optimize gvar and GPOS".to_string()
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize Optimize config: {}. Using defaults.",
+                e
+            );
+            OptimizeConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!(
+            "Optimize-{}-{}",
+            self.config.tolerance, self.config.gpos_compression
+        )
+    }
+}
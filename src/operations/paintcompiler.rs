@@ -0,0 +1,94 @@
+use std::{collections::HashMap, process::Output};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PaintcompilerConfig {
+    /// The Python file defining the paints, usually `paints.py`
+    pub paints: String,
+}
+
+/// Add COLRv1 paints to a compiled font by running paintcompiler
+///
+/// The paint definitions are Python, and are run against the font's glyphs to
+/// produce the COLR and CPAL tables.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Paintcompiler {
+    args: Option<String>,
+    config: PaintcompilerConfig,
+}
+
+impl Paintcompiler {
+    pub fn new() -> Self {
+        Paintcompiler {
+            args: None,
+            config: PaintcompilerConfig::default(),
+        }
+    }
+}
+
+impl Operation for Paintcompiler {
+    fn shortname(&self) -> &str {
+        "Paintcompiler"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let mut cmd = format!("paintcompiler --paints {}", self.config.paints);
+        if let Some(args) = &self.args {
+            cmd.push(' ');
+            cmd.push_str(args);
+        }
+        cmd.push_str(&format!(
+            " -o {} {}",
+            outputs[0].to_filename(Some(".ttf"))?,
+            inputs[0].to_filename(Some(".ttf"))?
+        ));
+        self.run_shell_command(&cmd, outputs)
+    }
+
+    fn description(&self) -> String {
+        format!("Add paints from {}", self.config.paints)
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize Paintcompiler config: {}. Using defaults.",
+                e
+            );
+            PaintcompilerConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!(
+            "Paintcompiler-{}-{}",
+            self.config.paints,
+            self.args.as_deref().unwrap_or("")
+        )
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    os::unix::process::ExitStatusExt,
+    process::Output,
+};
+
+use read_fonts::{
+    FontRef, TableProvider,
+    tables::{
+        cmap::{CmapSubtable, PlatformId},
+        post::DEFAULT_GLYPH_NAMES,
+    },
+    types::{GlyphId16, Version16Dot16},
+};
+use serde::{Deserialize, Serialize};
+use serde_inline_default::serde_inline_default;
+use serde_json::Value;
+use write_fonts::{FontBuilder, from_obj::ToOwnedTable, tables::post::Post};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+#[serde_inline_default]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProductionNamesConfig {
+    /// Name encoded glyphs `uniXXXX` (or `uXXXXX` outside the BMP), unless
+    /// they already have one of the standard Macintosh glyph names
+    #[serde_inline_default(true)]
+    pub production: bool,
+    /// Explicit renames, from old name to new; these win over production names
+    #[serde(default)]
+    pub rename: HashMap<String, String>,
+    /// Drop the glyph names entirely, writing a version 3 `post` table
+    #[serde(default)]
+    pub post_v3: bool,
+}
+
+impl Default for ProductionNamesConfig {
+    fn default() -> Self {
+        // Just deserialize nothing and let serde fill in the defaults
+        serde_json::from_str("{}").unwrap()
+    }
+}
+
+/// Rename the glyphs in a compiled font, usually to their production names
+///
+/// Only the `post` table carries glyph names in a TrueType font, so that's
+/// the only table rewritten. Names which would clash get a numeric suffix.
+#[derive(PartialEq, Debug)]
+pub(crate) struct ProductionNames {
+    config: ProductionNamesConfig,
+}
+
+impl ProductionNames {
+    pub fn new() -> Self {
+        ProductionNames {
+            config: ProductionNamesConfig::default(),
+        }
+    }
+
+    /// The lowest codepoint mapped to each glyph
+    fn codepoints(font: &FontRef) -> Result<BTreeMap<GlyphId16, u32>, ApplicationError> {
+        let cmap = font.cmap()?;
+        let mut codepoints = BTreeMap::new();
+        for record in cmap.encoding_records() {
+            if !matches!(
+                record.platform_id(),
+                PlatformId::Unicode | PlatformId::Windows
+            ) {
+                continue;
+            }
+            let mappings: Vec<(u32, GlyphId16)> = match record.subtable(cmap.offset_data())? {
+                CmapSubtable::Format4(subtable) => subtable
+                    .iter()
+                    .filter_map(|(codepoint, gid)| Some((codepoint, gid.try_into().ok()?)))
+                    .collect(),
+                CmapSubtable::Format12(subtable) => subtable
+                    .iter()
+                    .filter_map(|(codepoint, gid)| Some((codepoint, gid.try_into().ok()?)))
+                    .collect(),
+                _ => continue,
+            };
+            for (codepoint, gid) in mappings {
+                codepoints
+                    .entry(gid)
+                    .and_modify(|existing: &mut u32| *existing = (*existing).min(codepoint))
+                    .or_insert(codepoint);
+            }
+        }
+        Ok(codepoints)
+    }
+
+    fn new_names(&self, font: &FontRef) -> Result<Vec<String>, ApplicationError> {
+        let post = font.post()?;
+        let num_glyphs = font.maxp()?.num_glyphs();
+        let codepoints = if self.config.production {
+            Self::codepoints(font)?
+        } else {
+            BTreeMap::new()
+        };
+        let mut seen = HashSet::new();
+        let mut names = vec![];
+        for gid in 0..num_glyphs {
+            let gid = GlyphId16::new(gid);
+            let old = post
+                .glyph_name(gid)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("glyph{:05}", gid.to_u16()));
+            let mut name = if let Some(new) = self.config.rename.get(&old) {
+                new.clone()
+            } else if gid.to_u16() == 0 {
+                ".notdef".to_string()
+            } else if let Some(codepoint) = codepoints.get(&gid)
+                && !DEFAULT_GLYPH_NAMES.contains(&old.as_str())
+            {
+                if *codepoint > 0xFFFF {
+                    format!("u{codepoint:05X}")
+                } else {
+                    format!("uni{codepoint:04X}")
+                }
+            } else {
+                old
+            };
+            if seen.contains(&name) {
+                let base = name.clone();
+                let mut suffix = 1;
+                while seen.contains(&name) {
+                    name = format!("{base}.{suffix}");
+                    suffix += 1;
+                }
+                log::warn!("Glyph name {base} is used twice; renaming glyph {gid} to {name}");
+            }
+            seen.insert(name.clone());
+            names.push(name);
+        }
+        Ok(names)
+    }
+}
+
+impl Operation for ProductionNames {
+    fn shortname(&self) -> &str {
+        "ProductionNames"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        let mut post: Post = font.post()?.to_owned_table();
+        if self.config.post_v3 {
+            post.version = Version16Dot16::VERSION_3_0;
+            post.num_glyphs = None;
+            post.glyph_name_index = None;
+            post.string_data = None;
+        } else {
+            let names = self.new_names(&font)?;
+            let renamed = Post::new_v2(names.iter().map(String::as_str));
+            post.version = renamed.version;
+            post.num_glyphs = renamed.num_glyphs;
+            post.glyph_name_index = renamed.glyph_name_index;
+            post.string_data = renamed.string_data;
+        }
+        let mut builder = FontBuilder::new();
+        builder.add_table(&post)?;
+        builder.copy_missing_tables(font);
+        outputs[0].set_contents(builder.build())?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        if self.config.post_v3 {
+            "Drop glyph names".to_string()
+        } else {
+            "Rename glyphs".to_string()
+        }
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize ProductionNames config: {}. Using defaults.",
+                e
+            );
+            ProductionNamesConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        let mut renames = self
+            .config
+            .rename
+            .iter()
+            .map(|(old, new)| format!("{old}={new}"))
+            .collect::<Vec<_>>();
+        renames.sort();
+        format!(
+            "ProductionNames-{}-{}-{}",
+            self.config.production,
+            self.config.post_v3,
+            renames.join(",")
+        )
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+    os::unix::process::ExitStatusExt,
+    path::{Component, Path, PathBuf},
+    process::Output,
+};
+
+use read_fonts::{
+    FontRef, TableProvider,
+    tables::cmap::CmapSubtable,
+    types::{GlyphId, GlyphId16, NameId},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+    operations::buildmetadata::english_name,
+};
+
+const SAMPLE_TEXT: &str = "The quick brown fox jumps over the lazy dog";
+const WATERFALL_SIZES: &[u32] = &[8, 10, 12, 14, 16, 20, 24, 32, 48, 64, 72];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProofFormat {
+    /// Waterfalls and a glyph grid, as a web page
+    #[default]
+    Html,
+    /// A rendering of the sample text, made by `hb-view`
+    Png,
+}
+
+impl ProofFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ProofFormat::Html => "html",
+            ProofFormat::Png => "png",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofConfig {
+    #[serde(default)]
+    pub format: ProofFormat,
+    /// The sample text; defaults to a pangram
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// Write a proof for a built font: an HTML page of waterfalls and a glyph
+/// grid, or a PNG rendering of the sample text
+#[derive(PartialEq, Debug)]
+pub(crate) struct Proof {
+    config: ProofConfig,
+}
+
+impl Proof {
+    pub fn new() -> Self {
+        Proof {
+            config: ProofConfig::default(),
+        }
+    }
+
+    fn text(&self) -> &str {
+        self.config.text.as_deref().unwrap_or(SAMPLE_TEXT)
+    }
+
+    fn escape(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    /// The path to `target`, relative to the directory `from`
+    fn relative_path(from: &Path, target: &Path) -> Result<String, ApplicationError> {
+        let from = from.canonicalize()?;
+        let target = target.canonicalize()?;
+        let common = from
+            .components()
+            .zip(target.components())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let mut relative: PathBuf = from
+            .components()
+            .skip(common)
+            .map(|_| Component::ParentDir)
+            .collect();
+        relative.extend(target.components().skip(common));
+        Ok(relative.to_string_lossy().to_string())
+    }
+
+    /// Every encoded codepoint in the font, in order, with its glyph
+    fn codepoints(font: &FontRef) -> Result<BTreeMap<u32, GlyphId>, ApplicationError> {
+        let cmap = font.cmap()?;
+        let mut codepoints = BTreeMap::new();
+        for record in cmap.encoding_records() {
+            match record.subtable(cmap.offset_data())? {
+                CmapSubtable::Format4(subtable) => codepoints.extend(subtable.iter()),
+                CmapSubtable::Format12(subtable) => codepoints.extend(subtable.iter()),
+                _ => {}
+            }
+        }
+        Ok(codepoints)
+    }
+
+    /// The names of glyphs which no codepoint reaches, other than `.notdef`;
+    /// they can't be typed, so the glyph grid lists them by name instead
+    fn unencoded_glyphs(
+        font: &FontRef,
+        codepoints: &BTreeMap<u32, GlyphId>,
+    ) -> Result<Vec<String>, ApplicationError> {
+        let encoded: HashSet<GlyphId> = codepoints.values().copied().collect();
+        let post = font.post().ok();
+        let num_glyphs = font.maxp()?.num_glyphs();
+        Ok((1..num_glyphs)
+            .map(GlyphId16::new)
+            .filter(|gid| !encoded.contains(&GlyphId::from(*gid)))
+            .map(|gid| {
+                post.as_ref()
+                    .and_then(|post| post.glyph_name(gid))
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("glyph{:05}", gid.to_u16()))
+            })
+            .collect())
+    }
+
+    /// The named instances of a variable font, as (name, font-variation-settings)
+    fn instances(font: &FontRef) -> Result<Vec<(String, String)>, ApplicationError> {
+        let Ok(fvar) = font.fvar() else {
+            return Ok(vec![]);
+        };
+        let axes = fvar.axes()?;
+        let mut instances = vec![];
+        for instance in fvar.instances()?.iter() {
+            let instance = instance?;
+            let name = english_name(font, instance.subfamily_name_id)
+                .unwrap_or_else(|| "Instance".to_string());
+            let settings = axes
+                .iter()
+                .zip(instance.coordinates.iter())
+                .map(|(axis, value)| format!("\"{}\" {}", axis.axis_tag(), value.get().to_f64()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            instances.push((name, settings));
+        }
+        Ok(instances)
+    }
+
+    fn render(&self, font: &FontRef, font_url: &str) -> Result<String, ApplicationError> {
+        let family = english_name(font, NameId::FULL_NAME).unwrap_or_default();
+        let mut styles = Self::instances(font)?;
+        if styles.is_empty() {
+            styles.push((
+                english_name(font, NameId::SUBFAMILY_NAME).unwrap_or_default(),
+                "normal".to_string(),
+            ));
+        }
+
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n\
+             @font-face {{ font-family: \"Proof\"; src: url(\"{url}\"); }}\n\
+             body {{ font-family: sans-serif; }}\n\
+             .proof {{ font-family: \"Proof\"; }}\n\
+             .grid {{ display: flex; flex-wrap: wrap; font-size: 32px; }}\n\
+             .grid span {{ width: 1.5em; height: 1.5em; text-align: center; border: 1px solid #ddd; }}\n\
+             </style>\n</head>\n<body>\n<h1>{title}</h1>\n",
+            title = Self::escape(&family),
+            url = Self::escape(font_url),
+        );
+        for (name, settings) in &styles {
+            let _ = writeln!(html, "<h2>{}</h2>", Self::escape(name));
+            for size in WATERFALL_SIZES {
+                let _ = writeln!(
+                    html,
+                    "<p class=\"proof\" style=\"font-size: {size}px; font-variation-settings: {}\">{}</p>",
+                    Self::escape(settings),
+                    Self::escape(self.text())
+                );
+            }
+        }
+        html.push_str("<h2>Glyphs</h2>\n<div class=\"proof grid\">\n");
+        let codepoints = Self::codepoints(font)?;
+        for codepoint in codepoints.keys() {
+            let _ = writeln!(html, "<span>&#x{codepoint:04X};</span>");
+        }
+        html.push_str("</div>\n");
+        let unencoded = Self::unencoded_glyphs(font, &codepoints)?;
+        if !unencoded.is_empty() {
+            html.push_str("<h2>Unencoded glyphs</h2>\n<ul>\n");
+            for name in unencoded {
+                let _ = writeln!(html, "<li>{}</li>", Self::escape(&name));
+            }
+            html.push_str("</ul>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        Ok(html)
+    }
+}
+
+impl Operation for Proof {
+    fn shortname(&self) -> &str {
+        "Proof"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let font_path = inputs[0].to_filename(Some(".ttf"))?;
+        let extension = format!(".{}", self.config.format.extension());
+        let proof_path = outputs[0].to_filename(Some(&extension))?;
+        let proof_dir = Path::new(&proof_path)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        std::fs::create_dir_all(proof_dir)?;
+
+        let bytes = std::fs::read(&font_path)?;
+        let font = FontRef::new(&bytes)?;
+        if font.cff().is_ok() || font.cff2().is_ok() {
+            return Err(ApplicationError::WrongInputs(format!(
+                "Proof needs a TrueType-flavoured font, but {font_path} has CFF outlines"
+            )));
+        }
+        if self.config.format == ProofFormat::Png {
+            let text = self.text().replace('\'', r"'\''");
+            let cmd = format!(
+                "hb-view --font-size=48 --output-format=png -o {proof_path} {font_path} '{text}'"
+            );
+            return self.run_shell_command(&cmd, outputs);
+        }
+        let font_url = Self::relative_path(proof_dir, Path::new(&font_path))?;
+        outputs[0].set_contents(self.render(&font, &font_url)?.into_bytes())?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        match self.config.format {
+            ProofFormat::Html => "Write HTML proof".to_string(),
+            ProofFormat::Png => "Write PNG proof".to_string(),
+        }
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!("Failed to deserialize Proof config: {}. Using defaults.", e);
+            ProofConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("Proof-{:?}", self.config)
+    }
+}
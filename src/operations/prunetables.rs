@@ -0,0 +1,129 @@
+use std::{collections::HashMap, os::unix::process::ExitStatusExt, process::Output};
+
+use read_fonts::{FontRef, types::Tag};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use write_fonts::FontBuilder;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// Tables which are only useful while developing a font, and are dropped by default
+const UNWANTED_TABLES: [&str; 10] = [
+    "FFTM", "TTFA", "TSI0", "TSI1", "TSI2", "TSI3", "TSI5", "TSIV", "Debg", "prop",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneTablesConfig {
+    /// Tables to drop as well as the default ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub drop: Vec<String>,
+    /// Tables to keep even though they would be dropped by default
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keep: Vec<String>,
+}
+
+/// Remove tool-specific and debugging tables from a font
+///
+/// By default this drops the tables left behind by FontForge (`FFTM`),
+/// ttfautohint (`TTFA`), VTT's sources (`TSI*`), and a few others which have
+/// no business in a shipped binary.
+#[derive(PartialEq, Debug)]
+pub(crate) struct PruneTables {
+    config: PruneTablesConfig,
+}
+
+impl PruneTables {
+    pub fn new() -> Self {
+        PruneTables {
+            config: PruneTablesConfig::default(),
+        }
+    }
+
+    fn tag(tag: &str) -> Result<Tag, ApplicationError> {
+        Tag::new_checked(tag.as_bytes())
+            .map_err(|e| ApplicationError::InvalidRecipe(format!("Bad table tag {tag}: {e}")))
+    }
+
+    fn tables_to_drop(&self) -> Result<Vec<Tag>, ApplicationError> {
+        let keep = self
+            .config
+            .keep
+            .iter()
+            .map(|tag| Self::tag(tag))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut drop = UNWANTED_TABLES
+            .iter()
+            .map(|tag| Tag::new_checked(tag.as_bytes()).unwrap())
+            .collect::<Vec<_>>();
+        for tag in &self.config.drop {
+            drop.push(Self::tag(tag)?);
+        }
+        drop.retain(|tag| !keep.contains(tag));
+        Ok(drop)
+    }
+}
+
+impl Operation for PruneTables {
+    fn shortname(&self) -> &str {
+        "PruneTables"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        let drop = self.tables_to_drop()?;
+
+        let mut builder = FontBuilder::new();
+        for record in font.table_directory.table_records() {
+            let tag = record.tag();
+            if drop.contains(&tag) {
+                log::debug!("Dropping {} table", tag);
+                continue;
+            }
+            if let Some(data) = font.table_data(tag) {
+                builder.add_raw(tag, data);
+            }
+        }
+        outputs[0].set_contents(builder.build())?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Prune unwanted tables".to_string()
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize PruneTables config: {}. Using defaults.",
+                e
+            );
+            PruneTablesConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("PruneTables-{:?}", self.config)
+    }
+}
@@ -0,0 +1,63 @@
+use std::process::Output;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// Remap OpenType layout features (e.g. `smcp -> ccmp`) by shelling out to gftools
+#[derive(PartialEq, Debug)]
+pub(crate) struct RemapLayout {
+    args: Option<String>,
+}
+
+impl RemapLayout {
+    pub fn new() -> Self {
+        RemapLayout { args: None }
+    }
+}
+
+impl Operation for RemapLayout {
+    fn shortname(&self) -> &str {
+        "RemapLayout"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let Some(mapping) = &self.args else {
+            return Err(ApplicationError::InvalidRecipe(
+                "The remapLayout operation needs a mapping, e.g. \"smcp -> ccmp\"".to_string(),
+            ));
+        };
+        let cmd = format!(
+            "gftools remap-layout -o {} {} \"{}\"",
+            outputs[0].to_filename(Some(".ttf"))?,
+            inputs[0].to_filename(Some(".ttf"))?,
+            mapping
+        );
+        self.run_shell_command(&cmd, outputs)
+    }
+
+    fn description(&self) -> String {
+        format!("Remap layout {}", self.args.as_deref().unwrap_or(""))
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn identifier(&self) -> String {
+        format!("RemapLayout-{}", self.args.as_deref().unwrap_or(""))
+    }
+}
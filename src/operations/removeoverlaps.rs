@@ -0,0 +1,68 @@
+use std::process::Output;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// Remove contour overlaps from a static TTF
+///
+/// This shells out to `fonttools ttLib.removeOverlaps`, which merges the
+/// overlapping contours of every glyph with skia-pathops. Any arguments are
+/// passed after the input and output file names, so `--ignore-errors` or a
+/// list of the glyph names to work on can be given. It only makes sense for
+/// static fonts: in a variable font the merged contours would no longer
+/// interpolate.
+#[derive(PartialEq, Debug)]
+pub(crate) struct RemoveOverlaps {
+    args: Option<String>,
+}
+
+impl RemoveOverlaps {
+    pub fn new() -> Self {
+        RemoveOverlaps { args: None }
+    }
+}
+
+impl Operation for RemoveOverlaps {
+    fn shortname(&self) -> &str {
+        "RemoveOverlaps"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let mut cmd = format!(
+            "fonttools ttLib.removeOverlaps {} {}",
+            inputs[0].to_filename(Some(".ttf"))?,
+            outputs[0].to_filename(Some(".ttf"))?
+        );
+        if let Some(args) = &self.args {
+            cmd.push(' ');
+            cmd.push_str(args);
+        }
+        self.run_shell_command(&cmd, outputs)
+    }
+
+    fn description(&self) -> String {
+        "Remove overlaps".to_string()
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn identifier(&self) -> String {
+        format!("RemoveOverlaps-{}", self.args.as_deref().unwrap_or(""))
+    }
+}
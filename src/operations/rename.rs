@@ -0,0 +1,103 @@
+use std::{collections::HashMap, process::Output};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameConfig {
+    /// The new family name
+    pub name: String,
+    /// Only rename the family, keeping the style names as they are
+    #[serde(default)]
+    pub just_family: bool,
+}
+
+/// Rename a font's family by shelling out to gftools
+#[derive(PartialEq, Debug)]
+pub(crate) struct Rename {
+    args: Option<String>,
+    config: RenameConfig,
+}
+
+impl Rename {
+    pub fn new() -> Self {
+        Rename {
+            args: None,
+            config: RenameConfig::default(),
+        }
+    }
+}
+
+impl Operation for Rename {
+    fn shortname(&self) -> &str {
+        "Rename"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        if self.config.name.is_empty() {
+            return Err(ApplicationError::InvalidRecipe(
+                "The rename operation needs a name".to_string(),
+            ));
+        }
+        let mut cmd = "gftools rename-font".to_string();
+        if self.config.just_family {
+            cmd.push_str(" --just-family");
+        }
+        if let Some(args) = &self.args {
+            cmd.push(' ');
+            cmd.push_str(args);
+        }
+        cmd.push_str(&format!(
+            " -o {} {} \"{}\"",
+            outputs[0].to_filename(Some(".ttf"))?,
+            inputs[0].to_filename(Some(".ttf"))?,
+            self.config.name
+        ));
+        self.run_shell_command(&cmd, outputs)
+    }
+
+    fn description(&self) -> String {
+        format!("Rename family to {}", self.config.name)
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize Rename config: {}. Using defaults.",
+                e
+            );
+            RenameConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!(
+            "Rename-{:?}-{}",
+            self.config,
+            self.args.as_deref().unwrap_or("")
+        )
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    collections::HashMap,
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::Output,
+    sync::{LazyLock, Mutex},
+};
+
+use read_fonts::{FontRef, TableProvider, types::NameId};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use write_fonts::{
+    FontBuilder,
+    from_obj::ToOwnedTable,
+    tables::{head::Head, name::Name},
+    types::Fixed,
+};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SetVersionConfig {
+    /// The version to set, e.g. "1.002"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Increment the font's existing version by 0.001 (ignored if `version` is given)
+    #[serde(default)]
+    pub bump: bool,
+    /// Take the version from `git describe --tags` (ignored if `version` is given)
+    #[serde(default)]
+    pub from_git: bool,
+}
+
+/// The version git describes each repository as, by the directory it was asked
+/// in, so that it's only asked once however many fonts get a version
+static GIT_VERSIONS: LazyLock<Mutex<HashMap<PathBuf, GitVersion>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A version read from the most recent git tag
+#[derive(Clone)]
+struct GitVersion {
+    version: String,
+    /// The abbreviated commit hash, if there are commits after the tag
+    commit: Option<String>,
+}
+
+/// Set head.fontRevision and the name table version string (name ID 5)
+///
+/// When the version comes from git and the current commit isn't the tagged
+/// one, the commit hash is added to the version string, e.g.
+/// "Version 1.002;git-1a2b3c4".
+#[derive(PartialEq, Debug)]
+pub(crate) struct SetVersion {
+    config: SetVersionConfig,
+}
+
+impl SetVersion {
+    pub fn new() -> Self {
+        SetVersion {
+            config: SetVersionConfig::default(),
+        }
+    }
+
+    fn parse_version(version: &str) -> Result<f64, ApplicationError> {
+        let trimmed = version
+            .trim()
+            .trim_start_matches("Version ")
+            .trim_start_matches('v');
+        trimmed.parse::<f64>().map_err(|_| {
+            ApplicationError::InvalidRecipe(format!("Could not parse version '{version}'"))
+        })
+    }
+
+    /// Read the version from the most recent tag reachable from HEAD of the
+    /// repository holding the config file, whose directory the build runs in
+    fn git_version() -> Result<GitVersion, ApplicationError> {
+        let dir = std::env::current_dir()?;
+        let mut versions = GIT_VERSIONS
+            .lock()
+            .map_err(|e| ApplicationError::Other(e.to_string()))?;
+        if let Some(version) = versions.get(&dir) {
+            return Ok(version.clone());
+        }
+        let version = Self::describe(&dir)?;
+        versions.insert(dir, version.clone());
+        Ok(version)
+    }
+
+    fn describe(dir: &Path) -> Result<GitVersion, ApplicationError> {
+        let output = std::process::Command::new("git")
+            .args(["describe", "--tags", "--long", "--dirty"])
+            .current_dir(dir)
+            .output()?;
+        if !output.status.success() {
+            return Err(ApplicationError::Other(format!(
+                "Could not get version from git: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let description = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let dirty = description.ends_with("-dirty");
+        // Tags may themselves contain hyphens, so split from the right:
+        // <tag>-<commits since tag>-g<hash>[-dirty]
+        let mut parts = description.trim_end_matches("-dirty").rsplitn(3, '-');
+        let (Some(hash), Some(count), Some(tag)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ApplicationError::Other(format!(
+                "Could not parse git description '{description}'"
+            )));
+        };
+        let hash = hash.trim_start_matches('g');
+        let commit = if count != "0" || dirty {
+            Some(format!("{hash}{}", if dirty { "-dirty" } else { "" }))
+        } else {
+            None
+        };
+        Ok(GitVersion {
+            version: tag.to_string(),
+            commit,
+        })
+    }
+
+    /// What follows the version number in a version string, e.g.
+    /// "; ttfautohint (v1.8.4)", less any commit an earlier build added
+    fn version_suffix(version_string: &str) -> String {
+        version_string
+            .split(';')
+            .skip(1)
+            .filter(|part| !part.starts_with("git-"))
+            .map(|part| format!(";{part}"))
+            .collect()
+    }
+
+    /// Work out the version the font should have, given its current one, and
+    /// any commit to mention in the version string
+    fn new_version(&self, current: f64) -> Result<(f64, Option<String>), ApplicationError> {
+        match &self.config.version {
+            Some(version) => Ok((Self::parse_version(version)?, None)),
+            None if self.config.from_git => {
+                let git = Self::git_version()?;
+                Ok((Self::parse_version(&git.version)?, git.commit))
+            }
+            None if self.config.bump => Ok((((current * 1000.0).round() + 1.0) / 1000.0, None)),
+            None => Ok((current, None)),
+        }
+    }
+}
+
+impl Operation for SetVersion {
+    fn shortname(&self) -> &str {
+        "SetVersion"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+
+        let mut head: Head = font.head()?.to_owned_table();
+        let (version, commit) = self.new_version(head.font_revision.to_f64())?;
+        let commit = commit
+            .map(|hash| format!(";git-{hash}"))
+            .unwrap_or_default();
+        head.font_revision = Fixed::from_f64(version);
+
+        let mut name: Name = font.name()?.to_owned_table();
+        name.name_record = name
+            .name_record
+            .into_iter()
+            .map(|mut record| {
+                if record.name_id == NameId::VERSION_STRING {
+                    let suffix = Self::version_suffix(&record.string);
+                    record.string = format!("Version {version:.3}{commit}{suffix}").into();
+                }
+                record
+            })
+            .collect();
+
+        let mut builder = FontBuilder::new();
+        builder.add_table(&head)?;
+        builder.add_table(&name)?;
+        builder.copy_missing_tables(font);
+        outputs[0].set_contents(builder.build())?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        match &self.config.version {
+            Some(version) => format!("Set version to {version}"),
+            None if self.config.from_git => "Set version from git".to_string(),
+            None if self.config.bump => "Bump version".to_string(),
+            None => "Set version".to_string(),
+        }
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize SetVersion config: {}. Using defaults.",
+                e
+            );
+            SetVersionConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("SetVersion-{:?}", self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_suffix_drops_old_commit() {
+        assert_eq!(
+            SetVersion::version_suffix("Version 1.001;git-1a2b3c4; ttfautohint (v1.8.4)"),
+            "; ttfautohint (v1.8.4)"
+        );
+        assert_eq!(SetVersion::version_suffix("Version 1.001"), "");
+    }
+}
@@ -0,0 +1,162 @@
+use std::{os::unix::process::ExitStatusExt, process::Output};
+
+use read_fonts::{
+    FontRef, TableProvider,
+    tables::{head::MacStyle, os2::SelectionFlags},
+    types::NameId,
+};
+use write_fonts::{
+    FontBuilder,
+    from_obj::ToOwnedTable,
+    tables::{
+        head::Head,
+        name::{Name, NameRecord},
+        os2::Os2,
+    },
+};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+const RIBBI: [&str; 4] = ["Regular", "Italic", "Bold", "Bold Italic"];
+
+/// Make a static font link into its family's Regular/Italic/Bold/Bold Italic group
+///
+/// The style is taken from the typographic subfamily name (or the subfamily
+/// name if there isn't one). OS/2 fsSelection and head.macStyle get the
+/// bold and italic bits to match, and the names are rearranged so that name
+/// ID 2 is always one of the four RIBBI styles: "Light Italic" in family
+/// "Foo" becomes style "Italic" in family "Foo Light", with typographic names
+/// "Foo" and "Light Italic".
+#[derive(PartialEq, Debug)]
+pub(crate) struct FixStyleLinking;
+
+impl FixStyleLinking {
+    /// The English Windows name with the given ID
+    fn english_name(name: &Name, name_id: NameId) -> Option<String> {
+        name.name_record
+            .iter()
+            .find(|record| {
+                record.platform_id == 3 && record.language_id == 0x0409 && record.name_id == name_id
+            })
+            .map(|record| record.string.as_str().to_string())
+    }
+
+    /// Set (or with `None`, remove) the English Windows name with the given ID
+    fn set_name(name: &mut Name, name_id: NameId, string: Option<&str>) {
+        let mut records = std::mem::take(&mut name.name_record)
+            .into_iter()
+            .filter(|record| {
+                !(record.platform_id == 3
+                    && record.language_id == 0x0409
+                    && record.name_id == name_id)
+            })
+            .collect::<Vec<_>>();
+        if let Some(string) = string {
+            records.push(NameRecord::new(
+                3,
+                1,
+                0x0409,
+                name_id,
+                string.to_string().into(),
+            ));
+        }
+        name.name_record = records.into_iter().collect();
+    }
+}
+
+impl Operation for FixStyleLinking {
+    fn shortname(&self) -> &str {
+        "FixStyleLinking"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        let mut name: Name = font.name()?.to_owned_table();
+        let family = Self::english_name(&name, NameId::TYPOGRAPHIC_FAMILY_NAME)
+            .or_else(|| Self::english_name(&name, NameId::FAMILY_NAME))
+            .ok_or_else(|| ApplicationError::Other("Font has no family name".to_string()))?;
+        let style = Self::english_name(&name, NameId::TYPOGRAPHIC_SUBFAMILY_NAME)
+            .or_else(|| Self::english_name(&name, NameId::SUBFAMILY_NAME))
+            .unwrap_or_else(|| "Regular".to_string());
+        let italic = style.contains("Italic");
+        let bold = style == "Bold" || style == "Bold Italic";
+
+        if RIBBI.contains(&style.as_str()) {
+            Self::set_name(&mut name, NameId::FAMILY_NAME, Some(&family));
+            Self::set_name(&mut name, NameId::SUBFAMILY_NAME, Some(&style));
+            Self::set_name(&mut name, NameId::TYPOGRAPHIC_FAMILY_NAME, None);
+            Self::set_name(&mut name, NameId::TYPOGRAPHIC_SUBFAMILY_NAME, None);
+        } else {
+            let weight = style.replace("Italic", "").trim().to_string();
+            Self::set_name(
+                &mut name,
+                NameId::FAMILY_NAME,
+                Some(&format!("{family} {weight}")),
+            );
+            Self::set_name(
+                &mut name,
+                NameId::SUBFAMILY_NAME,
+                Some(if italic { "Italic" } else { "Regular" }),
+            );
+            Self::set_name(&mut name, NameId::TYPOGRAPHIC_FAMILY_NAME, Some(&family));
+            Self::set_name(&mut name, NameId::TYPOGRAPHIC_SUBFAMILY_NAME, Some(&style));
+        }
+
+        let mut os2: Os2 = font.os2()?.to_owned_table();
+        os2.fs_selection
+            .remove(SelectionFlags::ITALIC | SelectionFlags::BOLD | SelectionFlags::REGULAR);
+        if italic {
+            os2.fs_selection |= SelectionFlags::ITALIC;
+        }
+        if bold {
+            os2.fs_selection |= SelectionFlags::BOLD;
+        }
+        if !italic && !bold {
+            os2.fs_selection |= SelectionFlags::REGULAR;
+        }
+        let mut head: Head = font.head()?.to_owned_table();
+        head.mac_style.remove(MacStyle::BOLD | MacStyle::ITALIC);
+        if italic {
+            head.mac_style |= MacStyle::ITALIC;
+        }
+        if bold {
+            head.mac_style |= MacStyle::BOLD;
+        }
+
+        let mut builder = FontBuilder::new();
+        builder.add_table(&name)?;
+        builder.add_table(&os2)?;
+        builder.add_table(&head)?;
+        builder.copy_missing_tables(font);
+        outputs[0].set_contents(builder.build())?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Fix style linking".to_string()
+    }
+
+    fn identifier(&self) -> String {
+        "FixStyleLinking".to_string()
+    }
+}
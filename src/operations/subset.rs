@@ -0,0 +1,171 @@
+use std::{collections::HashMap, os::unix::process::ExitStatusExt, process::Output};
+
+use google_fonts_glyphsets::GLYPHSETS;
+use read_fonts::{
+    FontRef, TableProvider,
+    collections::int_set::IntSet,
+    types::{GlyphId, GlyphId16, NameId, Tag},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use skera::{Plan, subset_font};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+    recipe_providers::includesubsets::UnicodeRange,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubsetConfig {
+    /// A named glyphset whose codepoints are kept, e.g. `GF_Latin_Core`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glyphset: Option<String>,
+    /// Ranges of codepoints to keep
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ranges: Vec<UnicodeRange>,
+    /// Names of glyphs to keep as well as those reached from the codepoints
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub glyphs: Vec<String>,
+    /// Layout features to keep; if none are given, all are kept
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layout_features: Vec<String>,
+}
+
+/// Reduce a font to a subset of its codepoints, glyphs and layout features
+///
+/// If no codepoints or glyphs are asked for, all of them are kept, so a step
+/// which only lists `layoutFeatures` just drops the other features.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Subset {
+    config: SubsetConfig,
+}
+
+impl Subset {
+    pub fn new() -> Self {
+        Subset {
+            config: SubsetConfig::default(),
+        }
+    }
+
+    fn codepoints(&self) -> Result<IntSet<u32>, ApplicationError> {
+        let mut codepoints = IntSet::empty();
+        if let Some(name) = &self.config.glyphset {
+            let glyphset = GLYPHSETS.get(name.as_str()).ok_or_else(|| {
+                ApplicationError::InvalidRecipe(format!("Unknown glyphset name: {}", name))
+            })?;
+            codepoints.extend(glyphset.iter_codepoints());
+        }
+        for range in &self.config.ranges {
+            codepoints.insert_range(range.start..=range.end);
+        }
+        Ok(codepoints)
+    }
+
+    fn glyph_ids(&self, font: &FontRef) -> Result<IntSet<GlyphId>, ApplicationError> {
+        let mut glyph_ids = IntSet::empty();
+        if self.config.glyphs.is_empty() {
+            return Ok(glyph_ids);
+        }
+        let post = font.post()?;
+        let num_glyphs = font.maxp()?.num_glyphs();
+        for gid in 0..num_glyphs {
+            let gid = GlyphId16::new(gid);
+            if post
+                .glyph_name(gid)
+                .is_some_and(|name| self.config.glyphs.iter().any(|wanted| wanted == name))
+            {
+                glyph_ids.insert(gid.into());
+            }
+        }
+        Ok(glyph_ids)
+    }
+
+    fn layout_features(&self) -> Result<IntSet<Tag>, ApplicationError> {
+        if self.config.layout_features.is_empty() {
+            return Ok(IntSet::all());
+        }
+        let mut features = IntSet::empty();
+        for feature in &self.config.layout_features {
+            features.insert(Tag::new_checked(feature.as_bytes()).map_err(|e| {
+                ApplicationError::InvalidRecipe(format!("Bad feature tag {feature}: {e}"))
+            })?);
+        }
+        Ok(features)
+    }
+}
+
+impl Operation for Subset {
+    fn shortname(&self) -> &str {
+        "Subset"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        let mut codepoints = self.codepoints()?;
+        let mut glyph_ids = self.glyph_ids(&font)?;
+        if self.config.glyphset.is_none()
+            && self.config.ranges.is_empty()
+            && self.config.glyphs.is_empty()
+        {
+            codepoints = IntSet::all();
+            glyph_ids = IntSet::all();
+        }
+        let plan = Plan::new(
+            &glyph_ids,
+            &codepoints,
+            &font,
+            skera::SubsetFlags::SUBSET_FLAGS_DEFAULT | skera::SubsetFlags::SUBSET_FLAGS_GLYPH_NAMES,
+            &IntSet::<Tag>::empty(),
+            &IntSet::<Tag>::all(),
+            &self.layout_features()?,
+            &IntSet::<NameId>::all(),
+            &IntSet::<u16>::all(),
+            &None,
+        );
+        let subset = subset_font(&font, &plan)
+            .map_err(|e| ApplicationError::Other(format!("subset failed: {}", e)))?;
+        outputs[0].set_contents(subset)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        match &self.config.glyphset {
+            Some(glyphset) => format!("Subset to {glyphset}"),
+            None => "Subset font".to_string(),
+        }
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize Subset config: {}. Using defaults.",
+                e
+            );
+            SubsetConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("Subset-{:?}", self.config)
+    }
+}
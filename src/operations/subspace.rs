@@ -11,6 +11,36 @@ use skera::{Plan, parse_instancing_spec, subset_font};
 use std::{os::unix::process::ExitStatusExt, process::Output};
 use tracing::info_span;
 
+/// Instance a variable font according to a skera instancing spec such as
+/// `wght=400:700,wdth=100`
+pub(crate) fn instance_font(bytes: &[u8], spec: &str) -> Result<Vec<u8>, ApplicationError> {
+    let spec = parse_instancing_spec(spec).map_err(|e| {
+        ApplicationError::InvalidRecipe(format!("Bad instancing spec '{spec}': {e:?}"))
+    })?;
+    let fontref = FontRef::new(bytes)?;
+    let plan = Plan::new(
+        &IntSet::<GlyphId>::all(),
+        &IntSet::<u32>::all(),
+        &fontref,
+        skera::SubsetFlags::SUBSET_FLAGS_DEFAULT
+            | skera::SubsetFlags::SUBSET_FLAGS_UPDATE_NAME_TABLE
+            | skera::SubsetFlags::SUBSET_FLAGS_GLYPH_NAMES,
+        &IntSet::<Tag>::empty(),
+        &IntSet::<Tag>::all(),
+        &IntSet::<Tag>::all(),
+        &IntSet::<NameId>::all(),
+        &IntSet::<u16>::all(),
+        &Some(spec),
+    );
+    subset_font(&fontref, &plan)
+        .map_err(|e| ApplicationError::Other(format!("subspace failed: {}", e)))
+}
+
+/// Restrict the axes of a variable font
+///
+/// The arguments give a range, a location or `drop` for each axis to be
+/// changed, separated by spaces or commas: `wght=400:700 ital=1`. Axes which
+/// aren't mentioned are left alone.
 #[derive(PartialEq, Debug)]
 pub(crate) struct Subspace {
     args: Option<String>,
@@ -20,6 +50,26 @@ impl Subspace {
     pub fn new() -> Self {
         Subspace { args: None }
     }
+
+    /// The arguments as the comma-separated spec skera expects
+    fn spec(&self) -> Result<String, ApplicationError> {
+        let axes = self
+            .args
+            .as_deref()
+            .unwrap_or("")
+            .split([',', ' '])
+            .filter(|axis| !axis.is_empty())
+            .collect::<Vec<_>>();
+        if let Some(bad) = axes.iter().find(|axis| {
+            axis.split_once('=')
+                .is_none_or(|(tag, value)| tag.is_empty() || tag.len() > 4 || value.is_empty())
+        }) {
+            return Err(ApplicationError::InvalidRecipe(format!(
+                "Bad axis spec '{bad}' for subspace; expected e.g. wght=400:700, ital=1 or wdth=drop"
+            )));
+        }
+        Ok(axes.join(","))
+    }
 }
 
 impl Operation for Subspace {
@@ -42,35 +92,12 @@ impl Operation for Subspace {
     ) -> Result<Output, ApplicationError> {
         let _span = info_span!("subspace").entered();
         let bytes = inputs[0].to_bytes()?;
-        let spec = parse_instancing_spec(self.args.as_deref().unwrap_or("")).unwrap();
-
-        let fontref = FontRef::new(&bytes)?;
-        let plan = Plan::new(
-            &IntSet::<GlyphId>::all(),
-            &IntSet::<u32>::all(),
-            &fontref,
-            skera::SubsetFlags::SUBSET_FLAGS_DEFAULT
-                | skera::SubsetFlags::SUBSET_FLAGS_UPDATE_NAME_TABLE
-                | skera::SubsetFlags::SUBSET_FLAGS_GLYPH_NAMES,
-            &IntSet::<Tag>::empty(),
-            &IntSet::<Tag>::all(),
-            &IntSet::<Tag>::all(),
-            &IntSet::<NameId>::all(),
-            &IntSet::<u16>::all(),
-            &Some(spec),
-        );
-
-        match subset_font(&fontref, &plan) {
-            Ok(bytes) => {
-                outputs[0].set_contents(bytes)?;
-                Ok(Output {
-                    status: std::process::ExitStatus::from_raw(0),
-                    stdout: Vec::new(),
-                    stderr: Vec::new(),
-                })
-            }
-            Err(e) => Err(ApplicationError::Other(format!("subspace failed: {}", e))),
-        }
+        outputs[0].set_contents(instance_font(&bytes, &self.spec()?)?)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        })
     }
 
     fn description(&self) -> String {
@@ -0,0 +1,107 @@
+use std::{collections::HashMap, os::unix::process::ExitStatusExt, process::Output};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TTFAutohintConfig {
+    /// If ttfautohint fails, pass the font through unhinted instead of failing the build
+    #[serde(default)]
+    pub fallback_to_unhinted: bool,
+}
+
+/// Hint a font by shelling out to ttfautohint
+#[derive(PartialEq, Debug)]
+pub(crate) struct TTFAutohint {
+    args: Option<String>,
+    config: TTFAutohintConfig,
+}
+
+impl TTFAutohint {
+    pub fn new() -> Self {
+        TTFAutohint {
+            args: None,
+            config: TTFAutohintConfig::default(),
+        }
+    }
+}
+
+impl Operation for TTFAutohint {
+    fn shortname(&self) -> &str {
+        "TTFAutohint"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Path]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let mut cmd = "ttfautohint".to_string();
+        if let Some(args) = &self.args {
+            cmd.push(' ');
+            cmd.push_str(args);
+        }
+        cmd.push_str(&format!(
+            " {} {}",
+            inputs[0].to_filename(Some(".ttf"))?,
+            outputs[0].to_filename(Some(".ttf"))?
+        ));
+        let output = self.run_shell_command(&cmd, outputs)?;
+        if output.status.success() || !self.config.fallback_to_unhinted {
+            return Ok(output);
+        }
+        log::warn!(
+            "ttfautohint failed, so leaving the font unhinted: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        outputs[0].set_contents(inputs[0].to_bytes()?)?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            ..output
+        })
+    }
+
+    fn description(&self) -> String {
+        match &self.args {
+            Some(args) => format!("Autohint with ttfautohint {}", args),
+            None => "Autohint with ttfautohint".to_string(),
+        }
+    }
+
+    fn set_args(&mut self, args: Option<String>) {
+        self.args = args;
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize TTFAutohint config: {}. Using defaults.",
+                e
+            );
+            TTFAutohintConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!(
+            "TTFAutohint-{}-{}",
+            self.args.as_deref().unwrap_or(""),
+            self.config.fallback_to_unhinted
+        )
+    }
+}
@@ -0,0 +1,191 @@
+use std::{os::unix::process::ExitStatusExt, process::Output};
+
+use read_fonts::{
+    FontRef, ReadError, TableProvider,
+    types::{GlyphId, Tag},
+};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+/// Tables every TrueType font must have
+const REQUIRED_TABLES: [Tag; 8] = [
+    Tag::new(b"cmap"),
+    Tag::new(b"head"),
+    Tag::new(b"hhea"),
+    Tag::new(b"hmtx"),
+    Tag::new(b"maxp"),
+    Tag::new(b"name"),
+    Tag::new(b"OS/2"),
+    Tag::new(b"post"),
+];
+
+/// Check that a font is structurally sound before it is shipped
+///
+/// Every table fontations knows about is parsed, along with the parts of it
+/// which are only read on demand (name strings, cmap subtables, every glyph
+/// and its variations, layout lookups). The font is passed through
+/// unchanged if all is well; otherwise the build fails with a list of the
+/// problems. This is no substitute for a QA profile, but catches corrupt
+/// output cheaply.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Validate;
+
+impl Validate {
+    fn check_table(font: &FontRef, tag: Tag) -> Result<(), ReadError> {
+        match &tag.to_be_bytes() {
+            b"head" => {
+                font.head()?;
+            }
+            b"hhea" => {
+                font.hhea()?;
+            }
+            b"maxp" => {
+                font.maxp()?;
+            }
+            b"OS/2" => {
+                font.os2()?;
+            }
+            b"post" => {
+                font.post()?;
+            }
+            b"hmtx" => {
+                font.hmtx()?;
+            }
+            b"name" => {
+                let name = font.name()?;
+                for record in name.name_record() {
+                    record.string(name.string_data())?;
+                }
+            }
+            b"cmap" => {
+                let cmap = font.cmap()?;
+                for record in cmap.encoding_records() {
+                    record.subtable(cmap.offset_data())?;
+                }
+            }
+            b"glyf" => {
+                let glyf = font.glyf()?;
+                let loca = font.loca(None)?;
+                for gid in 0..font.maxp()?.num_glyphs() {
+                    loca.get_glyf(GlyphId::new(gid as u32), &glyf)?;
+                }
+            }
+            b"gvar" => {
+                let gvar = font.gvar()?;
+                for gid in 0..font.maxp()?.num_glyphs() {
+                    gvar.glyph_variation_data(GlyphId::new(gid as u32))?;
+                }
+            }
+            b"fvar" => {
+                let fvar = font.fvar()?;
+                fvar.axes()?;
+                fvar.instances()?;
+            }
+            b"avar" => {
+                font.avar()?;
+            }
+            b"STAT" => {
+                let stat = font.stat()?;
+                stat.design_axes()?;
+                stat.offset_to_axis_values().transpose()?;
+            }
+            b"GDEF" => {
+                font.gdef()?;
+            }
+            b"GSUB" => {
+                let gsub = font.gsub()?;
+                gsub.script_list()?;
+                gsub.feature_list()?;
+                for lookup in gsub.lookup_list()?.lookups().iter() {
+                    lookup?;
+                }
+            }
+            b"GPOS" => {
+                let gpos = font.gpos()?;
+                gpos.script_list()?;
+                gpos.feature_list()?;
+                for lookup in gpos.lookup_list()?.lookups().iter() {
+                    lookup?;
+                }
+            }
+            b"HVAR" => {
+                font.hvar()?;
+            }
+            b"MVAR" => {
+                font.mvar()?;
+            }
+            b"COLR" => {
+                font.colr()?;
+            }
+            b"CPAL" => {
+                font.cpal()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn problems(font: &FontRef) -> Vec<String> {
+        let mut problems = REQUIRED_TABLES
+            .iter()
+            .filter(|tag| font.table_data(**tag).is_none())
+            .map(|tag| format!("missing {tag} table"))
+            .collect::<Vec<_>>();
+        for record in font.table_directory.table_records() {
+            let tag = record.tag();
+            if font.table_data(tag).is_none() {
+                problems.push(format!("{tag} table lies outside the file"));
+                continue;
+            }
+            if let Err(e) = Self::check_table(font, tag) {
+                problems.push(format!("{tag}: {e}"));
+            }
+        }
+        problems
+    }
+}
+
+impl Operation for Validate {
+    fn shortname(&self) -> &str {
+        "Validate"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font =
+            FontRef::new(&bytes).map_err(|e| ApplicationError::InvalidFont(e.to_string()))?;
+        let problems = Self::problems(&font);
+        if !problems.is_empty() {
+            return Err(ApplicationError::InvalidFont(problems.join("; ")));
+        }
+        outputs[0].set_contents(bytes.clone())?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Validate font structure".to_string()
+    }
+
+    fn identifier(&self) -> String {
+        "Validate".to_string()
+    }
+}
@@ -0,0 +1,132 @@
+use std::{collections::HashMap, os::unix::process::ExitStatusExt, process::Output};
+
+use read_fonts::{FontRef, TableProvider, tables::os2::SelectionFlags};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use write_fonts::{
+    FontBuilder,
+    from_obj::ToOwnedTable,
+    tables::{hhea::Hhea, os2::Os2},
+    types::FWord,
+};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VerticalMetricsConfig {
+    /// The typo ascender to set; if not given, the font's own is kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub typo_ascender: Option<i16>,
+    /// The typo descender to set; if not given, the font's own is kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub typo_descender: Option<i16>,
+    /// The typo line gap to set; if not given, the font's own is kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub typo_line_gap: Option<i16>,
+    /// Keep the existing usWinAscent and usWinDescent
+    #[serde(default)]
+    pub preserve_win: bool,
+    /// Keep the existing hhea ascender, descender and line gap
+    #[serde(default)]
+    pub preserve_hhea: bool,
+}
+
+/// Normalize a font's vertical metrics following the Google Fonts spec
+///
+/// The typo metrics are the source of truth: USE_TYPO_METRICS is set, the hhea
+/// metrics are copied from the typo ones, and the win metrics are set from the
+/// font's bounding box so that nothing is clipped on Windows. The bounding box
+/// is this font's own; for a family, the win metrics should really cover the
+/// tallest and deepest glyphs of all its fonts.
+#[derive(PartialEq, Debug)]
+pub(crate) struct VerticalMetrics {
+    config: VerticalMetricsConfig,
+}
+
+impl VerticalMetrics {
+    pub fn new() -> Self {
+        VerticalMetrics {
+            config: VerticalMetricsConfig::default(),
+        }
+    }
+}
+
+impl Operation for VerticalMetrics {
+    fn shortname(&self) -> &str {
+        "VerticalMetrics"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let bytes = inputs[0].to_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        let head = font.head()?;
+        let mut os2: Os2 = font.os2()?.to_owned_table();
+        let mut hhea: Hhea = font.hhea()?.to_owned_table();
+
+        if let Some(ascender) = self.config.typo_ascender {
+            os2.s_typo_ascender = ascender;
+        }
+        if let Some(descender) = self.config.typo_descender {
+            os2.s_typo_descender = descender;
+        }
+        if let Some(line_gap) = self.config.typo_line_gap {
+            os2.s_typo_line_gap = line_gap;
+        }
+        os2.fs_selection |= SelectionFlags::USE_TYPO_METRICS;
+        if !self.config.preserve_win {
+            os2.us_win_ascent = head.y_max().max(0) as u16;
+            os2.us_win_descent = head.y_min().min(0).unsigned_abs();
+        }
+        if !self.config.preserve_hhea {
+            hhea.ascender = FWord::new(os2.s_typo_ascender);
+            hhea.descender = FWord::new(os2.s_typo_descender);
+            hhea.line_gap = FWord::new(os2.s_typo_line_gap);
+        }
+
+        let mut builder = FontBuilder::new();
+        builder.add_table(&os2)?;
+        builder.add_table(&hhea)?;
+        builder.copy_missing_tables(font);
+        outputs[0].set_contents(builder.build())?;
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Fix vertical metrics".to_string()
+    }
+
+    fn set_extra(&mut self, extra: HashMap<String, Value>) {
+        let value = Value::Object(extra.into_iter().collect());
+        self.config = serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to deserialize VerticalMetrics config: {}. Using defaults.",
+                e
+            );
+            VerticalMetricsConfig::default()
+        });
+    }
+
+    fn identifier(&self) -> String {
+        format!("VerticalMetrics-{:?}", self.config)
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    io::Write,
+    os::unix::process::ExitStatusExt,
+    process::{ExitStatus, Output},
+};
+
+use flate2::{Compression, write::ZlibEncoder};
+use read_fonts::{FontRef, TableProvider};
+
+use crate::{
+    buildsystem::{DataKind, Operation, OperationOutput},
+    error::ApplicationError,
+};
+
+const WOFF_HEADER_SIZE: usize = 44;
+const WOFF_TABLE_ENTRY_SIZE: usize = 20;
+
+fn padded(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+/// Wrap a font in a WOFF (version 1) container
+///
+/// Each table is compressed with zlib, unless that would make it bigger, in
+/// which case it is stored as it is. No metadata or private blocks are written.
+pub(crate) fn encode_woff(font: &FontRef) -> Result<Vec<u8>, ApplicationError> {
+    let mut records = font.table_directory.table_records().to_vec();
+    records.sort_by_key(|record| record.tag());
+    let num_tables = records.len();
+
+    let mut entries = Vec::with_capacity(num_tables * WOFF_TABLE_ENTRY_SIZE);
+    let mut data = Vec::new();
+    let data_start = WOFF_HEADER_SIZE + num_tables * WOFF_TABLE_ENTRY_SIZE;
+    let mut total_sfnt_size = 12 + 16 * num_tables;
+    for record in &records {
+        let tag = record.tag();
+        let table = font
+            .table_data(tag)
+            .ok_or_else(|| ApplicationError::FontReadError(format!("Missing {tag} table")))?;
+        let table = table.as_bytes();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(table)?;
+        let compressed = encoder.finish()?;
+        let stored = if compressed.len() < table.len() {
+            compressed.as_slice()
+        } else {
+            table
+        };
+
+        entries.extend_from_slice(&tag.to_be_bytes());
+        entries.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+        entries.extend_from_slice(&(stored.len() as u32).to_be_bytes());
+        entries.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        entries.extend_from_slice(&record.checksum().to_be_bytes());
+
+        data.extend_from_slice(stored);
+        data.resize(padded(data.len()), 0);
+        total_sfnt_size += padded(table.len());
+    }
+
+    let revision = font.head()?.font_revision().to_bits();
+    let length = data_start + data.len();
+    let mut woff = Vec::with_capacity(length);
+    woff.extend_from_slice(b"wOFF");
+    woff.extend_from_slice(&font.table_directory.sfnt_version().to_be_bytes());
+    woff.extend_from_slice(&(length as u32).to_be_bytes());
+    woff.extend_from_slice(&(num_tables as u16).to_be_bytes());
+    woff.extend_from_slice(&0u16.to_be_bytes());
+    woff.extend_from_slice(&(total_sfnt_size as u32).to_be_bytes());
+    woff.extend_from_slice(&((revision >> 16) as u16).to_be_bytes());
+    woff.extend_from_slice(&(revision as u16).to_be_bytes());
+    // Metadata and private block offsets and lengths
+    woff.extend_from_slice(&[0; 20]);
+    woff.extend_from_slice(&entries);
+    woff.extend_from_slice(&data);
+    Ok(woff)
+}
+
+/// Convert a font to classic WOFF, for places which still don't take WOFF2
+#[derive(PartialEq, Debug)]
+pub(crate) struct Woff;
+
+impl Operation for Woff {
+    fn shortname(&self) -> &str {
+        "Woff"
+    }
+
+    fn input_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn output_kinds(&self) -> Vec<DataKind> {
+        vec![DataKind::Bytes]
+    }
+
+    fn execute(
+        &self,
+        inputs: &[OperationOutput],
+        outputs: &[OperationOutput],
+    ) -> Result<Output, ApplicationError> {
+        let input_file = inputs
+            .first()
+            .ok_or_else(|| ApplicationError::WrongInputs("No input file provided".to_string()))?;
+        let bytes = input_file.to_mapped_bytes()?;
+        let font = FontRef::new(&bytes)?;
+        outputs[0].set_contents(encode_woff(&font)?)?;
+        Ok(Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+
+    fn description(&self) -> String {
+        "Convert to woff".to_string()
+    }
+}
@@ -1,72 +1,50 @@
-use serde::{Deserialize, Serialize};
+use schemars::{JsonSchema, Schema, SchemaGenerator};
+use serde::{
+    Deserialize, Serialize,
+    de::{self, DeserializeOwned, MapAccess, SeqAccess, Visitor},
+};
 use serde_json::Value;
-use std::{collections::HashMap, sync::Arc};
+use std::{borrow::Cow, collections::HashMap, fmt, sync::Arc};
 use tracing::info_span;
 
 use crate::{
-    buildsystem::{BuildGraph, BuildStep},
+    buildsystem::{BuildGraph, BuildStep, WithEnvironment, parse_cwd, parse_env},
     error::ApplicationError,
-    operations::OpStep,
-    recipe_providers::{
-        googlefonts::{GoogleFontsOptions, GoogleFontsProvider},
-        noto::{NotoOptions, NotoProvider},
-    },
+    operations::{ConfigOperationBuilder, OpStep},
+    recipe_providers::{googlefonts::GoogleFontsOptions, make_provider},
 };
 
-/// Enum representing different recipe providers as stored in the config file
-///
-/// This is used during recipe deserialization to handle different provider-specific options.
-/// We first determine which provider is requested, then parse its options separately
-/// to provide clear error messages.
-
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
-#[serde(rename_all = "lowercase")]
-#[derive(Default)]
-enum RecipeProviderTag {
-    #[default]
-    GoogleFonts,
-    Noto,
-}
+/// The provider used when a config has neither a recipe nor a `recipeProvider`
+const DEFAULT_PROVIDER: &str = "googlefonts";
 
-pub(crate) trait Provider {
+/// Something which generates a recipe from a config file's options
+///
+/// Providers are looked up by the name given in `recipeProvider:`; see
+/// [`crate::recipe_providers::register_provider`] for adding new ones.
+pub trait Provider {
     fn generate_recipe(&self) -> Result<Recipe, ApplicationError>;
 }
 
-/// Parse provider-specific options with clear error reporting
-fn parse_provider_options(
-    tag: &RecipeProviderTag,
-    raw_config: &serde_yaml_ng::Value,
-) -> Result<Box<dyn Provider>, ApplicationError> {
-    match tag {
-        RecipeProviderTag::GoogleFonts => {
-            let options: GoogleFontsOptions = serde_yaml_ng::from_value(raw_config.clone())
-                .map_err(|e| {
-                    ApplicationError::InvalidRecipe(format!(
-                        "Failed to parse GoogleFonts provider options: {}",
-                        e
-                    ))
-                })?;
-            Ok(Box::new(GoogleFontsProvider::new(options)))
-        }
-        RecipeProviderTag::Noto => {
-            let options: NotoOptions =
-                serde_yaml_ng::from_value(raw_config.clone()).map_err(|e| {
-                    ApplicationError::InvalidRecipe(format!(
-                        "Failed to parse Noto provider options: {}",
-                        e
-                    ))
-                })?;
-            Ok(Box::new(NotoProvider::new(options)))
+/// Deserialize a YAML value, prefixing any error with the key path at which it occurred
+pub(crate) fn from_value_with_path<T: DeserializeOwned, E: de::Error>(
+    value: serde_yaml_ng::Value,
+) -> Result<T, E> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let path = e.path().to_string();
+        if path == "." {
+            E::custom(e.into_inner())
+        } else {
+            E::custom(format!("{path}: {}", e.into_inner()))
         }
-    }
+    })
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[derive(Serialize, JsonSchema, PartialEq, Debug, Clone)]
 #[serde(untagged)]
 pub(crate) enum Step {
     OperationStep {
         operation: OpStep,
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         args: Option<String>,
         #[serde(default, skip_serializing_if = "Option::is_none")]
         input_file: Option<String>,
@@ -80,10 +58,104 @@ pub(crate) enum Step {
         #[serde(flatten)]
         extra: HashMap<String, Value>,
     },
+    /// A reference to a named sequence of steps in the config's `stepSets`
+    StepSetStep {
+        #[serde(rename = "stepSet")]
+        step_set: String,
+    },
+}
+
+// Steps are told apart by their keys rather than with `#[serde(untagged)]`, so
+// that a malformed step reports what is actually wrong with it instead of
+// "data did not match any variant of untagged enum Step".
+impl<'de> Deserialize<'de> for Step {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct OperationStepHelper {
+            operation: OpStep,
+            #[serde(default)]
+            args: Option<String>,
+            #[serde(default)]
+            input_file: Option<String>,
+            #[serde(flatten)]
+            extra: HashMap<String, Value>,
+            #[serde(default)]
+            needs: Vec<String>,
+            /// Shorthand for a single extra input, e.g. the donor of `addSubset`
+            #[serde(default)]
+            from: Option<String>,
+            /// The other fonts of the family, for `buildStat`
+            #[serde(default)]
+            siblings: Vec<String>,
+        }
+        #[derive(Deserialize)]
+        struct SourceStepHelper {
+            source: String,
+            #[serde(flatten)]
+            extra: HashMap<String, Value>,
+        }
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct StepSetStepHelper {
+            #[serde(rename = "stepSet")]
+            step_set: String,
+        }
+
+        let value = serde_yaml_ng::Value::deserialize(deserializer)?;
+        if !value.is_mapping() {
+            return Err(de::Error::custom("a step must be a mapping"));
+        }
+        if value.get("operation").is_some() {
+            let step: OperationStepHelper = from_value_with_path(value)?;
+            if !step.siblings.is_empty() && step.operation != OpStep::BuildStat {
+                return Err(de::Error::custom(
+                    "`siblings` can only be given for a buildStat step",
+                ));
+            }
+            if let Some(env) = step.extra.get("env") {
+                parse_env(env.clone()).map_err(de::Error::custom)?;
+            }
+            if let Some(cwd) = step.extra.get("cwd") {
+                parse_cwd(cwd.clone()).map_err(de::Error::custom)?;
+            }
+            Ok(Step::OperationStep {
+                operation: step.operation,
+                args: step.args,
+                input_file: step.input_file,
+                extra: step.extra,
+                needs: step
+                    .from
+                    .into_iter()
+                    .chain(step.needs)
+                    .chain(step.siblings)
+                    .collect(),
+            })
+        } else if value.get("source").is_some() {
+            let step: SourceStepHelper = from_value_with_path(value)?;
+            Ok(Step::SourceStep {
+                source: step.source,
+                extra: step.extra,
+            })
+        } else if value.get("stepSet").is_some() {
+            let step: StepSetStepHelper = from_value_with_path(value)?;
+            Ok(Step::StepSetStep {
+                step_set: step.step_set,
+            })
+        } else {
+            Err(de::Error::custom(
+                "a step must have an `operation`, `source` or `stepSet` key",
+            ))
+        }
+    }
 }
 
 impl Step {
-    fn to_operation(&self) -> Result<(Option<String>, BuildStep, Vec<String>), ApplicationError> {
+    pub(crate) fn to_operation(
+        &self,
+    ) -> Result<(Option<String>, BuildStep, Vec<String>), ApplicationError> {
         match self {
             Step::OperationStep {
                 operation,
@@ -92,9 +164,19 @@ impl Step {
                 input_file,
                 needs,
             } => {
+                let mut extra = extra.clone();
+                let env = extra
+                    .remove("env")
+                    .map(parse_env)
+                    .transpose()?
+                    .unwrap_or_default();
+                let cwd = extra.remove("cwd").map(parse_cwd).transpose()?;
                 let mut op = operation.operation();
-                op.set_extra(extra.clone());
+                op.set_extra(extra);
                 op.set_args(args.clone());
+                if !env.is_empty() || cwd.is_some() {
+                    op = Box::new(WithEnvironment::new(op, env, cwd));
+                }
                 // Return the needs vector along with the operation
                 Ok((input_file.clone(), Arc::new(op), needs.clone()))
             }
@@ -104,16 +186,201 @@ impl Step {
                     "Source step not implemented: {source}"
                 )))
             }
+            Step::StepSetStep { step_set } => Err(ApplicationError::InvalidRecipe(format!(
+                "Step set '{step_set}' was not expanded"
+            ))),
         }
     }
 }
 
+/// Replace any `stepSet` references in a list of steps with the steps they name.
+///
+/// Step sets may themselves refer to other step sets; `stack` holds the names
+/// currently being expanded so that we can detect cycles.
+fn expand_step_sets(
+    steps: Vec<Step>,
+    step_sets: &HashMap<String, Vec<Step>>,
+    stack: &mut Vec<String>,
+) -> Result<Vec<Step>, ApplicationError> {
+    let mut expanded = vec![];
+    for step in steps {
+        let Step::StepSetStep { step_set } = step else {
+            expanded.push(step);
+            continue;
+        };
+        if stack.contains(&step_set) {
+            return Err(ApplicationError::InvalidRecipe(format!(
+                "Step set '{step_set}' refers to itself"
+            )));
+        }
+        let set_steps = step_sets.get(&step_set).ok_or_else(|| {
+            ApplicationError::InvalidRecipe(format!("Unknown step set '{step_set}'"))
+        })?;
+        stack.push(step_set);
+        expanded.extend(expand_step_sets(set_steps.clone(), step_sets, stack)?);
+        stack.pop();
+    }
+    Ok(expanded)
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct ConfigOperation(pub(crate) Vec<Step>);
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+/// An output format which a target in an explicit recipe can be expanded into
+#[derive(Deserialize, JsonSchema, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum TargetFormat {
+    Ttf,
+    Otf,
+    Woff,
+    Woff2,
+}
+
+impl TargetFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            TargetFormat::Ttf => "ttf",
+            TargetFormat::Otf => "otf",
+            TargetFormat::Woff => "woff",
+            TargetFormat::Woff2 => "woff2",
+        }
+    }
+
+    /// Add any steps needed to turn the output of `steps` into this format
+    ///
+    /// fontc can't write CFF outlines, so an OTF is compiled with `buildOTF`
+    /// in place of the `fontc` step, and doesn't share the compile with the
+    /// other formats.
+    fn steps(&self, mut steps: Vec<Step>) -> Result<ConfigOperation, String> {
+        if *self == TargetFormat::Otf {
+            let compile = steps.iter_mut().find(|step| {
+                matches!(
+                    step,
+                    Step::OperationStep {
+                        operation: OpStep::Fontc,
+                        ..
+                    }
+                )
+            });
+            let Some(Step::OperationStep {
+                operation,
+                args,
+                extra,
+                ..
+            }) = compile
+            else {
+                return Err(
+                    "The otf format needs a `fontc` step to replace with `buildOTF`".to_string(),
+                );
+            };
+            *operation = OpStep::BuildOTF;
+            // fontc's options mean nothing to fontmake
+            *args = None;
+            extra.clear();
+        }
+        let builder = ConfigOperationBuilder::new_from_steps(steps);
+        Ok(match self {
+            TargetFormat::Ttf | TargetFormat::Otf => builder.build(),
+            TargetFormat::Woff => builder.woff().build(),
+            TargetFormat::Woff2 => builder.compress().build(),
+        })
+    }
+}
+
+/// A target as written in an explicit recipe.
+///
+/// A target is usually just a list of steps, but it may instead be a mapping
+/// with `steps` and `formats`, in which case one target is generated per format
+/// (by replacing the target's extension), all sharing the same steps. Since the
+/// graph deduplicates common prefixes, the expensive compile is only done once.
+#[derive(JsonSchema)]
+#[serde(untagged)]
+enum TargetSpec {
+    Steps(Vec<Step>),
+    Matrix(TargetMatrix),
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct TargetMatrix {
+    steps: Vec<Step>,
+    formats: Vec<TargetFormat>,
+}
+
+// Dispatch on the shape of the target by hand, rather than with `#[serde(untagged)]`,
+// so that errors inside the steps keep their key path.
+impl<'de> Deserialize<'de> for TargetSpec {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct TargetSpecVisitor;
+
+        impl<'de> Visitor<'de> for TargetSpecVisitor {
+            type Value = TargetSpec;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a list of steps, or a mapping with `steps` and `formats`")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<TargetSpec, A::Error> {
+                let mut steps = vec![];
+                while let Some(step) = seq.next_element()? {
+                    steps.push(step);
+                }
+                Ok(TargetSpec::Steps(steps))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<TargetSpec, A::Error> {
+                TargetMatrix::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(TargetSpec::Matrix)
+            }
+        }
+
+        deserializer.deserialize_any(TargetSpecVisitor)
+    }
+}
+
+#[derive(Serialize, PartialEq, Debug, Clone, Default)]
 pub struct Recipe(pub HashMap<String, ConfigOperation>);
 
+impl<'de> Deserialize<'de> for Recipe {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let targets = HashMap::<String, TargetSpec>::deserialize(deserializer)?;
+        let mut expanded: Vec<(String, ConfigOperation)> = vec![];
+        for (target, spec) in targets {
+            match spec {
+                TargetSpec::Steps(steps) => expanded.push((target, ConfigOperation(steps))),
+                TargetSpec::Matrix(TargetMatrix { steps, formats }) => {
+                    for format in formats {
+                        let name = std::path::Path::new(&target)
+                            .with_extension(format.extension())
+                            .to_string_lossy()
+                            .to_string();
+                        let operation = format.steps(steps.clone()).map_err(|e| {
+                            serde::de::Error::custom(format!("Target '{target}': {e}"))
+                        })?;
+                        expanded.push((name, operation));
+                    }
+                }
+            }
+        }
+        let mut recipe = Recipe::new();
+        for (target, operation) in expanded {
+            if recipe.contains_key(&target) {
+                return Err(serde::de::Error::custom(format!(
+                    "Target '{target}' is defined more than once"
+                )));
+            }
+            recipe.insert(target, operation);
+        }
+        Ok(recipe)
+    }
+}
+
 impl Recipe {
     pub fn new() -> Self {
         Recipe(HashMap::new())
@@ -135,12 +402,89 @@ impl Recipe {
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
+    /// Make every `fix` step shell out to `gftools fix-font` instead of using
+    /// the built-in fixer
+    pub fn use_external_fix(&mut self) {
+        for operation in self.0.values_mut() {
+            for step in operation.0.iter_mut() {
+                if let Step::OperationStep {
+                    operation: OpStep::Fix,
+                    extra,
+                    ..
+                } = step
+                {
+                    extra.insert("useExternal".to_string(), Value::Bool(true));
+                }
+            }
+        }
+    }
+    /// Describe how `new` differs from this recipe
+    ///
+    /// Lists targets which were added or removed, and for targets present in
+    /// both, the steps removed and added to get from one to the other, in
+    /// order, so moving a step shows up too. Returns an empty string if the
+    /// recipes are the same.
+    pub fn diff(&self, new: &Recipe) -> String {
+        let describe = |step: &Step| serde_json::to_string(step).unwrap_or_default();
+        let mut targets: Vec<&String> = self.0.keys().chain(new.0.keys()).collect();
+        targets.sort();
+        targets.dedup();
+
+        let mut diff = String::new();
+        for target in targets {
+            match (self.0.get(target), new.0.get(target)) {
+                (Some(_), None) => diff.push_str(&format!("- {target}\n")),
+                (None, Some(_)) => diff.push_str(&format!("+ {target}\n")),
+                (Some(old_steps), Some(new_steps)) if old_steps != new_steps => {
+                    diff.push_str(&format!("~ {target}\n"));
+                    for (sign, step) in Self::diff_steps(&old_steps.0, &new_steps.0) {
+                        diff.push_str(&format!("    {sign} {}\n", describe(step)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        diff
+    }
+
+    /// The steps to remove from `old` and add to it to make `new`, in order,
+    /// keeping the longest common run of steps in place
+    fn diff_steps<'a>(old: &'a [Step], new: &'a [Step]) -> Vec<(char, &'a Step)> {
+        // common[i][j] is the length of the longest common subsequence of
+        // old[i..] and new[j..]
+        let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                common[i][j] = if old[i] == new[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        let mut changes = vec![];
+        while i < old.len() || j < new.len() {
+            if i < old.len() && j < new.len() && old[i] == new[j] {
+                i += 1;
+                j += 1;
+            } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+                changes.push(('-', &old[i]));
+                i += 1;
+            } else {
+                changes.push(('+', &new[j]));
+                j += 1;
+            }
+        }
+        changes
+    }
+
     pub fn to_graph(&self, debug_intermediates: bool) -> Result<BuildGraph, ApplicationError> {
         let _span = info_span!("generate_graph").entered();
         let mut graph = BuildGraph::new(debug_intermediates);
 
-        // Track dependencies: (step_node, needs_targets)
-        let mut dependencies: Vec<(petgraph::graph::NodeIndex, Vec<String>)> = Vec::new();
+        // Track dependencies: (step_node, needs_targets, reads_finished_targets)
+        let mut dependencies: Vec<(petgraph::graph::NodeIndex, Vec<String>, bool)> = Vec::new();
         let mut source_dependencies: Vec<(petgraph::graph::NodeIndex, String)> = Vec::new();
 
         for (target, operation) in self.0.iter() {
@@ -171,19 +515,32 @@ impl Recipe {
 
             // Add the path and get the nodes for each step
             let added_path = graph.add_path(source_filename, operations_for_path, target);
+            for (node, step) in added_path.op_nodes.iter().zip(operation.0.iter().skip(1)) {
+                let step = serde_json::to_value(step).map_err(|e| {
+                    ApplicationError::Other(format!("Could not serialize step: {e}"))
+                })?;
+                graph.set_step(*node, step);
+            }
 
             source_dependencies.push((added_path.entry_node, source_filename.to_string()));
 
             // Record dependencies with their corresponding nodes
-            for (step_idx, (_, _, needs)) in operations.iter().enumerate() {
+            for (step_idx, (_, op, needs)) in operations.iter().enumerate() {
                 if !needs.is_empty() && step_idx < added_path.op_nodes.len() {
-                    dependencies.push((added_path.op_nodes[step_idx], needs.clone()));
+                    dependencies.push((
+                        added_path.op_nodes[step_idx],
+                        needs.clone(),
+                        op.reads_finished_targets(),
+                    ));
                 }
             }
         }
 
-        // Now add dependency edges
-        for (target_node, needs) in dependencies {
+        // Now add dependency edges. The recipe is unordered, so operations
+        // which only read what they need go last, once the targets' final
+        // producers are known.
+        dependencies.sort_by_key(|(_, _, reads_finished_targets)| *reads_finished_targets);
+        for (target_node, needs, _) in dependencies {
             for (slot, need_target) in needs.iter().enumerate() {
                 // Input slot starts at 1 because slot 0 is the primary input from the path
                 graph.add_dependency(need_target, target_node, slot + 1)?;
@@ -194,6 +551,8 @@ impl Recipe {
             graph.add_source_dependency(&source_target, target_node)?;
         }
 
+        graph.merge_duplicates();
+        graph.validate()?;
         Ok(graph)
     }
 }
@@ -202,6 +561,9 @@ impl Recipe {
 pub struct Config {
     #[serde(default)]
     recipe: Recipe,
+    /// Glob patterns for targets which should not be built
+    #[serde(rename = "skipTargets", skip_serializing_if = "Vec::is_empty")]
+    skip_targets: Vec<String>,
     #[serde(skip)]
     provider: Option<Box<dyn Provider>>,
 }
@@ -210,44 +572,112 @@ pub struct Config {
 // 1. Separate provider tag detection from options parsing (for better error messages)
 // 2. Default to GoogleFonts when no provider and no recipe is specified
 // 3. Ignore provider when an explicit recipe is given
+// 4. Keep track of key paths in errors, which `#[serde(flatten)]` would lose
 impl<'de> Deserialize<'de> for Config {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        #[derive(Deserialize)]
-        struct ConfigHelper {
-            #[serde(default)]
-            recipe: Recipe,
-            #[serde(rename = "recipeProvider", default)]
-            recipe_provider_tag: Option<RecipeProviderTag>,
-            #[serde(flatten)]
-            raw_config: serde_yaml_ng::Value,
+        deserializer.deserialize_map(ConfigVisitor)
+    }
+}
+
+struct ConfigVisitor;
+
+impl<'de> Visitor<'de> for ConfigVisitor {
+    type Value = Config;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a config mapping")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Config, A::Error> {
+        let mut recipe = Recipe::new();
+        let mut recipe_provider: Option<String> = None;
+        let mut skip_targets = vec![];
+        let mut step_sets: HashMap<String, Vec<Step>> = HashMap::new();
+        // Everything else is left for the provider to parse
+        let mut raw_config = serde_yaml_ng::Mapping::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "recipe" => recipe = map.next_value()?,
+                "recipeProvider" => recipe_provider = map.next_value()?,
+                "skipTargets" => skip_targets = map.next_value()?,
+                "stepSets" => step_sets = map.next_value()?,
+                _ => {
+                    let value: serde_yaml_ng::Value = map.next_value()?;
+                    raw_config.insert(key.into(), value);
+                }
+            }
         }
 
-        let helper = ConfigHelper::deserialize(deserializer)?;
+        // Expand named step sets in the explicit recipe
+        for operation in recipe.0.values_mut() {
+            let steps = std::mem::take(&mut operation.0);
+            operation.0 =
+                expand_step_sets(steps, &step_sets, &mut vec![]).map_err(de::Error::custom)?;
+        }
 
         // If there's an explicit recipe, don't use a provider
-        let provider = if !helper.recipe.is_empty() {
+        let provider = if !recipe.is_empty() {
             None
         } else {
             // Determine which provider to use (default to GoogleFonts)
-            let tag = helper.recipe_provider_tag.unwrap_or_default();
+            let name = recipe_provider.as_deref().unwrap_or(DEFAULT_PROVIDER);
 
             // Parse provider-specific options with clear error messages
             Some(
-                parse_provider_options(&tag, &helper.raw_config)
-                    .map_err(serde::de::Error::custom)?,
+                make_provider(name, &serde_yaml_ng::Value::Mapping(raw_config))
+                    .map_err(de::Error::custom)?,
             )
         };
 
         Ok(Config {
-            recipe: helper.recipe,
+            recipe,
+            skip_targets,
             provider,
         })
     }
 }
 
+/// The layout of a config file, used to generate its JSON schema
+///
+/// Provider options are flattened into the top level of the config; the
+/// Google Fonts and Noto providers currently share the same options.
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct ConfigSchema {
+    /// An explicit recipe, mapping target filenames to the steps which build them
+    #[serde(default)]
+    recipe: HashMap<String, TargetSpec>,
+    /// The recipe provider used to generate a recipe when none is given
+    /// (`googlefonts`, the default, `noto` or `simple`)
+    #[serde(rename = "recipeProvider", default)]
+    recipe_provider: Option<String>,
+    /// Glob patterns for targets which should not be built
+    #[serde(rename = "skipTargets", default)]
+    skip_targets: Vec<String>,
+    /// Named sequences of steps which recipe targets can refer to with `stepSet`
+    #[serde(rename = "stepSets", default)]
+    step_sets: HashMap<String, Vec<Step>>,
+    /// Treat provider options which aren't recognised as errors rather than warnings
+    #[serde(default)]
+    strict: bool,
+    #[serde(flatten)]
+    provider_options: GoogleFontsOptions,
+}
+
+impl JsonSchema for Config {
+    fn schema_name() -> Cow<'static, str> {
+        "Config".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        ConfigSchema::json_schema(generator)
+    }
+}
+
 impl Config {
     pub fn recipe(&self) -> Result<Recipe, ApplicationError> {
         let _span = info_span!("generate_recipe").entered();
@@ -259,8 +689,26 @@ impl Config {
         };
         // If the user provided a recipe in the config, overlay it on top.
         recipe.extend(self.recipe.clone());
+        self.skip_targets(&mut recipe)?;
         Ok(recipe)
     }
+
+    /// Remove any targets matching the `skipTargets` patterns from the recipe
+    fn skip_targets(&self, recipe: &mut Recipe) -> Result<(), ApplicationError> {
+        for pattern in &self.skip_targets {
+            let glob = glob::Pattern::new(pattern).map_err(|e| {
+                ApplicationError::InvalidRecipe(format!(
+                    "Invalid skipTargets pattern {pattern}: {e}"
+                ))
+            })?;
+            let before = recipe.len();
+            recipe.0.retain(|target, _| !glob.matches(target));
+            if recipe.len() == before {
+                log::warn!("skipTargets pattern {} did not match any targets", pattern);
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -313,6 +761,669 @@ recipe:
         assert!(deserialized.recipe.contains_key("Nunito.designspace"));
     }
 
+    #[test]
+    fn test_format_matrix_expansion() {
+        let config = r#"
+recipe:
+    ../fonts/Nunito[wght].ttf:
+        steps:
+            - source: "Nunito.glyphs"
+            - operation: "fontc"
+        formats: [ttf, woff2]
+"#;
+        let deserialized: Config =
+            serde_yaml_ng::from_str(config).expect("Failed to deserialize YAML");
+        assert_eq!(deserialized.recipe.len(), 2);
+        let ttf = &deserialized.recipe.0["../fonts/Nunito[wght].ttf"];
+        let woff2 = &deserialized.recipe.0["../fonts/Nunito[wght].woff2"];
+        assert_eq!(ttf.0.len(), 2);
+        assert_eq!(woff2.0.len(), 3);
+        assert_eq!(woff2.0[..2], ttf.0[..]);
+        assert!(matches!(
+            woff2.0[2],
+            Step::OperationStep {
+                operation: OpStep::Compress,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_format_matrix_otf_and_woff() {
+        let config = r#"
+recipe:
+    ../fonts/Nunito-Regular.ttf:
+        steps:
+            - source: "Nunito.glyphs"
+            - operation: "fontc"
+              args: "--flatten-components"
+            - operation: "fix"
+        formats: [ttf, otf, woff]
+"#;
+        let deserialized: Config =
+            serde_yaml_ng::from_str(config).expect("Failed to deserialize YAML");
+        assert_eq!(deserialized.recipe.len(), 3);
+        let ttf = &deserialized.recipe.0["../fonts/Nunito-Regular.ttf"];
+        let otf = &deserialized.recipe.0["../fonts/Nunito-Regular.otf"];
+        let woff = &deserialized.recipe.0["../fonts/Nunito-Regular.woff"];
+        assert_eq!(otf.0.len(), 3);
+        assert!(matches!(
+            &otf.0[1],
+            Step::OperationStep {
+                operation: OpStep::BuildOTF,
+                args: None,
+                ..
+            }
+        ));
+        assert_eq!(otf.0[2], ttf.0[2]);
+        assert_eq!(woff.0[..3], ttf.0[..]);
+        assert!(matches!(
+            woff.0[3],
+            Step::OperationStep {
+                operation: OpStep::Woff,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_format_matrix_otf_needs_fontc() {
+        let config = r#"
+recipe:
+    Nunito.ttf:
+        steps:
+            - source: "Nunito-Regular.ttf"
+            - operation: "fix"
+        formats: [ttf, otf]
+"#;
+        let result: Result<Config, _> = serde_yaml_ng::from_str(config);
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("otf"), "{error}");
+    }
+
+    #[test]
+    fn test_format_matrix_clash_is_an_error() {
+        let config = r#"
+recipe:
+    Nunito.woff2:
+        - source: "Nunito.ttf"
+        - operation: "compress"
+    Nunito.ttf:
+        steps:
+            - source: "Nunito.glyphs"
+            - operation: "fontc"
+        formats: [ttf, woff2]
+"#;
+        let result: Result<Config, _> = serde_yaml_ng::from_str(config);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_skip_targets() {
+        let config = r#"
+skipTargets:
+    - "*.woff2"
+recipe:
+    Nunito.ttf:
+        steps:
+            - source: "Nunito.glyphs"
+            - operation: "fontc"
+        formats: [ttf, woff2]
+"#;
+        let deserialized: Config =
+            serde_yaml_ng::from_str(config).expect("Failed to deserialize YAML");
+        let recipe = deserialized.recipe().unwrap();
+        assert_eq!(recipe.len(), 1);
+        assert!(recipe.contains_key("Nunito.ttf"));
+    }
+
+    #[test]
+    fn test_add_subset_step() {
+        let config = r#"
+recipe:
+    Nunito.designspace:
+        - source: "Nunito.glyphs"
+        - operation: "addSubset"
+          from: "NotoSans.glyphs"
+          includeCodepoints: [1024, 1025]
+          layoutHandling: closure
+"#;
+        let deserialized: Config =
+            serde_yaml_ng::from_str(config).expect("Failed to deserialize YAML");
+        let recipe = deserialized.recipe().unwrap();
+        let Step::OperationStep { extra, needs, .. } = &recipe.0["Nunito.designspace"].0[1] else {
+            panic!("Expected an operation step");
+        };
+        assert_eq!(needs, &vec!["NotoSans.glyphs".to_string()]);
+        let config: crate::operations::addsubset::AddSubsetConfig =
+            serde_json::from_value(Value::Object(extra.clone().into_iter().collect()))
+                .expect("Failed to deserialize AddSubset config");
+        assert_eq!(config.include_codepoints, vec![0x0400, 0x0401]);
+        assert_eq!(config.layout_handling, fontmerge::LayoutHandling::Closure);
+    }
+
+    #[test]
+    fn test_buildstat_siblings() {
+        let config = r#"
+recipe:
+    Nunito[wght].ttf:
+        - source: "Nunito.glyphs"
+        - operation: "fontc"
+        - operation: "buildStat"
+          siblings: ["Nunito-Italic[wght].ttf"]
+    Nunito-Italic[wght].ttf:
+        - source: "Nunito-Italic.glyphs"
+        - operation: "fontc"
+"#;
+        let deserialized: Config =
+            serde_yaml_ng::from_str(config).expect("Failed to deserialize YAML");
+        let recipe = deserialized.recipe().unwrap();
+        let Step::OperationStep { needs, .. } = &recipe.0["Nunito[wght].ttf"].0[2] else {
+            panic!("Expected an operation step");
+        };
+        assert_eq!(needs, &vec!["Nunito-Italic[wght].ttf".to_string()]);
+
+        let config = r#"
+recipe:
+    Nunito[wght].ttf:
+        - source: "Nunito.glyphs"
+        - operation: "fontc"
+          siblings: ["Nunito-Italic[wght].ttf"]
+"#;
+        let result: Result<Config, _> = serde_yaml_ng::from_str(config);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_checksums_read_finished_targets() {
+        use petgraph::visit::EdgeRef;
+
+        let config = r#"
+recipe:
+    Nunito[wght].ttf:
+        - source: "Nunito.glyphs"
+        - operation: "fontc"
+        - operation: "buildStat"
+          siblings: ["Nunito-Italic[wght].ttf"]
+    Nunito-Italic[wght].ttf:
+        - source: "Nunito-Italic.glyphs"
+        - operation: "fontc"
+    SHA256SUMS:
+        - source: "Nunito[wght].ttf"
+        - operation: "checksums"
+          needs: ["Nunito-Italic[wght].ttf"]
+"#;
+        // The recipe's order changes from run to run, so try a few
+        for _ in 0..10 {
+            let deserialized: Config =
+                serde_yaml_ng::from_str(config).expect("Failed to deserialize YAML");
+            let graph = deserialized.recipe().unwrap().to_graph(false).unwrap();
+            let checksums = graph
+                .node_indices()
+                .find(|node| graph.node_weight(*node).unwrap().shortname() == "Checksums")
+                .unwrap();
+            // Look through any conversions to the operations which made the fonts
+            let mut producers = vec![];
+            let mut pending = vec![checksums];
+            while let Some(node) = pending.pop() {
+                for edge in graph.edges_directed(node, petgraph::Direction::Incoming) {
+                    let producer = graph.node_weight(edge.source()).unwrap().shortname();
+                    if ["BuildStat", "Fontc"].contains(&producer) {
+                        producers.push(producer);
+                    } else {
+                        pending.push(edge.source());
+                    }
+                }
+            }
+            assert_eq!(producers, vec!["BuildStat", "BuildStat"]);
+        }
+    }
+
+    #[test]
+    fn test_use_external_fix() {
+        let config = r#"
+recipe:
+    Nunito.ttf:
+        - source: "Nunito.glyphs"
+        - operation: "fontc"
+        - operation: "fix"
+"#;
+        let deserialized: Config =
+            serde_yaml_ng::from_str(config).expect("Failed to deserialize YAML");
+        let mut recipe = deserialized.recipe().unwrap();
+        recipe.use_external_fix();
+        let steps = &recipe.0["Nunito.ttf"].0;
+        let Step::OperationStep { extra, .. } = &steps[2] else {
+            panic!("Expected an operation step");
+        };
+        assert_eq!(extra.get("useExternal"), Some(&Value::Bool(true)));
+        let Step::OperationStep { extra, .. } = &steps[1] else {
+            panic!("Expected an operation step");
+        };
+        assert!(extra.is_empty());
+    }
+
+    #[test]
+    fn test_step_sets() {
+        let config = r#"
+stepSets:
+    finish:
+        - operation: fix
+        - stepSet: webfont
+    webfont:
+        - operation: compress
+recipe:
+    Nunito.woff2:
+        - source: "Nunito.glyphs"
+        - operation: "fontc"
+        - stepSet: finish
+"#;
+        let deserialized: Config =
+            serde_yaml_ng::from_str(config).expect("Failed to deserialize YAML");
+        let steps = &deserialized.recipe.0["Nunito.woff2"].0;
+        assert_eq!(steps.len(), 4);
+        assert!(matches!(
+            steps[2],
+            Step::OperationStep {
+                operation: OpStep::Fix,
+                ..
+            }
+        ));
+        assert!(matches!(
+            steps[3],
+            Step::OperationStep {
+                operation: OpStep::Compress,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_unknown_step_set_is_an_error() {
+        let config = r#"
+recipe:
+    Nunito.ttf:
+        - source: "Nunito.glyphs"
+        - stepSet: missing
+"#;
+        let result: Result<Config, _> = serde_yaml_ng::from_str(config);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_step_errors_report_key_path() {
+        let config = r#"
+recipe:
+    Nunito.ttf:
+        - source: "Nunito.glyphs"
+        - operation: "fontcc"
+"#;
+        let deserializer = serde_yaml_ng::Deserializer::from_str(config);
+        let error = serde_path_to_error::deserialize::<_, Config>(deserializer)
+            .err()
+            .expect("Unknown operation should fail");
+        assert_eq!(error.path().to_string(), "recipe.Nunito.ttf[1]");
+        assert!(
+            error
+                .inner()
+                .to_string()
+                .contains("unknown variant `fontcc`")
+        );
+    }
+
+    #[test]
+    fn test_step_without_known_key_is_an_error() {
+        let config = r#"
+recipe:
+    Nunito.ttf:
+        - source: "Nunito.glyphs"
+        - operatoin: "fontc"
+"#;
+        let error = serde_yaml_ng::from_str::<Config>(config)
+            .err()
+            .expect("Step without an operation should fail");
+        assert!(
+            error
+                .to_string()
+                .contains("a step must have an `operation`, `source` or `stepSet` key")
+        );
+    }
+
+    #[test]
+    fn test_schema_generation() {
+        let schema = serde_json::to_value(schemars::schema_for!(Config)).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        assert!(properties.contains_key("recipe"));
+        assert!(properties.contains_key("stepSets"));
+        assert!(properties.contains_key("sources"));
+    }
+
+    #[test]
+    fn test_explain_target() {
+        let config = r#"
+recipe:
+    Nunito.ttf:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+    Nunito.woff2:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+        - operation: compress
+"#;
+        let config: Config = serde_yaml_ng::from_str(config).unwrap();
+        let graph = config.recipe().unwrap().to_graph(false).unwrap();
+        let explanation = graph.explain("Nunito.woff2").unwrap();
+        assert!(explanation.starts_with("Nunito.woff2 is built from Nunito.glyphs"));
+        assert!(explanation.contains("shared with: Nunito.ttf"));
+        assert!(graph.explain("Nunito.otf").is_err());
+    }
+
+    #[test]
+    fn test_graph_to_json() {
+        let config = r#"
+recipe:
+    Nunito.woff2:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+        - operation: compress
+"#;
+        let config: Config = serde_yaml_ng::from_str(config).unwrap();
+        let graph = config.recipe().unwrap().to_graph(false).unwrap();
+        let text = graph.to_json().unwrap();
+        let json: crate::buildsystem::GraphJson = serde_json::from_str(&text).unwrap();
+        let fontc = json
+            .nodes
+            .iter()
+            .find(|node| node.shortname == "Fontc")
+            .expect("No fontc node");
+        assert_eq!(fontc.step.as_ref().unwrap()["operation"], "fontc");
+        let compress = json.targets["Nunito.woff2"];
+        let edge = json
+            .edges
+            .iter()
+            .find(|edge| edge.from == fontc.id && edge.to == compress)
+            .expect("No edge from fontc to compress");
+        assert_eq!(edge.slot, 0);
+        assert!(
+            json.sinks
+                .iter()
+                .all(|sink| json.edges.iter().any(|e| e.to == *sink))
+        );
+
+        let rebuilt = BuildGraph::from_json(&text).unwrap();
+        assert_eq!(rebuilt.to_json().unwrap(), text);
+    }
+
+    #[test]
+    fn test_duplicate_steps_are_merged() {
+        let config = r#"
+recipe:
+    Nunito.ttf:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+    Nunito.woff2:
+        - source: "./Nunito.glyphs"
+        - operation: fontc
+        - operation: compress
+"#;
+        let config: Config = serde_yaml_ng::from_str(config).unwrap();
+        let graph = config.recipe().unwrap().to_graph(false).unwrap();
+        let explanation = graph.explain("Nunito.woff2").unwrap();
+        assert!(explanation.contains("shared with: Nunito.ttf"));
+    }
+
+    #[test]
+    fn test_statics_share_one_compile() {
+        let config = r#"
+recipe:
+    Nunito[wght].ttf:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+    Nunito-Regular.ttf:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+        - operation: instantiate
+          args: wght=400
+    Nunito-Bold.ttf:
+        - source: "./Nunito.glyphs"
+        - operation: fontc
+        - operation: instantiate
+          args: wght=700
+    Nunito-Black.ttf:
+        - source: "./Nunito.glyphs"
+        - operation: fontc
+        - operation: instantiate
+          args: wght=900
+"#;
+        let config: Config = serde_yaml_ng::from_str(config).unwrap();
+        let graph = config.recipe().unwrap().to_graph(false).unwrap();
+        let json: crate::buildsystem::GraphJson =
+            serde_json::from_str(&graph.to_json().unwrap()).unwrap();
+        let fontc = json
+            .nodes
+            .iter()
+            .filter(|node| node.shortname == "Fontc")
+            .collect::<Vec<_>>();
+        assert_eq!(fontc.len(), 1);
+        for target in ["Nunito-Regular.ttf", "Nunito-Bold.ttf", "Nunito-Black.ttf"] {
+            let instance = json.targets[target];
+            assert!(
+                json.edges
+                    .iter()
+                    .any(|edge| edge.from == fontc[0].id && edge.to == instance),
+                "{target} is not instanced from the shared compile"
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_log_finds_dirty_targets() {
+        use crate::buildsystem::{BuildLog, DirtyReason};
+        let graph_for = |quality: u8| {
+            let config = format!(
+                r#"
+recipe:
+    Nunito.ttf:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+    Nunito.woff2:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+        - operation: compress
+          quality: {quality}
+"#
+            );
+            let config: Config = serde_yaml_ng::from_str(&config).unwrap();
+            config.recipe().unwrap().to_graph(false).unwrap()
+        };
+        let graph = graph_for(11);
+        let mut log = BuildLog::default();
+        let dirty = log.dirty_targets(&graph);
+        assert_eq!(dirty["Nunito.woff2"], vec![DirtyReason::NeverBuilt]);
+
+        log.record(BuildLog::fingerprint(&graph));
+        let dirty = log.dirty_targets(&graph_for(9));
+        // Neither target has actually been written here
+        assert_eq!(dirty["Nunito.ttf"], vec![DirtyReason::OutputMissing]);
+        assert_eq!(
+            dirty["Nunito.woff2"],
+            vec![DirtyReason::OutputMissing, DirtyReason::RecipeChanged]
+        );
+    }
+
+    #[test]
+    fn test_build_log_sees_step_config() {
+        use crate::buildsystem::{BuildLog, DirtyReason};
+        // fontc's identifier doesn't include its configuration
+        let graph_for = |flatten: bool| {
+            let config = format!(
+                r#"
+recipe:
+    Nunito.ttf:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+          flattenComponents: {flatten}
+"#
+            );
+            let config: Config = serde_yaml_ng::from_str(&config).unwrap();
+            config.recipe().unwrap().to_graph(false).unwrap()
+        };
+        let mut log = BuildLog::default();
+        log.record(BuildLog::fingerprint(&graph_for(false)));
+        let dirty = log.dirty_targets(&graph_for(true));
+        assert_eq!(
+            dirty["Nunito.ttf"],
+            vec![DirtyReason::OutputMissing, DirtyReason::RecipeChanged]
+        );
+    }
+
+    #[test]
+    fn test_step_env() {
+        let step: Step = serde_yaml_ng::from_str(
+            "operation: fontc\nenv:\n  PYTHONWARNINGS: ignore\n  SOURCE_DATE_EPOCH: 0\n",
+        )
+        .unwrap();
+        let (_, op, _) = step.to_operation().unwrap();
+        assert_eq!(op.shortname(), "Fontc");
+        assert!(
+            op.identifier()
+                .ends_with("-env:PYTHONWARNINGS=ignore,SOURCE_DATE_EPOCH=0")
+        );
+
+        let bad: Result<Step, _> =
+            serde_yaml_ng::from_str("operation: fontc\nenv:\n  PATH: [a, b]\n");
+        assert!(bad.is_err());
+    }
+
+    #[test]
+    fn test_step_cwd() {
+        let step: Step = serde_yaml_ng::from_str("operation: fontc\ncwd: sources/vtt\n").unwrap();
+        let (_, op, _) = step.to_operation().unwrap();
+        assert!(op.identifier().ends_with("-cwd:sources/vtt"));
+
+        let bad: Result<Step, _> = serde_yaml_ng::from_str("operation: fontc\ncwd: 3\n");
+        assert!(bad.is_err());
+    }
+
+    #[test]
+    fn test_input_arity_is_validated() {
+        let config = r#"
+recipe:
+    Nunito.ttf:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+    Nunito-Italic.ttf:
+        - source: "Nunito-Italic.glyphs"
+        - operation: fontc
+          needs: ["Nunito.ttf"]
+"#;
+        let config: Config = serde_yaml_ng::from_str(config).unwrap();
+        let Err(ApplicationError::InvalidRecipe(message)) =
+            config.recipe().unwrap().to_graph(false)
+        else {
+            panic!("Expected an invalid recipe error");
+        };
+        assert_eq!(
+            message,
+            "operation Fontc for target Nunito-Italic.ttf expects 1 input but has 2"
+        );
+    }
+
+    #[test]
+    fn test_recipe_diff() {
+        let old = r#"
+recipe:
+    Nunito.ttf:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+    Nunito.woff2:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+        - operation: compress
+    Nunito-Subset.ttf:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+        - operation: fix
+        - operation: subset
+"#;
+        let new = r#"
+recipe:
+    Nunito.ttf:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+        - operation: fix
+    Nunito-Italic.ttf:
+        - source: "Nunito-Italic.glyphs"
+        - operation: fontc
+    Nunito-Subset.ttf:
+        - source: "Nunito.glyphs"
+        - operation: fontc
+        - operation: subset
+        - operation: fix
+"#;
+        let old: Config = serde_yaml_ng::from_str(old).unwrap();
+        let new: Config = serde_yaml_ng::from_str(new).unwrap();
+        let diff = old.recipe().unwrap().diff(&new.recipe().unwrap());
+        assert_eq!(
+            diff,
+            "+ Nunito-Italic.ttf\n\
+             ~ Nunito-Subset.ttf\n    - {\"operation\":\"fix\"}\n    + {\"operation\":\"fix\"}\n\
+             ~ Nunito.ttf\n    + {\"operation\":\"fix\"}\n\
+             - Nunito.woff2\n"
+        );
+        assert!(
+            old.recipe()
+                .unwrap()
+                .diff(&old.recipe().unwrap())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_fix_options_reach_fix_step() {
+        let options: GoogleFontsOptions = serde_yaml_ng::from_str(
+            r#"
+includeSourceFixes: true
+fixArgs: "--fvar-instance-axis-dflts"
+"#,
+        )
+        .unwrap();
+        let steps = ConfigOperationBuilder::new()
+            .fix(&options.fix_config)
+            .build()
+            .0;
+        let Step::OperationStep { extra, .. } = &steps[0] else {
+            panic!("Expected an operation step");
+        };
+        assert_eq!(extra["includeSourceFixes"], Value::Bool(true));
+        assert_eq!(
+            extra["fixArgs"],
+            Value::String("--fvar-instance-axis-dflts".to_string())
+        );
+    }
+
+    #[test]
+    fn test_outputs_override_directories() {
+        let options: GoogleFontsOptions = serde_yaml_ng::from_str(
+            r#"
+outputDir: ../out
+outputs:
+  ttf: $outputDir/statics
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            options.static_filename("Foo-Regular", None, Some("ttf")),
+            "../out/statics/Foo-Regular.ttf"
+        );
+        assert_eq!(
+            options.static_filename("Foo-Regular", None, Some("woff2")),
+            "../out/webfonts/Foo-Regular.woff2"
+        );
+        assert!(options.check_outputs(&["ttf"]).is_empty());
+        assert_eq!(options.check_outputs(&["variable"]), vec!["ttf"]);
+    }
+
     #[test]
     fn test_invalid_provider_options() {
         // Test with a field that has the wrong type (sources should be array, not string)
@@ -1,17 +1,44 @@
 use crate::recipe_providers::includesubsets::IncludeSubsetsOptions;
 use babelfont::{Font, Instance, UserCoord};
 use fontdrasil::coords::UserLocation;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_inline_default::serde_inline_default;
 use serde_json::Value;
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
 
 use crate::{
     error::ApplicationError,
     operations::{
-        ConfigOperationBuilder, addsubset::AddSubsetConfig, fix::FixConfig, fontc::FontcConfig,
+        ConfigOperationBuilder,
+        addsubset::AddSubsetConfig,
+        avar2::Avar2Config,
+        buildcolor::BuildColorConfig,
+        buildotf::BuildOTFConfig,
+        buildstat::{BuildStatConfig, StatSpec},
+        buildupstream::BuildUpstreamConfig,
+        buildvtt::BuildVTTConfig,
+        checksums::{ChecksumFormat, ChecksumsConfig},
+        decompose::DecomposeConfig,
+        fix::FixConfig,
+        fontc::FontcConfig,
+        fvarinstances::{FvarInstance, FvarInstancesConfig},
+        localizednames::LocalizedNamesConfig,
+        mvar::MvarConfig,
+        oblique::ObliqueConfig,
+        optimize::OptimizeConfig,
+        paintcompiler::PaintcompilerConfig,
+        proof::{ProofConfig, ProofFormat},
+        prunetables::PruneTablesConfig,
+        rename::RenameConfig,
+        setversion::SetVersionConfig,
+        ttfautohint::TTFAutohintConfig,
     },
     recipe::{Provider, Recipe},
+    recipe_providers::{SourceSpec, apply_overrides, resolve_sources},
 };
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -26,6 +53,7 @@ pub(crate) enum FontFormat {
     TTF,
     OTF,
     WOFF2,
+    WOFF,
 }
 impl FontFormat {
     fn extension(&self) -> &'static str {
@@ -33,6 +61,7 @@ impl FontFormat {
             FontFormat::TTF => "ttf",
             FontFormat::OTF => "otf",
             FontFormat::WOFF2 => "woff2",
+            FontFormat::WOFF => "woff",
         }
     }
 }
@@ -49,11 +78,13 @@ fn big_hammer<T, U>(x: T) -> U {
 }
 
 #[serde_inline_default]
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct GoogleFontsOptions {
     #[serde(default)]
-    pub sources: Vec<String>,
+    pub sources: Vec<SourceSpec>,
+    /// Directories for particular kinds of output, overriding the defaults;
+    /// which kinds there are depends on the provider
     #[serde(default)]
     pub outputs: HashMap<String, String>,
     #[serde(default)]
@@ -75,6 +106,12 @@ pub struct GoogleFontsOptions {
     #[serde_inline_default("$outputDir/webfonts".to_string())]
     pub woff_dir: String,
 
+    #[serde_inline_default("$outputDir/proofs".to_string())]
+    pub proof_dir: String,
+
+    #[serde_inline_default("$outputDir/monochrome".to_string())]
+    pub monochrome_dir: String,
+
     #[serde(default)]
     pub filename_suffix: Option<String>,
 
@@ -90,9 +127,136 @@ pub struct GoogleFontsOptions {
     #[serde(rename = "buildTTF")]
     pub build_ttf: bool,
 
+    // OTFs are built with fontmake, so are off unless asked for
+    #[serde_inline_default(false)]
+    #[serde(rename = "buildOTF")]
+    pub build_otf: bool,
+
     #[serde_inline_default(true)]
     pub build_webfont: bool,
 
+    /// Also write classic WOFF webfonts alongside the WOFF2 ones
+    #[serde(default)]
+    pub build_woff: bool,
+
+    /// Write a METADATA.pb describing the built fonts into the output directory
+    #[serde_inline_default(false)]
+    pub build_metadata: bool,
+
+    /// Write an upstream.yaml listing the built fonts next to the output directory
+    #[serde_inline_default(false)]
+    pub build_upstream: bool,
+
+    /// The repository and branch to record in upstream.yaml; by default,
+    /// these are taken from an existing upstream.yaml or from git
+    #[serde(default)]
+    pub upstream: BuildUpstreamConfig,
+
+    /// Write a proof of each built font into the proof directory
+    #[serde_inline_default(false)]
+    pub build_proofs: bool,
+
+    /// Whether proofs are HTML pages or PNG renderings
+    #[serde(default)]
+    pub proof_format: ProofFormat,
+
+    /// Build a small-caps sibling family ("Family SC") from sources which have small caps
+    #[serde(default)]
+    pub build_small_cap: bool,
+
+    /// Compile color layers into COLRv1/CPAL (with fontmake, as fontc ignores them)
+    #[serde(default)]
+    pub build_color: bool,
+
+    /// A paintcompiler definition file (`paints.py`) to add COLRv1 paints with
+    #[serde(default)]
+    pub paints: Option<String>,
+
+    /// Also build monochrome versions of color fonts, without COLR and CPAL
+    #[serde(default)]
+    pub build_monochrome_fallback: bool,
+
+    /// For families without italics, also build an oblique of each static TTF,
+    /// slanted by this many degrees
+    #[serde(default)]
+    pub oblique_angle: Option<f64>,
+
+    /// Remove contour overlaps from static TTFs, as Google Fonts requires
+    #[serde_inline_default(true)]
+    pub remove_outline_overlaps: bool,
+
+    // Autohinting options
+    #[serde_inline_default(true)]
+    #[serde(rename = "autohintTTF")]
+    pub autohint_ttf: bool,
+
+    /// Hint the VFs with ttfautohint-vf, using natural stem widths as gftools
+    /// does, followed by any `ttfautohintArgs`
+    #[serde_inline_default(false)]
+    #[serde(rename = "autohintVF")]
+    pub autohint_vf: bool,
+
+    /// Arguments to pass to ttfautohint for statics, in place of gftools'
+    /// `--stem-width-mode nnn`
+    #[serde(default)]
+    pub ttfautohint_args: Option<String>,
+
+    /// VTT hinting sources (TTX files) keyed by the filename of the font they
+    /// hint; these fonts are not autohinted
+    #[serde(default)]
+    pub vtt_sources: HashMap<String, String>,
+
+    /// The STAT table for the VFs: either a list of axes for all of them, or
+    /// a list per VF filename; if not given, it is derived from the axis registry
+    #[serde(default)]
+    pub stat: Option<StatSpec>,
+
+    /// A designspace whose `<mappings>` are used to add an avar2 table to the VFs
+    #[serde(default)]
+    pub avar2: Option<String>,
+
+    /// Named instances to replace the ones the VFs are compiled with
+    #[serde(default)]
+    pub fvar_instances: Vec<FvarInstance>,
+
+    /// Optimize the VFs' gvar and GPOS before compressing them to WOFF2
+    #[serde(default)]
+    pub optimize_webfonts: bool,
+
+    /// Add an MVAR table to color VFs so their vertical metrics follow the masters'
+    /// (fontc makes its own for everything else)
+    #[serde(default)]
+    pub build_mvar: bool,
+
+    /// Write a manifest of checksums for all the fonts we build into the output directory
+    #[serde(default)]
+    pub checksums: Option<ChecksumFormat>,
+
+    /// Check that each source's masters are interpolation-compatible before compiling
+    #[serde(default)]
+    pub check_compatibility: bool,
+
+    /// Rename the family in every font we build, e.g. when the sources use a working name
+    #[serde(default)]
+    pub family_name: Option<String>,
+
+    /// Family names in other languages, keyed by language tag
+    #[serde(default)]
+    pub localized_family_names: BTreeMap<String, String>,
+
+    // Version stamping
+    /// The version to stamp into head.fontRevision and the version string
+    #[serde(default)]
+    pub version: Option<String>,
+
+    /// Increment the sources' version by 0.001 (ignored if `version` is given)
+    #[serde(default)]
+    pub bump_version: bool,
+
+    /// Take the version from the most recent git tag (ignored if `version` is given)
+    #[serde(default)]
+    pub version_from_git: bool,
+
     // Fix arguments
     #[serde(flatten, default)]
     pub fix_config: FixConfig,
@@ -113,18 +277,209 @@ impl Default for GoogleFontsOptions {
     }
 }
 
+/// The kinds of output whose directories can be set in `outputs:`
+const OUTPUT_KINDS: [&str; 6] = ["variable", "ttf", "otf", "webfonts", "proofs", "monochrome"];
+
+/// What gftools hands ttfautohint when hinting statics
+const GFTOOLS_TTFAUTOHINT_ARGS: &str = "--stem-width-mode nnn";
+
 impl GoogleFontsOptions {
+    /// Warn about any kinds of output in `outputs:` which the provider doesn't
+    /// know about, and return them
+    pub(crate) fn check_outputs(&self, kinds: &[&str]) -> Vec<String> {
+        let mut unknown = self
+            .outputs
+            .keys()
+            .filter(|kind| !kinds.contains(&kind.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+        unknown.sort();
+        if !unknown.is_empty() {
+            log::warn!(
+                "Ignoring unknown output kind(s) {} in outputs; expected one of {}",
+                unknown.join(", "),
+                kinds.join(", ")
+            );
+        }
+        unknown
+    }
+
+    /// The directory for a kind of output, taking any `outputs:` override into account
+    fn dir_for(&self, kind: &str, default: &str) -> String {
+        self.outputs
+            .get(kind)
+            .map(String::as_str)
+            .unwrap_or(default)
+            .replace("$outputDir", &self.output_dir)
+    }
     fn vf_dir(&self) -> String {
-        self.vf_dir.replace("$outputDir", &self.output_dir)
+        self.dir_for("variable", &self.vf_dir)
     }
     fn tt_dir(&self) -> String {
-        self.tt_dir.replace("$outputDir", &self.output_dir)
+        self.dir_for("ttf", &self.tt_dir)
     }
     fn ot_dir(&self) -> String {
-        self.ot_dir.replace("$outputDir", &self.output_dir)
+        self.dir_for("otf", &self.ot_dir)
     }
     fn woff_dir(&self) -> String {
-        self.woff_dir.replace("$outputDir", &self.output_dir)
+        self.dir_for("webfonts", &self.woff_dir)
+    }
+    fn proof_dir(&self) -> String {
+        self.dir_for("proofs", &self.proof_dir)
+    }
+    fn monochrome_dir(&self) -> String {
+        self.dir_for("monochrome", &self.monochrome_dir)
+    }
+
+    /// Compile the source, in color if the recipe asks for it
+    fn compile(&self, source: &Font, builder: ConfigOperationBuilder) -> ConfigOperationBuilder {
+        let builder = if self.build_color {
+            let variable = source.masters.len() >= 2;
+            let builder = builder.build_color(&BuildColorConfig { variable });
+            if variable {
+                builder
+            } else {
+                self.component_steps(builder)
+            }
+        } else {
+            builder.compile(&self.fontc_config)
+        };
+        match &self.paints {
+            Some(paints) => builder.paintcompiler(&PaintcompilerConfig {
+                paints: paints.clone(),
+            }),
+            None => builder,
+        }
+    }
+
+    /// Do what fontc's component options would to a static font built some
+    /// other way
+    fn component_steps(&self, builder: ConfigOperationBuilder) -> ConfigOperationBuilder {
+        let builder = if self.fontc_config.flatten_components {
+            builder.flatten()
+        } else {
+            builder
+        };
+        if self.fontc_config.decompose_transformed_components {
+            builder.decompose(&DecomposeConfig {
+                only_transformed: true,
+            })
+        } else {
+            builder
+        }
+    }
+
+    /// Add a monochrome fallback for a color font, if the recipe asks for one
+    fn add_monochrome_fallback(&self, recipe: &mut Recipe, target: &str) {
+        if !self.build_color || !self.build_monochrome_fallback {
+            return;
+        }
+        let basename = Path::new(target)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| target.to_string());
+        let builder = ConfigOperationBuilder::new()
+            .source(target.to_string())
+            .prune_tables(&PruneTablesConfig {
+                drop: vec!["COLR".to_string(), "CPAL".to_string()],
+                keep: vec![],
+            });
+        recipe.insert(
+            format!("{}/{basename}", self.monochrome_dir()),
+            builder.build(),
+        );
+    }
+
+    /// Add a compatibility check after the source step if the recipe asks for one
+    fn check_masters(
+        &self,
+        source: &Font,
+        builder: ConfigOperationBuilder,
+    ) -> ConfigOperationBuilder {
+        if self.check_compatibility && source.masters.len() >= 2 {
+            builder.check_compatibility()
+        } else {
+            builder
+        }
+    }
+
+    /// Add a renaming step if the recipe overrides the family name
+    fn rename_family(&self, builder: ConfigOperationBuilder) -> ConfigOperationBuilder {
+        match &self.family_name {
+            Some(name) => builder.rename(&RenameConfig {
+                name: name.clone(),
+                just_family: true,
+            }),
+            None => builder,
+        }
+    }
+
+    /// Add localized family names if the recipe has any
+    fn localize_names(&self, builder: ConfigOperationBuilder) -> ConfigOperationBuilder {
+        if self.localized_family_names.is_empty() {
+            return builder;
+        }
+        builder.localized_names(&LocalizedNamesConfig {
+            family_names: self.localized_family_names.clone(),
+        })
+    }
+
+    /// Merge VTT hinting into the target if the recipe has VTT sources for it
+    fn vtt_source(&self, target: &str) -> Option<BuildVTTConfig> {
+        let filename = Path::new(target).file_name()?.to_str()?;
+        self.vtt_sources
+            .get(filename)
+            .map(|vtt_source| BuildVTTConfig {
+                vtt_source: vtt_source.clone(),
+                keep_sources: false,
+            })
+    }
+
+    /// Add a version-setting step if the recipe asks for one
+    fn stamp_version(&self, builder: ConfigOperationBuilder) -> ConfigOperationBuilder {
+        if self.version.is_none() && !self.bump_version && !self.version_from_git {
+            return builder;
+        }
+        builder.set_version(&SetVersionConfig {
+            version: self.version.clone(),
+            bump: self.bump_version,
+            from_git: self.version_from_git,
+        })
+    }
+
+    /// Hint a static TTF with ttfautohint as gftools does, keeping the font
+    /// unhinted if ttfautohint can't cope with it
+    fn autohint_static(&self, builder: ConfigOperationBuilder) -> ConfigOperationBuilder {
+        let args = self
+            .ttfautohint_args
+            .as_deref()
+            .unwrap_or(GFTOOLS_TTFAUTOHINT_ARGS);
+        builder.ttfautohint(
+            Some(args),
+            &TTFAutohintConfig {
+                fallback_to_unhinted: true,
+            },
+        )
+    }
+
+    /// Hint a static TTF and fix up its tables and names
+    fn finish_static(
+        &self,
+        builder: ConfigOperationBuilder,
+        target: &str,
+        format: FontFormat,
+    ) -> ConfigOperationBuilder {
+        let builder = if let Some(vtt) = self.vtt_source(target) {
+            builder.build_vtt(&vtt)
+        } else if self.autohint_ttf && format == FontFormat::TTF {
+            self.autohint_static(builder)
+        } else {
+            builder
+        };
+        let builder = builder.fix(&self.fix_config);
+        let builder = self.rename_family(builder);
+        let builder = self.localize_names(builder);
+        self.stamp_version(builder)
     }
 
     pub(crate) fn vf_filename(
@@ -165,7 +520,7 @@ impl GoogleFontsOptions {
         let axis_tags = tags.join(",");
 
         let mut directory = self.vf_dir();
-        if extension == "woff2" {
+        if extension == "woff2" || extension == "woff" {
             directory = self.woff_dir();
         }
 
@@ -180,7 +535,6 @@ impl GoogleFontsOptions {
         Ok(format!("{directory}/{sourcebase}[{axis_tags}].{extension}"))
     }
 
-    #[allow(dead_code)]
     pub fn static_filename(
         &self,
         instancebase: &str,
@@ -193,7 +547,7 @@ impl GoogleFontsOptions {
         let outdir = match extension {
             "ttf" => self.tt_dir(),
             "otf" => self.ot_dir(),
-            "woff2" => self.woff_dir(),
+            "woff2" | "woff" => self.woff_dir(),
             _ => self.tt_dir(),
         };
 
@@ -214,7 +568,12 @@ impl GoogleFontsOptions {
 pub struct GoogleFontsProvider {
     options: GoogleFontsOptions,
     sources: Vec<Font>,
+    /// Options for sources which override some of the provider options, by source path
+    source_options: HashMap<String, GoogleFontsOptions>,
     recipe: Recipe,
+    /// The variable and static TTF targets of the family proper, for METADATA.pb
+    vf_targets: Vec<String>,
+    static_targets: Vec<String>,
 }
 
 impl GoogleFontsProvider {
@@ -222,17 +581,34 @@ impl GoogleFontsProvider {
         GoogleFontsProvider {
             options,
             sources: vec![],
+            source_options: HashMap::new(),
             recipe: Recipe::default(),
+            vf_targets: vec![],
+            static_targets: vec![],
         }
     }
+
+    /// The options which apply to a given source, taking per-source overrides into account
+    fn options_for(&self, source: &Font) -> &GoogleFontsOptions {
+        source
+            .source
+            .as_ref()
+            .and_then(|path| self.source_options.get(&*path.to_string_lossy()))
+            .unwrap_or(&self.options)
+    }
 }
 
 impl Provider for GoogleFontsProvider {
     fn generate_recipe(&self) -> Result<Recipe, ApplicationError> {
+        self.options.check_outputs(&OUTPUT_KINDS);
         let mut provider = Self::new(self.options.clone());
         provider.load_all_sources()?;
         provider.build_all_variables()?;
         provider.build_all_statics()?;
+        provider.build_metadata();
+        provider.build_upstream();
+        provider.build_proofs();
+        provider.build_checksums();
 
         // Implementation for rewriting the recipe for Google fonts
         Ok(provider.recipe)
@@ -280,9 +656,10 @@ impl GoogleFontsProvider {
             .map(|italic_ds| self.style_for_instance(source, instance, italic_ds))
             .unwrap_or(Style::Roman);
 
-        self.options.vf_filename(
+        let options = self.options_for(source);
+        options.vf_filename(
             source,
-            self.options.filename_suffix.as_deref(),
+            options.filename_suffix.as_deref(),
             FontFormat::TTF,
             italic_ds.as_ref(),
             style,
@@ -290,23 +667,33 @@ impl GoogleFontsProvider {
     }
 
     fn load_all_sources(&mut self) -> Result<(), ApplicationError> {
-        for source in &self.options.sources {
-            log::debug!("Loading source font: {}", source);
-            let font = babelfont::load(source).map_err(|e| {
-                ApplicationError::InvalidRecipe(format!("Failed to load source {source}: {e}"))
+        for source in resolve_sources(&self.options.sources)? {
+            log::debug!("Loading source font: {}", source.path);
+            let font = babelfont::load(&source.path).map_err(|e| {
+                ApplicationError::InvalidRecipe(format!(
+                    "Failed to load source {}: {e}",
+                    source.path
+                ))
             })?;
+            if !source.overrides.is_empty() {
+                let options = apply_overrides(&self.options, &source)?;
+                let path = font
+                    .source
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .unwrap_or(source.path);
+                self.source_options.insert(path, options);
+            }
             self.sources.push(font);
         }
         Ok(())
     }
 
     fn build_all_variables(&mut self) -> Result<(), ApplicationError> {
-        if !self.options.build_variable {
-            return Ok(());
-        }
         let variable_targets = self
             .sources
             .iter()
+            .filter(|source| self.options_for(source).build_variable)
             .filter(|source| source.masters.len() >= 2)
             .flat_map(|source| {
                 if let Some(italic_ds) = self.has_slant_italic(source) {
@@ -323,52 +710,210 @@ impl GoogleFontsProvider {
         let filenames = variable_targets
             .iter()
             .map(|(source, italic_ds, roman)| {
-                self.options.vf_filename(
+                let options = self.options_for(source);
+                options.vf_filename(
                     source,
-                    self.options.filename_suffix.as_deref(),
+                    options.filename_suffix.as_deref(),
                     FontFormat::TTF,
                     italic_ds.as_ref(),
                     *roman,
                 )
             })
             .collect::<Result<Vec<_>, ApplicationError>>()?;
+        self.vf_targets.extend(filenames.iter().cloned());
+        // The first VF of each family builds the STAT tables for all of its
+        // siblings (e.g. Roman and Italic), so they can link to each other.
+        let family = |source: &Font| source.names.family_name.get_default().cloned();
         let mut new_recipes = vec![];
-        for (index, (source, italic_ds, style)) in variable_targets.into_iter().enumerate() {
-            let siblings = if index == 0 {
-                Some(filenames.iter().skip(1).cloned().collect::<Vec<String>>())
-            } else {
-                None
-            };
-            new_recipes.push(self.build_a_variable(source, italic_ds.as_ref(), style, siblings)?);
+        for (index, (source, italic_ds, style)) in variable_targets.iter().enumerate() {
+            let first_of_family = variable_targets
+                .iter()
+                .position(|(other, _, _)| family(other) == family(source))
+                == Some(index);
+            let siblings = first_of_family.then(|| {
+                variable_targets
+                    .iter()
+                    .zip(filenames.iter())
+                    .enumerate()
+                    .filter(|(other_index, ((other, _, _), _))| {
+                        *other_index != index && family(other) == family(source)
+                    })
+                    .map(|(_, (_, filename))| filename.clone())
+                    .collect::<Vec<String>>()
+            });
+            new_recipes.push(self.build_a_variable(
+                source,
+                italic_ds.as_ref(),
+                *style,
+                siblings,
+            )?);
         }
         let mut flat_recipes = Recipe::new();
         for recipe in new_recipes {
             flat_recipes.extend(recipe.clone());
         }
-        // Do avar2
         self.recipe.extend(flat_recipes);
         Ok(())
     }
 
     fn build_all_statics(&mut self) -> Result<(), ApplicationError> {
-        if !self.options.build_static {
-            return Ok(());
-        }
         for source in self.sources.iter() {
-            for instance in source.instances.iter() {
-                let recipe = self.build_a_static(source, instance, FontFormat::TTF)?;
-                self.recipe.extend(recipe);
+            let options = self.options_for(source);
+            if !options.build_static {
+                continue;
+            }
+            let mut formats = vec![FontFormat::TTF];
+            if options.build_otf {
+                formats.push(FontFormat::OTF);
+            }
+            // A single-master source is compiled straight to one static font
+            let instances: Vec<Option<&Instance>> = if source.masters.len() < 2 {
+                vec![source.instances.first()]
+            } else {
+                source.instances.iter().map(Some).collect()
+            };
+            for format in formats {
+                for instance in instances.iter() {
+                    let recipe = self.build_a_static(source, *instance, format)?;
+                    let options = self.options_for(source);
+                    let target = (format == FontFormat::TTF && options.build_ttf).then(|| {
+                        options.static_filename(
+                            &self.instance_base(source, *instance),
+                            options.filename_suffix.as_deref(),
+                            Some(format.extension()),
+                        )
+                    });
+                    self.recipe.extend(recipe);
+                    self.static_targets.extend(target);
+                }
             }
         }
         Ok(())
     }
 
+    /// Add a METADATA.pb target, built from the VFs if we have them and the statics otherwise
+    fn build_metadata(&mut self) {
+        if !self.options.build_metadata {
+            return;
+        }
+        let fonts = if self.vf_targets.is_empty() {
+            &self.static_targets
+        } else {
+            &self.vf_targets
+        };
+        let Some((first, others)) = fonts.split_first() else {
+            return;
+        };
+        let target = format!(
+            "{}/METADATA.pb",
+            self.options.output_dir.trim_end_matches('/')
+        );
+        let builder = ConfigOperationBuilder::new()
+            .source(first.clone())
+            .build_metadata(others);
+        self.recipe.insert(target, builder.build());
+    }
+
+    /// Add an upstream.yaml target in the directory above the output directory
+    fn build_upstream(&mut self) {
+        if !self.options.build_upstream {
+            return;
+        }
+        let fonts = if self.vf_targets.is_empty() {
+            &self.static_targets
+        } else {
+            &self.vf_targets
+        };
+        let Some((first, others)) = fonts.split_first() else {
+            return;
+        };
+        let target = Path::new(self.options.output_dir.trim_end_matches('/'))
+            .parent()
+            .map(|dir| dir.join("upstream.yaml").to_string_lossy().to_string())
+            .unwrap_or_else(|| "upstream.yaml".to_string());
+        let builder = ConfigOperationBuilder::new()
+            .source(first.clone())
+            .build_upstream(others, &self.options.upstream);
+        self.recipe.insert(target, builder.build());
+    }
+
+    /// Add a checksum manifest target covering every font in the recipe
+    fn build_checksums(&mut self) {
+        let Some(format) = self.options.checksums else {
+            return;
+        };
+        let mut fonts = self
+            .recipe
+            .0
+            .keys()
+            .filter(|target| {
+                Path::new(target).extension().is_some_and(|ext| {
+                    ["ttf", "otf", "woff", "woff2"].contains(&&*ext.to_string_lossy())
+                })
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        fonts.sort();
+        let Some((first, others)) = fonts.split_first() else {
+            return;
+        };
+        let target = format!(
+            "{}/{}",
+            self.options.output_dir.trim_end_matches('/'),
+            format.filename()
+        );
+        let builder = ConfigOperationBuilder::new()
+            .source(first.clone())
+            .checksums(others, &ChecksumsConfig { format });
+        self.recipe.insert(target, builder.build());
+    }
+
+    /// Add an HTML proof target for each of the family's fonts
+    fn build_proofs(&mut self) {
+        if !self.options.build_proofs {
+            return;
+        }
+        for font in self.vf_targets.iter().chain(self.static_targets.iter()) {
+            let Some(stem) = Path::new(font).file_stem() else {
+                continue;
+            };
+            let format = self.options.proof_format;
+            let target = format!(
+                "{}/{}.{}",
+                self.options.proof_dir(),
+                stem.to_string_lossy(),
+                format.extension()
+            );
+            let builder = ConfigOperationBuilder::new()
+                .source(font.clone())
+                .proof(&ProofConfig { format, text: None });
+            self.recipe.insert(target, builder.build());
+        }
+    }
+
+    /// The "Family-Style" stem of a static instance's filename
+    fn instance_base(&self, source: &Font, instance: Option<&Instance>) -> String {
+        format!(
+            "{}-{}",
+            self.options_for(source)
+                .family_name
+                .as_ref()
+                .or(source.names.family_name.get_default())
+                .unwrap_or(&"Unknown".to_string()),
+            instance
+                .and_then(|instance| instance.name.get_default())
+                .unwrap_or(&"Regular".to_string())
+        )
+        .replace(" ", "")
+    }
+
     fn build_a_static(
         &self,
         source: &Font,
-        instance: &Instance,
+        instance: Option<&Instance>,
         format: FontFormat,
     ) -> Result<Recipe, ApplicationError> {
+        let options = self.options_for(source);
         log::debug!(
             "Considering how to build static font for {} instance {:?}",
             source
@@ -376,34 +921,49 @@ impl GoogleFontsProvider {
                 .family_name
                 .get_default()
                 .unwrap_or(&"Unknown family".to_string()),
-            instance.location
+            instance.map(|instance| &instance.location)
         );
-        let instance_base = format!(
-            "{}-{}",
-            source
-                .names
-                .family_name
-                .get_default()
-                .unwrap_or(&"Unknown".to_string()),
-            instance
-                .name
-                .get_default()
-                .unwrap_or(&"Regular".to_string())
-        )
-        .replace(" ", "");
-        let target = self.options.static_filename(
+        let instance_base = self.instance_base(source, instance);
+        let target = options.static_filename(
             &instance_base,
-            self.options.filename_suffix.as_deref(),
+            options.filename_suffix.as_deref(),
             Some(format.extension()),
         );
         log::debug!("Static target filename: {}", target);
         let mut recipe = Recipe::new();
-        let vf_filename = self.vf_source_for_instance(source, instance)?;
-        let mut builder = ConfigOperationBuilder::new().source(vf_filename);
-        if source.instances.len() > 1 {
+
+        if format == FontFormat::OTF {
+            // fontc can't make CFF, so OTFs are built by fontmake straight from the source
+            let instance = instance
+                .filter(|_| source.masters.len() >= 2)
+                .and_then(|instance| instance.name.get_default())
+                .cloned();
+            let builder = ConfigOperationBuilder::new().source(Self::source_path(source)?);
+            let builder = options
+                .check_masters(source, builder)
+                .build_otf(&BuildOTFConfig { instance })
+                .fix(&options.fix_config);
+            let builder = options.rename_family(builder);
+            let builder = options.stamp_version(options.localize_names(builder));
+            recipe.insert(target, builder.build());
+            return Ok(recipe);
+        }
+
+        let mut builder = self.static_base_builder(source, instance)?;
+        // A multi-master source always compiles to a variable font, so even its
+        // only instance has to be cut out of it
+        if let Some(instance) = instance
+            && source.masters.len() >= 2
+        {
+            // When instancing from the VFs, the italic axis has already been pinned
+            let pinned_axis = self
+                .has_slant_italic(source)
+                .filter(|_| options.build_variable)
+                .map(|italic_ds| italic_ds.axis_tag);
             let loc: UserLocation = instance
                 .location
                 .iter()
+                .filter(|(axis, _)| pinned_axis.as_deref() != Some(&*axis.to_string()))
                 .map(|(axis, value)| {
                     (
                         fontdrasil::types::Tag::new(&axis.into_bytes()),
@@ -419,28 +979,109 @@ impl GoogleFontsProvider {
                     )
                 })
                 .collect();
-            builder = builder.instance(&loc);
+            builder = builder.instantiate(&loc);
         }
-        // Autohint steps
-        builder = builder.autohint();
-        // VTT steps
-        builder = builder.fix(&self.options.fix_config);
+        if options.remove_outline_overlaps && format == FontFormat::TTF {
+            builder = builder.remove_overlaps();
+        }
+        // An oblique is slanted from the unhinted outlines, then finished in the same way
+        let unfinished = builder.clone();
+        builder = options.finish_static(builder, &target, format);
 
-        if self.options.build_webfont && format == FontFormat::TTF {
-            let webfont_target = self.options.static_filename(
+        if options.build_webfont && format == FontFormat::TTF {
+            let webfont_target = options.static_filename(
                 &instance_base,
-                self.options.filename_suffix.as_deref(),
+                options.filename_suffix.as_deref(),
                 Some("woff2"),
             );
             log::debug!(" Building webfont target: {}", webfont_target);
             let webfont_builder = builder.clone().compress();
             recipe.insert(webfont_target, webfont_builder.build());
+            if options.build_woff {
+                let woff_target = options.static_filename(
+                    &instance_base,
+                    options.filename_suffix.as_deref(),
+                    Some("woff"),
+                );
+                recipe.insert(woff_target, builder.clone().woff().build());
+            }
         }
 
-        recipe.insert(target, builder.build());
+        if format == FontFormat::TTF
+            && let Some(angle) = options.oblique_angle
+            && !Self::has_italics(source)
+        {
+            let oblique_base = match instance_base.strip_suffix("-Regular") {
+                Some(family) => format!("{family}-Italic"),
+                None => format!("{instance_base}Italic"),
+            };
+            let oblique_target = options.static_filename(
+                &oblique_base,
+                options.filename_suffix.as_deref(),
+                Some("ttf"),
+            );
+            log::debug!(" Building oblique target: {}", oblique_target);
+            let oblique_builder = options.finish_static(
+                unfinished.oblique(&ObliqueConfig { angle }),
+                &oblique_target,
+                format,
+            );
+            recipe.insert(oblique_target, oblique_builder.build());
+        }
+
+        if format == FontFormat::TTF
+            && let Some(sc_family) = self.smallcap_family(source)
+        {
+            let sc_suffix = format!("{}SC", options.filename_suffix.as_deref().unwrap_or(""));
+            let sc_target = options.static_filename(&instance_base, Some(&sc_suffix), Some("ttf"));
+            log::debug!(" Building small caps target: {}", sc_target);
+            let sc_builder = Self::smallcap_steps(builder.clone(), &sc_family);
+            recipe.insert(sc_target, sc_builder.build());
+        }
+
+        if format != FontFormat::TTF || options.build_ttf {
+            if format == FontFormat::TTF {
+                options.add_monochrome_fallback(&mut recipe, &target);
+            }
+            recipe.insert(target, builder.build());
+        }
         Ok(recipe)
     }
 
+    /// The steps which produce the font a static instance is cut from
+    ///
+    /// If we are building the variable font anyway, statics are instanced from
+    /// it; otherwise the source is compiled here, as part of the static's own
+    /// recipe. (The graph shares the compile between all the instances.)
+    fn static_base_builder(
+        &self,
+        source: &Font,
+        instance: Option<&Instance>,
+    ) -> Result<ConfigOperationBuilder, ApplicationError> {
+        let options = self.options_for(source);
+        if let Some(instance) = instance
+            && source.masters.len() >= 2
+            && options.build_variable
+        {
+            let vf_filename = self.vf_source_for_instance(source, instance)?;
+            return Ok(ConfigOperationBuilder::new().source(vf_filename));
+        }
+        let builder = ConfigOperationBuilder::new().source(Self::source_path(source)?);
+        let builder = options.check_masters(source, builder);
+        let builder = self.add_subset_steps(options, builder)?;
+        Ok(options.compile(source, builder))
+    }
+
+    fn source_path(source: &Font) -> Result<String, ApplicationError> {
+        source
+            .source
+            .as_ref()
+            .map(|path| path.to_string_lossy().to_string())
+            .ok_or_else(|| {
+                ApplicationError::InvalidRecipe("Source font has no filename".to_string())
+            })
+    }
+
     fn build_a_variable(
         &self,
         source: &Font,
@@ -448,6 +1089,7 @@ impl GoogleFontsProvider {
         roman: Style,
         siblings: Option<Vec<String>>,
     ) -> Result<Recipe, ApplicationError> {
+        let options = self.options_for(source);
         log::debug!(
             "Considering how to build variable font for {}",
             source
@@ -458,9 +1100,9 @@ impl GoogleFontsProvider {
         );
         let mut recipe = Recipe::new();
         // Implementation for building a variable font
-        let target = self.options.vf_filename(
+        let target = options.vf_filename(
             source,
-            self.options.filename_suffix.as_deref(),
+            options.filename_suffix.as_deref(),
             FontFormat::TTF,
             italic_ds,
             roman,
@@ -476,36 +1118,169 @@ impl GoogleFontsProvider {
                 .to_string_lossy()
                 .to_string(),
         );
-        builder = self.add_subset_steps(builder)?;
-        builder = builder.compile(&self.options.fontc_config);
+        builder = options.check_masters(source, builder);
+        builder = self.add_subset_steps(options, builder)?;
+        builder = options.compile(source, builder);
+        if let Some(designspace) = &options.avar2 {
+            builder = builder.avar2(&Avar2Config {
+                designspace: designspace.clone(),
+            });
+        }
+        // fontc works out an MVAR itself; only fontmake's color builds need one adding
+        if options.build_mvar && !options.build_color {
+            log::warn!("fontc adds MVAR tables itself, so buildMvar is ignored");
+        } else if options.build_mvar {
+            builder = builder.mvar(&MvarConfig {
+                source: Self::source_path(source)?,
+            });
+        }
         // Any post-compile steps
-        // Any VTT steps
-        // If italic, subspace the axes according to style
+        if let Some(vtt) = options.vtt_source(&target) {
+            builder = builder.build_vtt(&vtt);
+        } else if options.autohint_vf {
+            builder = builder.autohint_vf(options.ttfautohint_args.as_deref());
+        }
+
+        // If italic, pin the italic axis to the end of its range for this style
+        if let Some(italic_ds) = italic_ds {
+            let value = match roman {
+                Style::Roman => italic_ds.min_value,
+                Style::Italic => italic_ds.max_value,
+            };
+            let tag = fontdrasil::types::Tag::new_checked(italic_ds.axis_tag.as_bytes()).map_err(
+                |e| {
+                    ApplicationError::InvalidRecipe(format!(
+                        "Bad italic axis tag {}: {e}",
+                        italic_ds.axis_tag
+                    ))
+                },
+            )?;
+            let loc: UserLocation = [(tag, big_hammer(value))].into_iter().collect();
+            builder = builder.instance(&loc);
+        }
 
-        builder = builder.fix(&self.options.fix_config);
+        builder = builder.fix(&options.fix_config);
+        builder = options.rename_family(builder);
+        builder = options.localize_names(builder);
+        builder = options.stamp_version(builder);
+        if !options.fvar_instances.is_empty() {
+            builder = builder.fvar_instances(&FvarInstancesConfig {
+                instances: options.fvar_instances.clone(),
+            });
+        }
         if let Some(siblings) = siblings {
-            builder = builder.buildstat(&siblings);
+            builder = builder.buildstat(
+                &siblings,
+                &BuildStatConfig {
+                    stat: options.stat.clone(),
+                    elided_fallback_name: None,
+                },
+            );
         }
 
-        if self.options.build_webfont {
-            let webfont_target = self.options.vf_filename(
+        if options.build_webfont {
+            let webfont_target = options.vf_filename(
                 source,
-                self.options.filename_suffix.as_deref(),
+                options.filename_suffix.as_deref(),
                 FontFormat::WOFF2,
                 italic_ds,
                 roman,
             )?;
             log::debug!(" Building webfont target: {}", webfont_target);
-            let webfont_builder = builder.clone().compress();
-            recipe.insert(webfont_target, webfont_builder.build());
+            // Compress the finished VF rather than sharing its steps, because a
+            // sibling's BuildStat may write the STAT table into it.
+            let mut webfont_builder = ConfigOperationBuilder::new().source(target.clone());
+            if options.optimize_webfonts {
+                webfont_builder = webfont_builder.optimize(&OptimizeConfig::default());
+            }
+            recipe.insert(webfont_target, webfont_builder.compress().build());
+            if options.build_woff {
+                let woff_target = options.vf_filename(
+                    source,
+                    options.filename_suffix.as_deref(),
+                    FontFormat::WOFF,
+                    italic_ds,
+                    roman,
+                )?;
+                let woff_builder = ConfigOperationBuilder::new().source(target.clone()).woff();
+                recipe.insert(woff_target, woff_builder.build());
+            }
         }
 
-        // Smallcaps go here
+        if let Some(sc_family) = self.smallcap_family(source) {
+            let sc_suffix = format!("{}SC", options.filename_suffix.as_deref().unwrap_or(""));
+            let sc_target =
+                options.vf_filename(source, Some(&sc_suffix), FontFormat::TTF, italic_ds, roman)?;
+            log::debug!(" Building small caps target: {}", sc_target);
+            if options.build_webfont {
+                let sc_webfont_target = options.vf_filename(
+                    source,
+                    Some(&sc_suffix),
+                    FontFormat::WOFF2,
+                    italic_ds,
+                    roman,
+                )?;
+                let sc_webfont_builder = ConfigOperationBuilder::new()
+                    .source(sc_target.clone())
+                    .compress();
+                recipe.insert(sc_webfont_target, sc_webfont_builder.build());
+            }
+            let sc_builder = Self::smallcap_steps(
+                ConfigOperationBuilder::new().source(target.clone()),
+                &sc_family,
+            );
+            recipe.insert(sc_target, sc_builder.build());
+        }
 
+        options.add_monochrome_fallback(&mut recipe, &target);
         recipe.insert(target, builder.build());
         Ok(recipe)
     }
 
+    /// The name of the small-caps sibling family, if we should build one for this source
+    fn smallcap_family(&self, source: &Font) -> Option<String> {
+        let options = self.options_for(source);
+        if !options.build_small_cap || !Self::has_smallcaps(source) {
+            return None;
+        }
+        let family = options
+            .family_name
+            .as_ref()
+            .or(source.names.family_name.get_default())?;
+        Some(format!("{family} SC"))
+    }
+
+    /// Whether the source already has italic styles, as instances or an italic axis
+    fn has_italics(source: &Font) -> bool {
+        source
+            .axes
+            .iter()
+            .any(|axis| axis.tag == "ital" || axis.tag == "slnt")
+            || source.instances.iter().any(|instance| {
+                instance
+                    .name
+                    .get_default()
+                    .is_some_and(|name| name.contains("Italic"))
+            })
+    }
+
+    /// Whether the source has small-cap glyphs (`a.sc`, `a.smcp` and so on)
+    fn has_smallcaps(source: &Font) -> bool {
+        source
+            .glyphs
+            .iter()
+            .any(|glyph| glyph.name.ends_with(".sc") || glyph.name.ends_with(".smcp"))
+    }
+
+    /// Turn a font into its small-caps sibling: small caps become the default
+    /// glyphs, and the family is renamed
+    fn smallcap_steps(builder: ConfigOperationBuilder, family: &str) -> ConfigOperationBuilder {
+        builder.remap_layout("smcp -> ccmp").rename(&RenameConfig {
+            name: family.to_string(),
+            just_family: true,
+        })
+    }
+
     fn has_slant_italic(&self, source: &Font) -> Option<ItalicDescriptor> {
         for axis in &source.axes {
             if axis.tag == "ital"
@@ -536,9 +1311,10 @@ impl GoogleFontsProvider {
 
     fn add_subset_steps(
         &self,
+        options: &GoogleFontsOptions,
         mut builder: ConfigOperationBuilder,
     ) -> Result<ConfigOperationBuilder, ApplicationError> {
-        for subset_options in &self.options.include_subsets {
+        for subset_options in &options.include_subsets {
             let donor_font = subset_options.obtain_donor_font()?;
             let codepoints = subset_options.subset.resolve()?;
             builder = builder.add_subset(
@@ -3,9 +3,10 @@ use crate::{
     operations::addsubset::{layout_handling_deser, layout_handling_ser},
 };
 use google_fonts_glyphsets::GLYPHSETS;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
 #[serde(untagged)]
 pub enum IncludeSubsetsSource {
     NamedSource(String),
@@ -36,13 +37,13 @@ impl IncludeSubsetsSource {
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
 pub struct UnicodeRange {
     pub start: u32,
     pub end: u32,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
 pub struct IncludeSubsetsCodepoints {
     #[serde(default)]
     pub name: Option<String>,
@@ -92,7 +93,7 @@ impl IncludeSubsetsCodepoints {
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
 pub struct IncludeSubsetsOptions {
     pub from: IncludeSubsetsSource,
     #[serde(flatten)]
@@ -102,6 +103,7 @@ pub struct IncludeSubsetsOptions {
         serialize_with = "layout_handling_ser",
         deserialize_with = "layout_handling_deser"
     )]
+    #[schemars(with = "String")]
     pub layout_handling: fontmerge::LayoutHandling,
     #[serde(default)]
     pub force: bool,
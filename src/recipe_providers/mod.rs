@@ -1,3 +1,486 @@
 pub(crate) mod googlefonts;
 pub(crate) mod includesubsets;
 pub(crate) mod noto;
+pub(crate) mod simple;
+
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, RwLock},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+
+use crate::{
+    error::ApplicationError,
+    recipe::{Provider, from_value_with_path},
+    recipe_providers::{
+        googlefonts::{GoogleFontsOptions, GoogleFontsProvider},
+        noto::{NotoOptions, NotoProvider},
+        simple::{SimpleOptions, SimpleProvider},
+    },
+};
+
+/// Builds a provider from the provider-specific keys of a config file
+pub type ProviderConstructor =
+    fn(&serde_yaml_ng::Value) -> Result<Box<dyn Provider>, ApplicationError>;
+
+/// The providers which `recipeProvider:` can name
+static PROVIDERS: LazyLock<RwLock<HashMap<String, ProviderConstructor>>> = LazyLock::new(|| {
+    let mut providers: HashMap<String, ProviderConstructor> = HashMap::new();
+    providers.insert("googlefonts".to_string(), googlefonts_provider);
+    providers.insert("noto".to_string(), noto_provider);
+    providers.insert("simple".to_string(), simple_provider);
+    RwLock::new(providers)
+});
+
+/// Make a provider available under the given `recipeProvider:` name
+///
+/// Registering a name which is already taken replaces the existing provider.
+pub fn register_provider(name: &str, constructor: ProviderConstructor) {
+    PROVIDERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.to_string(), constructor);
+}
+
+/// The names of all registered providers, sorted
+pub fn provider_names() -> Vec<String> {
+    let mut names: Vec<String> = PROVIDERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .keys()
+        .cloned()
+        .collect();
+    names.sort();
+    names
+}
+
+/// Construct the provider registered under `name` from the config's provider options
+pub(crate) fn make_provider(
+    name: &str,
+    raw_config: &serde_yaml_ng::Value,
+) -> Result<Box<dyn Provider>, ApplicationError> {
+    let constructor = PROVIDERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .copied()
+        .ok_or_else(|| {
+            ApplicationError::InvalidRecipe(format!(
+                "Unknown recipe provider '{name}'; known providers are {}",
+                provider_names().join(", ")
+            ))
+        })?;
+    constructor(raw_config)
+}
+
+/// Parse a provider's options out of the config, checking for keys it doesn't know
+///
+/// Unknown keys are usually typos, so we suggest the nearest known key. They
+/// are reported as warnings, or as errors if the config sets `strict: true`.
+pub fn parse_options<T: DeserializeOwned + JsonSchema>(
+    raw_config: &serde_yaml_ng::Value,
+    provider: &str,
+) -> Result<T, ApplicationError> {
+    let mut raw_config = raw_config.clone();
+    let strict = match raw_config.as_mapping_mut() {
+        Some(mapping) => match mapping.remove("strict") {
+            Some(serde_yaml_ng::Value::Bool(strict)) => strict,
+            Some(other) => {
+                return Err(ApplicationError::InvalidRecipe(format!(
+                    "strict: expected a boolean, found {other:?}"
+                )));
+            }
+            None => false,
+        },
+        None => false,
+    };
+    let problems = unknown_keys::<T>(&raw_config);
+    if strict && !problems.is_empty() {
+        return Err(ApplicationError::InvalidRecipe(problems.join("; ")));
+    }
+    for problem in problems {
+        log::warn!("{}", problem);
+    }
+    from_value_with_path(raw_config).map_err(|e: serde_yaml_ng::Error| {
+        ApplicationError::InvalidRecipe(format!(
+            "Failed to parse {provider} provider options: {}",
+            e
+        ))
+    })
+}
+
+/// Describe each top-level key of the config which isn't one of `T`'s options
+fn unknown_keys<T: JsonSchema>(raw_config: &serde_yaml_ng::Value) -> Vec<String> {
+    let schema = schemars::schema_for!(T);
+    let known: Vec<&str> = schema
+        .get("properties")
+        .and_then(Value::as_object)
+        .map(|properties| properties.keys().map(String::as_str).collect())
+        .unwrap_or_default();
+    let Some(mapping) = raw_config.as_mapping() else {
+        return vec![];
+    };
+    mapping
+        .keys()
+        .filter_map(|key| key.as_str())
+        .filter(|key| !known.contains(key))
+        .map(|key| {
+            let suggestion = known
+                .iter()
+                .map(|candidate| (edit_distance(key, candidate), candidate))
+                .filter(|(distance, _)| *distance <= 3)
+                .min()
+                .map(|(_, candidate)| format!("; did you mean `{candidate}`?"))
+                .unwrap_or_default();
+            format!("Unknown option `{key}`{suggestion}")
+        })
+        .collect()
+}
+
+/// The Levenshtein distance between two strings, ignoring case
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn googlefonts_provider(
+    raw_config: &serde_yaml_ng::Value,
+) -> Result<Box<dyn Provider>, ApplicationError> {
+    let options: GoogleFontsOptions = parse_options(raw_config, "GoogleFonts")?;
+    Ok(Box::new(GoogleFontsProvider::new(options)))
+}
+
+fn noto_provider(raw_config: &serde_yaml_ng::Value) -> Result<Box<dyn Provider>, ApplicationError> {
+    let options: NotoOptions = parse_options(raw_config, "Noto")?;
+    Ok(Box::new(NotoProvider::new(options)))
+}
+
+fn simple_provider(
+    raw_config: &serde_yaml_ng::Value,
+) -> Result<Box<dyn Provider>, ApplicationError> {
+    let options: SimpleOptions = parse_options(raw_config, "simple")?;
+    Ok(Box::new(SimpleProvider::new(options)))
+}
+
+/// A source as listed in a provider's `sources:`
+///
+/// Usually just a path (or glob pattern), but a source may also be given as a
+/// mapping with a `path` and any provider options which should be overridden
+/// for that source alone, e.g. `{path: Foo.glyphs, buildStatic: false}`.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum SourceSpec {
+    Path(String),
+    WithOverrides {
+        path: String,
+        #[serde(flatten)]
+        overrides: Map<String, Value>,
+    },
+}
+
+impl SourceSpec {
+    pub fn path(&self) -> &str {
+        match self {
+            SourceSpec::Path(path) => path,
+            SourceSpec::WithOverrides { path, .. } => path,
+        }
+    }
+
+    fn overrides(&self) -> Map<String, Value> {
+        match self {
+            SourceSpec::Path(_) => Map::new(),
+            SourceSpec::WithOverrides { overrides, .. } => overrides.clone(),
+        }
+    }
+}
+
+impl From<&str> for SourceSpec {
+    fn from(path: &str) -> Self {
+        SourceSpec::Path(path.to_string())
+    }
+}
+
+/// A concrete source file, with any per-source option overrides
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ResolvedSource {
+    pub path: String,
+    pub overrides: Map<String, Value>,
+}
+
+/// Apply per-source overrides on top of a provider's options.
+///
+/// The overrides use the same keys as the config file, so we round-trip the
+/// options through JSON rather than matching on field names by hand.
+pub(crate) fn apply_overrides<T>(
+    options: &T,
+    source: &ResolvedSource,
+) -> Result<T, ApplicationError>
+where
+    T: Serialize + for<'de> Deserialize<'de> + Clone,
+{
+    if source.overrides.is_empty() {
+        return Ok(options.clone());
+    }
+    let mut value = serde_json::to_value(options).map_err(|e| {
+        ApplicationError::InvalidRecipe(format!("Could not serialize provider options: {e}"))
+    })?;
+    if let Value::Object(map) = &mut value {
+        for (key, override_value) in &source.overrides {
+            map.insert(key.clone(), override_value.clone());
+        }
+    }
+    serde_json::from_value(value).map_err(|e| {
+        ApplicationError::InvalidRecipe(format!(
+            "Invalid option overrides for source {}: {e}",
+            source.path
+        ))
+    })
+}
+
+/// Source file extensions we look for when auto-discovering sources
+const SOURCE_EXTENSIONS: [&str; 3] = ["glyphs", "glyphspackage", "designspace"];
+
+/// Work out the list of source files a provider should load.
+///
+/// If no sources are given, they are discovered from the `sources/` directory,
+/// or failing that the current directory. Otherwise, any glob patterns (e.g.
+/// `sources/*.glyphs`) in the list are expanded, with every match sharing the
+/// pattern's overrides. Entries which name an existing file are kept as-is, even
+/// if they contain glob metacharacters. The matches for each pattern are sorted
+/// so that the order of sources, and hence of the generated recipe, is stable.
+pub(crate) fn resolve_sources(
+    sources: &[SourceSpec],
+) -> Result<Vec<ResolvedSource>, ApplicationError> {
+    if sources.is_empty() {
+        return Ok(discover_sources()?
+            .into_iter()
+            .map(|path| ResolvedSource {
+                path,
+                overrides: Map::new(),
+            })
+            .collect());
+    }
+    let mut expanded = vec![];
+    for spec in sources {
+        let source = spec.path();
+        let is_pattern = source.contains(['*', '?', '[']);
+        if !is_pattern || std::path::Path::new(source).exists() {
+            expanded.push(ResolvedSource {
+                path: source.to_string(),
+                overrides: spec.overrides(),
+            });
+            continue;
+        }
+        let mut matches = glob::glob(source)
+            .map_err(|e| {
+                ApplicationError::InvalidRecipe(format!("Invalid source pattern {source}: {e}"))
+            })?
+            .map(|entry| {
+                entry
+                    .map(|path| path.to_string_lossy().to_string())
+                    .map_err(|e| {
+                        ApplicationError::InvalidRecipe(format!(
+                            "Could not expand source pattern {source}: {e}"
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if matches.is_empty() {
+            return Err(ApplicationError::InvalidRecipe(format!(
+                "Source pattern {source} did not match any files"
+            )));
+        }
+        matches.sort();
+        expanded.extend(matches.into_iter().map(|path| ResolvedSource {
+            path,
+            overrides: spec.overrides(),
+        }));
+    }
+    Ok(expanded)
+}
+
+/// Find sources to build when the config doesn't list any.
+///
+/// Glyphs files and designspaces are always picked up. UFOs are only used if
+/// there is no designspace alongside them, since otherwise they are most likely
+/// the masters of that designspace.
+fn discover_sources() -> Result<Vec<String>, ApplicationError> {
+    for directory in ["sources", "."] {
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            continue;
+        };
+        let mut found = vec![];
+        let mut ufos = vec![];
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some(ext) if SOURCE_EXTENSIONS.contains(&ext) => found.push(path),
+                Some("ufo") => ufos.push(path),
+                _ => {}
+            }
+        }
+        let has_designspace = found
+            .iter()
+            .any(|path| path.extension().is_some_and(|ext| ext == "designspace"));
+        if !has_designspace {
+            found.extend(ufos);
+        }
+        if !found.is_empty() {
+            let mut found = found
+                .into_iter()
+                .map(|path| {
+                    path.strip_prefix(".")
+                        .unwrap_or(&path)
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .collect::<Vec<_>>();
+            found.sort();
+            log::info!(
+                "No sources specified; using {} from {}",
+                found.join(", "),
+                directory
+            );
+            return Ok(found);
+        }
+    }
+    Err(ApplicationError::InvalidRecipe(
+        "No sources were specified in the config, and none could be found in sources/ or the current directory".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recipe::{Config, Recipe, Step};
+
+    struct EmptyProvider;
+
+    impl Provider for EmptyProvider {
+        fn generate_recipe(&self) -> Result<Recipe, ApplicationError> {
+            Ok(Recipe::new())
+        }
+    }
+
+    fn empty_provider(_: &serde_yaml_ng::Value) -> Result<Box<dyn Provider>, ApplicationError> {
+        Ok(Box::new(EmptyProvider))
+    }
+
+    #[test]
+    fn test_simple_provider() {
+        let config: Config = serde_yaml_ng::from_str(
+            "recipeProvider: simple\nsources:\n  - sources/Foo.glyphs\n  - path: Bar.designspace\n    flattenComponents: true\n",
+        )
+        .unwrap();
+        let recipe = config.recipe().unwrap();
+        let mut targets = recipe.0.keys().cloned().collect::<Vec<_>>();
+        targets.sort();
+        assert_eq!(targets, vec!["Bar.ttf", "sources/Foo.ttf"]);
+        assert_eq!(recipe.0["sources/Foo.ttf"].0.len(), 2);
+        let Step::OperationStep { extra, .. } = &recipe.0["Bar.ttf"].0[1] else {
+            panic!("Expected a compile step");
+        };
+        assert_eq!(extra["flattenComponents"], Value::Bool(true));
+    }
+
+    #[test]
+    fn test_unknown_option_keys() {
+        let raw: serde_yaml_ng::Value =
+            serde_yaml_ng::from_str("sources: [Foo.glyphs]\nbuildWebFont: false\n").unwrap();
+        assert!(parse_options::<GoogleFontsOptions>(&raw, "GoogleFonts").is_ok());
+
+        let raw: serde_yaml_ng::Value =
+            serde_yaml_ng::from_str("sources: [Foo.glyphs]\nbuildWebFont: false\nstrict: true\n")
+                .unwrap();
+        let err = parse_options::<GoogleFontsOptions>(&raw, "GoogleFonts").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Recipe is not valid: Unknown option `buildWebFont`; did you mean `buildWebfont`?"
+        );
+
+        // Options flattened in from other structs are known too
+        let raw: serde_yaml_ng::Value =
+            serde_yaml_ng::from_str("flattenComponents: true\nstrict: true\n").unwrap();
+        assert!(parse_options::<GoogleFontsOptions>(&raw, "GoogleFonts").is_ok());
+    }
+
+    #[test]
+    fn test_register_provider() {
+        let config = "recipeProvider: emptytest\nsources: [Foo.glyphs]\n";
+        let err = serde_yaml_ng::from_str::<Config>(config)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            err.contains("Unknown recipe provider 'emptytest'"),
+            "Unexpected error: {err}"
+        );
+
+        register_provider("emptytest", empty_provider);
+        assert!(provider_names().contains(&"emptytest".to_string()));
+        let config: Config = serde_yaml_ng::from_str(config).unwrap();
+        assert!(config.recipe().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_sources() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in ["B.glyphs", "A.glyphs", "C.designspace"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let base = dir.path().to_string_lossy().to_string();
+        let expanded = resolve_sources(&[
+            SourceSpec::Path(format!("{base}/*.glyphs")),
+            SourceSpec::Path(format!("{base}/C.designspace")),
+        ])
+        .unwrap();
+        assert_eq!(
+            expanded
+                .into_iter()
+                .map(|source| source.path)
+                .collect::<Vec<_>>(),
+            vec![
+                format!("{base}/A.glyphs"),
+                format!("{base}/B.glyphs"),
+                format!("{base}/C.designspace"),
+            ]
+        );
+        assert!(resolve_sources(&[SourceSpec::Path(format!("{base}/*.ufo"))]).is_err());
+        // Anything babelfont loads will do, not just what fontc reads
+        assert_eq!(
+            resolve_sources(&[SourceSpec::from("Font.vfj")]).unwrap()[0].path,
+            "Font.vfj"
+        );
+    }
+
+    #[test]
+    fn test_per_source_overrides() {
+        let options: GoogleFontsOptions = serde_yaml_ng::from_str(
+            r#"
+sources:
+    - Text.glyphs
+    - path: Display.glyphs
+      buildStatic: false
+"#,
+        )
+        .unwrap();
+        let sources = resolve_sources(&options.sources).unwrap();
+        assert_eq!(sources[0].path, "Text.glyphs");
+        assert!(apply_overrides(&options, &sources[0]).unwrap().build_static);
+        assert_eq!(sources[1].path, "Display.glyphs");
+        assert!(!apply_overrides(&options, &sources[1]).unwrap().build_static);
+    }
+}
@@ -5,16 +5,24 @@ use fontdrasil::coords::UserLocation;
 
 use crate::{
     error::ApplicationError,
-    operations::{addsubset::AddSubsetConfig, fix::FixConfig, ConfigOperationBuilder, OpStep},
+    operations::{
+        addsubset::AddSubsetConfig, fix::FixConfig, ttfautohint::TTFAutohintConfig,
+        ConfigOperationBuilder, OpStep,
+    },
     recipe::{Provider, Recipe, Step},
-    recipe_providers::googlefonts::GoogleFontsOptions,
+    recipe_providers::{apply_overrides, googlefonts::GoogleFontsOptions, resolve_sources},
 };
 
 pub type NotoOptions = GoogleFontsOptions; // They're the same these days
 
+/// The buckets whose directories can be set in `outputs:`
+const OUTPUT_KINDS: [&str; 4] = ["unhinted", "hinted", "full", "googlefonts"];
+
 pub struct NotoProvider {
     options: NotoOptions,
     sources: Vec<Font>,
+    /// Options for sources which override some of the provider options, by source path
+    source_options: HashMap<String, NotoOptions>,
     recipe: Recipe,
     resolved_subset_steps: Vec<ResolvedSubsetStep>,
 }
@@ -33,17 +41,41 @@ impl NotoProvider {
         NotoProvider {
             options,
             sources: vec![],
+            source_options: HashMap::new(),
             recipe: Recipe::default(),
             resolved_subset_steps: vec![],
         }
     }
 
+    /// The options which apply to a given source, taking per-source overrides into account.
+    ///
+    /// Note that `includeSubsets` is resolved once for the whole family and can't be overridden.
+    fn options_for(&self, source: &Font) -> &NotoOptions {
+        source
+            .source
+            .as_ref()
+            .and_then(|path| self.source_options.get(&*path.to_string_lossy()))
+            .unwrap_or(&self.options)
+    }
+
     fn load_all_sources(&mut self) -> Result<(), ApplicationError> {
-        for source in &self.options.sources {
-            log::debug!("Loading source font: {}", source);
-            let font = babelfont::load(source).map_err(|e| {
-                ApplicationError::InvalidRecipe(format!("Failed to load source {source}: {e}"))
+        for source in resolve_sources(&self.options.sources)? {
+            log::debug!("Loading source font: {}", source.path);
+            let font = babelfont::load(&source.path).map_err(|e| {
+                ApplicationError::InvalidRecipe(format!(
+                    "Failed to load source {}: {e}",
+                    source.path
+                ))
             })?;
+            if !source.overrides.is_empty() {
+                let options = apply_overrides(&self.options, &source)?;
+                let path = font
+                    .source
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .unwrap_or(source.path);
+                self.source_options.insert(path, options);
+            }
             self.sources.push(font);
         }
         Ok(())
@@ -87,24 +119,50 @@ impl NotoProvider {
         tags
     }
 
-    fn variable_target(family: &str, bucket: &str, sourcebase: &str, axis_tags: &str) -> String {
-        format!("../fonts/{family}/{bucket}/variable-ttf/{sourcebase}[{axis_tags}].ttf")
+    /// The directory for a bucket of fonts; `outputs:` may override it, with
+    /// `$family` standing for the family's directory name
+    fn bucket_dir(&self, family: &str, bucket: &str) -> String {
+        match self.options.outputs.get(bucket) {
+            Some(dir) => dir.replace("$family", family),
+            None => format!("../fonts/{family}/{bucket}"),
+        }
+    }
+
+    fn variable_target(
+        &self,
+        family: &str,
+        bucket: &str,
+        sourcebase: &str,
+        axis_tags: &str,
+    ) -> String {
+        format!(
+            "{}/variable-ttf/{sourcebase}[{axis_tags}].ttf",
+            self.bucket_dir(family, bucket)
+        )
     }
 
-    fn static_target(family: &str, bucket: &str, instancebase: &str) -> String {
-        format!("../fonts/{family}/{bucket}/ttf/{instancebase}.ttf")
+    fn static_target(&self, family: &str, bucket: &str, instancebase: &str) -> String {
+        format!("{}/ttf/{instancebase}.ttf", self.bucket_dir(family, bucket))
     }
 
-    fn build_all_variables(&mut self) -> Result<(), ApplicationError> {
-        if !self.options.build_variable {
-            return Ok(());
+    /// Hint a static with ttfautohint if we were given arguments for it, and
+    /// with the built-in autohinter otherwise
+    fn autohint(options: &NotoOptions, builder: ConfigOperationBuilder) -> ConfigOperationBuilder {
+        match options.ttfautohint_args.as_deref() {
+            Some(args) => builder.ttfautohint(Some(args), &TTFAutohintConfig::default()),
+            None => builder.autohint(),
         }
+    }
 
+    fn build_all_variables(&mut self) -> Result<(), ApplicationError> {
         for source in self
             .sources
             .iter()
             .filter(|source| source.masters.len() >= 2)
         {
+            if !self.options_for(source).build_variable {
+                continue;
+            }
             self.recipe.extend(self.build_a_variable(source)?);
         }
         Ok(())
@@ -112,6 +170,7 @@ impl NotoProvider {
 
     fn build_a_variable(&self, source: &Font) -> Result<Recipe, ApplicationError> {
         let mut recipe = Recipe::new();
+        let options = self.options_for(source);
 
         let familyname_path = Self::familyname_path(source)?;
         let sourcebase = Self::sourcebase(source)?;
@@ -127,11 +186,12 @@ impl NotoProvider {
             .to_string_lossy()
             .to_string();
 
-        // Unhinted variable: compile + fix
+        // Unhinted variable: compile + dehint + fix
         let unhinted_target =
-            Self::variable_target(&familyname_path, "unhinted", &sourcebase, &axis_tags);
+            self.variable_target(&familyname_path, "unhinted", &sourcebase, &axis_tags);
         let mut builder = ConfigOperationBuilder::new().source(source_path.clone());
-        builder = builder.compile(&self.options.fontc_config);
+        builder = builder.compile(&options.fontc_config);
+        builder = builder.dehint();
         builder = builder.fix(&FixConfig::default());
         let unhinted_steps = builder.build();
         recipe.insert(unhinted_target.clone(), unhinted_steps.clone());
@@ -147,29 +207,29 @@ impl NotoProvider {
         if !self.options.include_subsets.is_empty() {
             // Full variable: addSubset + compile
             let full_target =
-                Self::variable_target(&familyname_path, "full", &sourcebase, &axis_tags);
+                self.variable_target(&familyname_path, "full", &sourcebase, &axis_tags);
             let mut full_builder = ConfigOperationBuilder::new().source(source_path.clone());
             full_builder = self.add_subset_steps(full_builder)?;
-            full_builder = full_builder.compile(&self.options.fontc_config);
+            full_builder = full_builder.compile(&options.fontc_config);
             let full_steps = full_builder.build();
             recipe.insert(full_target.clone(), full_steps.clone());
             add_slim(&mut recipe, &tags, &axis_tags, full_target, full_steps);
 
             // Googlefonts variable: addSubset + compile + fix
             let googlefonts_target =
-                Self::variable_target(&familyname_path, "googlefonts", &sourcebase, &axis_tags);
+                self.variable_target(&familyname_path, "googlefonts", &sourcebase, &axis_tags);
             let mut gf_builder = ConfigOperationBuilder::new().source(source_path);
             gf_builder = self.add_subset_steps(gf_builder)?;
-            gf_builder = gf_builder.compile(&self.options.fontc_config);
-            gf_builder = gf_builder.fix(&self.options.fix_config);
+            gf_builder = gf_builder.compile(&options.fontc_config);
+            gf_builder = gf_builder.fix(&options.fix_config);
             recipe.insert(googlefonts_target, gf_builder.build());
         } else {
             // Googlefonts variable without subset: compile + fix
             let googlefonts_target =
-                Self::variable_target(&familyname_path, "googlefonts", &sourcebase, &axis_tags);
+                self.variable_target(&familyname_path, "googlefonts", &sourcebase, &axis_tags);
             let mut gf_builder = ConfigOperationBuilder::new().source(source_path);
-            gf_builder = gf_builder.compile(&self.options.fontc_config);
-            gf_builder = gf_builder.fix(&self.options.fix_config);
+            gf_builder = gf_builder.compile(&options.fontc_config);
+            gf_builder = gf_builder.fix(&options.fix_config);
             recipe.insert(googlefonts_target, gf_builder.build());
         }
 
@@ -177,10 +237,10 @@ impl NotoProvider {
     }
 
     fn build_all_statics(&mut self) -> Result<(), ApplicationError> {
-        if !self.options.build_static {
-            return Ok(());
-        }
         for source in self.sources.iter() {
+            if !self.options_for(source).build_static {
+                continue;
+            }
             for instance in source.instances.iter() {
                 self.recipe.extend(self.build_a_static(source, instance)?);
             }
@@ -194,6 +254,7 @@ impl NotoProvider {
         instance: &Instance,
     ) -> Result<Recipe, ApplicationError> {
         let mut recipe = Recipe::new();
+        let options = self.options_for(source);
 
         let familyname_path = Self::familyname_path(source)?;
         let source_path = source
@@ -220,7 +281,7 @@ impl NotoProvider {
         .replace(" ", "");
 
         let mut base_builder = ConfigOperationBuilder::new().source(source_path.clone());
-        base_builder = base_builder.compile(&self.options.fontc_config);
+        base_builder = base_builder.compile(&options.fontc_config);
 
         if source.instances.len() > 1 {
             let loc: UserLocation = instance
@@ -245,17 +306,20 @@ impl NotoProvider {
         }
 
         // Unhinted static
-        let unhinted_target = Self::static_target(&familyname_path, "unhinted", &instancebase);
-        recipe.insert(unhinted_target, base_builder.clone().build());
+        let unhinted_target = self.static_target(&familyname_path, "unhinted", &instancebase);
+        recipe.insert(unhinted_target, base_builder.clone().dehint().build());
 
         // Hinted static
-        let hinted_target = Self::static_target(&familyname_path, "hinted", &instancebase);
-        recipe.insert(hinted_target, base_builder.clone().autohint().build());
+        let hinted_target = self.static_target(&familyname_path, "hinted", &instancebase);
+        recipe.insert(
+            hinted_target,
+            Self::autohint(options, base_builder.clone()).build(),
+        );
 
         if !self.options.include_subsets.is_empty() {
             let mut full_builder = ConfigOperationBuilder::new().source(source_path);
             full_builder = self.add_subset_steps(full_builder)?;
-            full_builder = full_builder.compile(&self.options.fontc_config);
+            full_builder = full_builder.compile(&options.fontc_config);
 
             if source.instances.len() > 1 {
                 let loc: UserLocation = instance
@@ -280,21 +344,24 @@ impl NotoProvider {
             }
 
             // Full static: addSubset + compile + instance + autohint
-            let full_target = Self::static_target(&familyname_path, "full", &instancebase);
-            recipe.insert(full_target, full_builder.clone().autohint().build());
+            let full_target = self.static_target(&familyname_path, "full", &instancebase);
+            recipe.insert(
+                full_target,
+                Self::autohint(options, full_builder.clone()).build(),
+            );
 
             // Googlefonts static: addSubset + compile + instance + autohint + fix
             let googlefonts_target =
-                Self::static_target(&familyname_path, "googlefonts", &instancebase);
-            let mut gf_builder = full_builder.autohint();
-            gf_builder = gf_builder.fix(&self.options.fix_config);
+                self.static_target(&familyname_path, "googlefonts", &instancebase);
+            let mut gf_builder = Self::autohint(options, full_builder);
+            gf_builder = gf_builder.fix(&options.fix_config);
             recipe.insert(googlefonts_target, gf_builder.build());
         } else {
             // Googlefonts static without subset: compile + instance + autohint + fix
             let googlefonts_target =
-                Self::static_target(&familyname_path, "googlefonts", &instancebase);
-            let mut gf_builder = base_builder.autohint();
-            gf_builder = gf_builder.fix(&self.options.fix_config);
+                self.static_target(&familyname_path, "googlefonts", &instancebase);
+            let mut gf_builder = Self::autohint(options, base_builder);
+            gf_builder = gf_builder.fix(&options.fix_config);
             recipe.insert(googlefonts_target, gf_builder.build());
         }
 
@@ -362,6 +429,7 @@ fn add_slim(
 
 impl Provider for NotoProvider {
     fn generate_recipe(&self) -> Result<Recipe, ApplicationError> {
+        self.options.check_outputs(&OUTPUT_KINDS);
         let mut provider = Self::new(self.options.clone());
         provider.load_all_sources()?;
         provider.resolve_subset_steps()?;
@@ -0,0 +1,51 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{
+    error::ApplicationError,
+    operations::{ConfigOperationBuilder, fontc::FontcConfig},
+    recipe::{Provider, Recipe},
+    recipe_providers::{SourceSpec, apply_overrides, resolve_sources},
+};
+
+/// Options for the simple provider, which compiles each source to a single
+/// font alongside it, with none of the Google Fonts naming or fixing
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SimpleOptions {
+    #[serde(default)]
+    pub sources: Vec<SourceSpec>,
+
+    // Fontc arguments
+    #[serde(flatten, default)]
+    pub fontc_config: FontcConfig,
+}
+
+pub struct SimpleProvider {
+    options: SimpleOptions,
+}
+
+impl SimpleProvider {
+    pub fn new(options: SimpleOptions) -> Self {
+        SimpleProvider { options }
+    }
+}
+
+impl Provider for SimpleProvider {
+    fn generate_recipe(&self) -> Result<Recipe, ApplicationError> {
+        let mut recipe = Recipe::new();
+        for source in resolve_sources(&self.options.sources)? {
+            let options = apply_overrides(&self.options, &source)?;
+            let target = Path::new(&source.path)
+                .with_extension("ttf")
+                .to_string_lossy()
+                .to_string();
+            let builder = ConfigOperationBuilder::new()
+                .source(source.path)
+                .compile(&options.fontc_config);
+            recipe.insert(target, builder.build());
+        }
+        Ok(recipe)
+    }
+}
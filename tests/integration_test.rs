@@ -69,6 +69,7 @@ async fn test_radio_canada_build() {
         #[cfg(feature = "graphviz")]
         draw_graph: false,
         ascii_graph: false,
+        ..Default::default()
     };
 
     // Run the build
@@ -101,6 +102,17 @@ async fn test_radio_canada_build() {
         "Italic WOFF2 webfont should be built"
     );
 
+    // Check statics, and that each one also gets a webfont
+    for style in ["Regular", "Medium", "SemiBold", "Bold"] {
+        let static_ttf = fonts_dir.join(format!("ttf/RadioCanadaDisplay-{style}.ttf"));
+        assert!(static_ttf.exists(), "{style} static should be built");
+        let static_woff2 = webfonts_dir.join(format!("RadioCanadaDisplay-{style}.woff2"));
+        assert!(
+            static_woff2.exists(),
+            "{style} static webfont should be built"
+        );
+    }
+
     // Verify WOFF2 files are smaller than TTF (compression working)
     let roman_ttf_size = roman_vf.metadata().unwrap().len();
     let roman_woff2_size = roman_woff2.metadata().unwrap().len();
@@ -148,6 +160,7 @@ async fn test_radio_canada_generate_recipe() {
         #[cfg(feature = "graphviz")]
         draw_graph: false,
         ascii_graph: false,
+        ..Default::default()
     };
 
     // Run recipe generation (should just print, not build)
@@ -192,6 +205,7 @@ async fn test_radio_canada_ascii_graph() {
         #[cfg(feature = "graphviz")]
         draw_graph: false,
         ascii_graph: true,
+        ..Default::default()
     };
 
     // Run ASCII graph generation